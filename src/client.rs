@@ -1,6 +1,8 @@
 use crate::editor::{EditorEvent, EditorIO};
 
 pub mod console;
+pub mod keymap;
+pub mod theme;
 
 pub trait ClientEvent<T>
 where
@@ -1,141 +1,780 @@
 use std::{
+    borrow::Cow,
     cmp,
-    io::{stdout, Cursor, Stdout},
+    io::{self, Stdout, Write},
+    iter,
+    ops::Range,
+    path::Path,
+    time::{Duration, Instant},
 };
 
 use crossterm::{
-    cursor::{self, Hide, MoveTo, SetCursorStyle},
-    event::{Event, KeyCode, KeyEvent, KeyEventKind},
+    cursor::{self, MoveTo, SetCursorStyle},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
+        KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+    },
     execute,
+    style::Stylize,
     terminal::{self, disable_raw_mode, enable_raw_mode, Clear, ClearType},
     ExecutableCommand,
 };
-use pad::PadStr;
 
 use crate::{
+    client::{keymap::KeyMap, theme::Theme},
     editor::{
-        vector::CharVectorEditor, Action, Container, EditorContentTrait, EditorEvent, Mode,
+        completion::Wildmenu,
+        layout::{Window, WindowId},
+        row_at, Action, Container, Editor, EditorContentTrait, EditorEvent, Highlight, Mode,
         Movement, Redraw,
     },
-    utils::TruncAt,
+    logger,
+    utils::{display_width, fit_width},
 };
 
 use super::ClientEvent;
 
-pub struct ConsoleClient {
-    stdout: Stdout,
+const SHOWCMD_WIDTH: usize = 10;
+// the `Ctrl-w` prefix of the window commands, while it waits for the next key
+const CTRL_W: char = '\x17';
+// lines scrolled per mouse wheel step
+const MOUSE_SCROLL: i32 = 3;
+
+/// Where the console client reads input from, so tests can script it.
+pub trait EventSource {
+    fn poll(&mut self, timeout: Duration) -> io::Result<bool>;
+    fn read(&mut self) -> io::Result<Event>;
+}
+
+pub struct TerminalEvents;
+
+impl EventSource for TerminalEvents {
+    fn poll(&mut self, timeout: Duration) -> io::Result<bool> {
+        event::poll(timeout)
+    }
+
+    fn read(&mut self) -> io::Result<Event> {
+        event::read()
+    }
+}
+
+/// What the console client measures idle time with, so tests can advance it
+/// together with a scripted `EventSource`.
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+pub struct ConsoleClient<E: EventSource = TerminalEvents, C: Clock = SystemClock, W: Write = Stdout>
+{
+    events: E,
+    clock: C,
+    out: W,
     line_numbered: bool,
+    size: (u16, u16),
+    // when the last event was read, idle timeouts count from there
+    last_input: Instant,
+    idle: bool,
+    // an autosave is due once input stops, at most once per pause
+    autosave_pending: bool,
+    // the terminal is only restored on drop when `load` set it up
+    loaded: bool,
+    keymap: KeyMap,
+    theme: Theme,
+    // width of the line number column, 0 without line numbers
+    gutter: u16,
+    // the part of the screen each window takes, and the one with the focus,
+    // as of the last resize
+    windows: Vec<(WindowId, Container)>,
+    focused: WindowId,
+    // where the text of the focused window starts on screen
+    origin: (u16, u16),
+    // laid out again since the last draw, which starts from a blank screen
+    relaid: bool,
+    // `tabstop` of the editor being drawn
+    tabstop: usize,
+    status_line: bool,
+    // `relativenumber` and cursor row the numbers were last drawn for
+    drawn_numbers: (bool, u32),
+    // row tinted by `cursorline` on screen
+    drawn_cursor_line: Option<u32>,
 }
 
-impl ConsoleClient {
-    pub fn new(line_numbered: bool) -> Self {
+impl<E: EventSource, C: Clock, W: Write> ConsoleClient<E, C, W> {
+    pub fn new(
+        line_numbered: bool,
+        events: E,
+        clock: C,
+        out: W,
+        keymap: KeyMap,
+        theme: Theme,
+    ) -> Self {
         Self {
-            stdout: stdout(),
+            events,
+            last_input: clock.now(),
+            clock,
+            out,
             line_numbered,
+            size: (0, 0),
+            idle: false,
+            autosave_pending: false,
+            loaded: false,
+            keymap,
+            theme,
+            gutter: 0,
+            windows: Vec::new(),
+            focused: 0,
+            origin: (0, 0),
+            relaid: false,
+            tabstop: 4,
+            status_line: false,
+            drawn_numbers: (false, 0),
+            drawn_cursor_line: None,
         }
     }
 
-    fn draw_line(&self, line_num: u32, content: String, len: u32) {
-        if self.line_numbered {
-            print!("{:>4}  ", line_num + 1);
+    /// Room for the largest line number plus two spaces, at least 4 digits wide.
+    fn gutter_width(&self, line_count: u32) -> u16 {
+        match self.line_numbered {
+            true => cmp::max(4, line_count.to_string().len() as u16) + 2,
+            false => 0,
         }
+    }
 
-        let striped_content = content.with_exact_width(len as usize);
+    /// The part of the screen above the command area, and below the tab
+    /// line when there are tab pages, each window takes, its status line
+    /// included.
+    fn window_areas<T: EditorContentTrait>(
+        &self,
+        context: &Editor<T>,
+    ) -> Vec<(WindowId, Container)> {
+        let (w, h) = self.size;
+        let area = Container {
+            top: (context.tab_count() > 1) as u32,
+            left: 0,
+            bottom: (h as u32).saturating_sub(context.options.cmdheight + 1),
+            right: (w as u32).saturating_sub(1),
+        };
+        context.windows.containers(&area)
+    }
+
+    /// Whether the gutter, the status lines or the windows are not laid out
+    /// for `context` anymore.
+    fn needs_resize<T: EditorContentTrait>(&self, context: &Editor<T>) -> bool {
+        self.gutter != self.gutter_width(context.line_count())
+            || self.status_line != (context.options.show_status() || context.windows.count() > 1)
+            || self.focused != context.windows.focused()
+            || self.windows != self.window_areas(context)
+    }
+
+    /// Lays the windows out again, the focused one getting the room left by its
+    /// gutter and status line; the screen is drawn anew.
+    fn resize<T: EditorContentTrait>(&mut self, context: &mut Editor<T>) {
+        self.gutter = self.gutter_width(context.line_count());
+        // split windows all have one, to tell them apart
+        self.status_line = context.options.show_status() || context.windows.count() > 1;
+        self.windows = self.window_areas(context);
+        self.focused = context.windows.focused();
+
+        let area = self
+            .windows
+            .iter()
+            .find(|(id, _)| *id == self.focused)
+            .map_or(Container::default(), |(_, area)| *area);
+        self.origin = (area.left as u16, area.top as u16);
+        self.relaid = true;
+
+        context.on_action(vec![Action::Resize(
+            (area.get_width() as u16 + 1).saturating_sub(self.gutter),
+            (area.get_height() as u16 + 1).saturating_sub(self.status_line as u16),
+        )]);
+    }
 
-        if cfg!(target_os = "windows") {
-            println!("{}", striped_content);
-        } else {
-            println!("{}\r", striped_content);
+    fn draw_line(
+        &mut self,
+        line_num: u32,
+        relative_to: Option<u32>,
+        content: String,
+        len: u32,
+        highlights: &[(Range<usize>, Highlight)],
+        left: usize,
+    ) {
+        self.draw_gutter(Some(line_num), relative_to);
+
+        // scrolled by screen columns, a tab cut by the left edge shows its rest
+        let width = len as usize;
+        let cells = expand_tabs(&content, self.tabstop)
+            .into_iter()
+            .skip(left)
+            .take(width)
+            .collect();
+
+        self.draw_cells(cells, width, content.chars().count(), highlights);
+    }
+
+    /// Prints the line number, or blanks for `None` (a wrapped row).
+    fn draw_gutter(&mut self, line_num: Option<u32>, relative_to: Option<u32>) {
+        if !self.line_numbered {
+            return;
+        }
+
+        let number = self.gutter_text(line_num, relative_to);
+        write!(self.out, "{}", self.theme.gutter_style().apply(number)).unwrap();
+    }
+
+    /// The line number column for `line_num`, blank for rows past the end.
+    fn gutter_text(&self, line_num: Option<u32>, relative_to: Option<u32>) -> String {
+        // the cursor line keeps its absolute number
+        let number = match (line_num, relative_to) {
+            (None, _) => String::new(),
+            (Some(line_num), Some(row)) if row != line_num => row.abs_diff(line_num).to_string(),
+            (Some(line_num), _) => (line_num + 1).to_string(),
+        };
+        format!("{:>1$}  ", number, self.gutter as usize - 2)
+    }
+
+    fn draw_cells(
+        &mut self,
+        mut cells: Vec<(char, usize)>,
+        width: usize,
+        line_len: usize,
+        highlights: &[(Range<usize>, Highlight)],
+    ) {
+        // padding keeps counting columns so the line break can be highlighted
+        let padding = (0..width - cells.len()).map(|i| (' ', line_len + i));
+        cells.extend(padding);
+
+        let striped_content = style_line(&cells, highlights, &self.theme);
+        write!(self.out, "{}", striped_content).unwrap();
+    }
+
+    /// Blanks a row past the end of the text, from the cursor.
+    fn draw_blank_row(&mut self, width: usize) {
+        let cells = self.gutter as usize + width;
+        write!(self.out, "{:1$}", "", cells).unwrap();
+    }
+
+    /// Redraws the lines `start..=end` that are inside the view.
+    fn draw_lines<T: EditorContentTrait>(&mut self, context: &Editor<T>, start: u32, end: u32) {
+        let view = &context.view;
+        let relative_to = context.options.relativenumber.then_some(context.cursor().0);
+        let (left, top) = self.origin;
+
+        for line_num in cmp::max(start, view.top)..=cmp::min(end, view.bottom) {
+            self.out
+                .execute(MoveTo(left, top + (line_num - view.top) as u16))
+                .unwrap();
+
+            match context.content.get_line(line_num) {
+                Some(line) => self.draw_line(
+                    line_num,
+                    relative_to,
+                    line,
+                    view.get_width(),
+                    &context.line_highlights(line_num),
+                    view.left as usize,
+                ),
+                None => self.draw_blank_row(view.get_width() as usize),
+            }
         }
     }
 
-    fn draw_cursor(&mut self, col: u32, row: u32, mode: Mode, view: &Container) {
-        let render_col = col - view.left;
-        let render_row = row - view.top;
+    /// Redraws the whole view with long lines broken into several rows.
+    fn draw_wrapped<T: EditorContentTrait>(&mut self, context: &Editor<T>) {
+        let view = &context.view;
+        let width = cmp::max(view.get_width() as usize, 1);
+        let screen_rows = view.get_height() + 1;
+        let relative_to = context.options.relativenumber.then_some(context.cursor().0);
+        let (left, top) = self.origin;
 
-        let carret = match mode {
+        self.out.execute(cursor::Hide).unwrap();
+
+        let mut screen_row = 0;
+        let mut line_num = view.top;
+        while let Some(line) = context.content.get_line(line_num) {
+            let cells = expand_tabs(&line, self.tabstop);
+            let highlights = context.line_highlights(line_num);
+            let line_len = line.chars().count();
+
+            let starts = context.row_starts(line_num);
+            for (row, start) in starts.iter().enumerate() {
+                if screen_row == screen_rows {
+                    return;
+                }
+
+                let end = starts.get(row + 1).map_or(cells.len(), |end| *end as usize);
+                let chunk = cells[*start as usize..end].iter().copied();
+                self.out
+                    .execute(MoveTo(left, top + screen_row as u16))
+                    .unwrap();
+                self.draw_gutter((row == 0).then_some(line_num), relative_to);
+                self.draw_cells(chunk.collect(), width, line_len, &highlights);
+                screen_row += 1;
+            }
+            line_num += 1;
+        }
+
+        for screen_row in screen_row..screen_rows {
+            self.out
+                .execute(MoveTo(left, top + screen_row as u16))
+                .unwrap();
+            self.draw_blank_row(width);
+        }
+    }
+
+    /// What `draw` redraws of the text: what the editor asks for, or all of it
+    /// when the line numbers change or wrapped rows may have moved.
+    fn text_redraw<T: EditorContentTrait>(
+        &self,
+        context: &Editor<T>,
+        renumber: bool,
+    ) -> Option<Redraw> {
+        let cursor_line = context.options.cursorline.then_some(context.cursor().0);
+        let retint = cursor_line != self.drawn_cursor_line;
+
+        // a wrapped line can grow by a row and push down everything below it,
+        // and the rows of the tinted lines are only known when drawing them all
+        match (renumber, context.options.wrap, context.should_redraw) {
+            (true, _, _) | (_, true, Some(_)) => Some(Redraw::All),
+            (_, true, None) if retint => Some(Redraw::All),
+            (false, _, redraw) => redraw,
+        }
+    }
+
+    fn draw_cursor<T: EditorContentTrait>(&mut self, context: &Editor<T>) {
+        let view = &context.view;
+        let (cursor_row, cursor_col) = context.render_cursor();
+        let mut render_row = cursor_row - view.top;
+
+        // tabs before the cursor take more than one column on screen
+        let mut render_col = context
+            .display_col(cursor_row, cursor_col)
+            .saturating_sub(view.left) as usize;
+
+        if context.options.wrap {
+            let starts = context.row_starts(cursor_row);
+            let above: u32 = (view.top..cursor_row)
+                .map(|row| context.display_rows(row))
+                .sum();
+
+            let row = row_at(&starts, render_col as u32);
+            render_row = above + row as u32;
+            render_col -= starts[row] as usize;
+        }
+
+        let carret = match context.mode() {
             Mode::Normal => SetCursorStyle::SteadyBlock,
             Mode::Insert => SetCursorStyle::BlinkingBar,
             Mode::Visual => SetCursorStyle::SteadyUnderScore,
+            Mode::Command => SetCursorStyle::BlinkingBar,
         };
 
+        let (left, top) = self.origin;
         execute!(
-            self.stdout,
+            self.out,
             cursor::Show,
             carret,
             cursor::MoveTo(
-                (render_col + if self.line_numbered { 6 } else { 0 }) as u16,
-                render_row as u16
+                left + render_col as u16 + self.gutter,
+                top + render_row as u16
             )
         )
         .unwrap();
     }
 
-    fn normal_mode_keybinding(&self, key: KeyEvent) -> Vec<Action> {
+    fn draw_command_line<T: EditorContentTrait>(&mut self, context: &Editor<T>) {
+        let (w, h) = self.size;
+        let width = cmp::max(w as usize, 1);
+        let height = context.options.cmdheight as usize;
+        let top = h.saturating_sub(height as u16);
+
+        let text: Vec<char> = match context.mode() {
+            Mode::Command => format!("{}{}", context.prompt, context.command),
+            _ => context.message.clone().unwrap_or_default(),
+        }
+        .chars()
+        .collect();
+
+        // long messages continue on the following rows of the command area
+        let mut rows: Vec<String> = text.chunks(width).map(|c| c.iter().collect()).collect();
+        rows.resize(height, String::new());
+
+        for (i, row) in rows.iter().enumerate() {
+            let mut line = fit_width(row, width);
+
+            if i == height - 1 && context.options.showcmd && context.mode() != Mode::Command {
+                let showcmd = fit_width(&context.pending_command(), SHOWCMD_WIDTH);
+                line = fit_width(&line, width.saturating_sub(SHOWCMD_WIDTH)) + &showcmd;
+            }
+
+            self.out.execute(MoveTo(0, top + i as u16)).unwrap();
+            write!(self.out, "{}", line).unwrap();
+        }
+
+        if context.mode() == Mode::Command {
+            // one cell per char, as they are drawn above
+            let end = context.command_cursor + 1;
+            let row = cmp::min(top + (end / width) as u16, h.saturating_sub(1));
+
+            execute!(
+                self.out,
+                cursor::Show,
+                SetCursorStyle::BlinkingBar,
+                cursor::MoveTo((end % width) as u16, row)
+            )
+            .unwrap();
+        }
+    }
+
+    /// The windows without the focus, their text as it is, without highlights
+    /// or wrapping, and the separators of the windows side by side.
+    fn draw_other_windows<T: EditorContentTrait>(&mut self, context: &Editor<T>) {
+        let screen_right = (self.size.0 as u32).saturating_sub(1);
+
+        for (id, area) in self.windows.clone() {
+            if area.right < screen_right {
+                for row in area.top..=area.bottom {
+                    self.out
+                        .execute(MoveTo(area.right as u16 + 1, row as u16))
+                        .unwrap();
+                    write!(self.out, "|").unwrap();
+                }
+            }
+
+            if let (false, Some((content, window))) =
+                (id == self.focused, context.window_content(id))
+            {
+                self.draw_window(context, content, window, area);
+            }
+        }
+    }
+
+    /// Draws `window` of `content` in `area`, above its status line.
+    fn draw_window<T: EditorContentTrait>(
+        &mut self,
+        context: &Editor<T>,
+        content: &T,
+        window: Window,
+        area: Container,
+    ) {
+        let width = (area.get_width() as usize).saturating_sub(self.gutter as usize);
+        let rows = (area.get_height() + 1).saturating_sub(self.status_line as u32);
+        let relative_to = context.options.relativenumber.then_some(window.cursor.0);
+        let (left, top) = window.scroll;
+
+        for row in 0..rows {
+            self.out
+                .execute(MoveTo(area.left as u16, (area.top + row) as u16))
+                .unwrap();
+
+            match content.get_line(top + row) {
+                Some(line) => self.draw_line(
+                    top + row,
+                    relative_to,
+                    line,
+                    width as u32,
+                    &[],
+                    left as usize,
+                ),
+                None => self.draw_blank_row(width),
+            }
+        }
+    }
+
+    /// The status line of each window, in the last row of its part of the
+    /// screen: file name on the left and cursor position on the right, the
+    /// mode in front of the name telling the focused one apart.
+    fn draw_status_lines<T: EditorContentTrait>(&mut self, context: &Editor<T>) {
+        for (id, area) in self.windows.clone() {
+            let width = area.get_width() as usize + 1;
+            let window = context.window_content(id).map(|(_, window)| window);
+            let line = match (window, context.window_position(id)) {
+                _ if id == self.focused => self.status_text(context, width),
+                (Some(window), Some(position)) => status_line(
+                    None,
+                    context.buffer_name(window.buffer),
+                    context.buffer_modified(window.buffer),
+                    position,
+                    width,
+                ),
+                _ => fit_width("", width),
+            };
+
+            self.out
+                .execute(MoveTo(area.left as u16, area.bottom as u16))
+                .unwrap();
+            write!(self.out, "{}", line.reverse()).unwrap();
+        }
+    }
+
+    /// Mode, file name and the position of the cursor as shown, 1-based.
+    fn status_text<T: EditorContentTrait>(&self, context: &Editor<T>, width: usize) -> String {
+        let mode = match context.mode() {
+            Mode::Normal => "NORMAL",
+            Mode::Insert => "INSERT",
+            Mode::Visual => "VISUAL",
+            Mode::Command => "COMMAND",
+        };
+        let (row, col) = context.render_cursor();
+        let path = context
+            .file_path()
+            .map_or(Cow::Borrowed("[No Name]"), Path::to_string_lossy);
+
+        status_line(
+            Some(mode),
+            &path,
+            context.modified,
+            (row + 1, col + 1),
+            width,
+        )
+    }
+
+    fn draw_wildmenu(&mut self, menu: &Wildmenu, cmdheight: u32) {
+        let (w, h) = self.size;
+        let width = w as usize;
+
+        // scroll the candidates so the selected one is always visible
+        let mut first = 0;
+        while menu.candidates[first..=menu.selected]
+            .iter()
+            .map(|c| c.chars().count() + 2)
+            .sum::<usize>()
+            > width
+            && first < menu.selected
+        {
+            first += 1;
+        }
+
+        let mut line = String::new();
+        let mut used = 0;
+        for (i, candidate) in menu.candidates.iter().enumerate().skip(first) {
+            let len = candidate.chars().count();
+            if used + len > width {
+                break;
+            }
+
+            line += &match i == menu.selected {
+                true => candidate.as_str().reverse().to_string(),
+                false => candidate.clone(),
+            };
+            line += "  ";
+            used += len + 2;
+        }
+        line += &" ".repeat(width.saturating_sub(used));
+
+        self.out
+            .execute(MoveTo(0, h.saturating_sub(cmdheight as u16 + 1)))
+            .unwrap();
+        write!(self.out, "{}", line).unwrap();
+    }
+
+    /// The tab pages on the top row, numbered from 1 and named after the file
+    /// of their focused window, the shown one highlighted.
+    fn draw_tab_line<T: EditorContentTrait>(&mut self, context: &Editor<T>) {
+        let width = self.size.0 as usize;
+        let (labels, current) = context.tab_labels();
+
+        let mut line = String::new();
+        let mut used = 0;
+        for (i, path) in labels.into_iter().enumerate() {
+            let name = Path::new(path)
+                .file_name()
+                .map_or(path.into(), |n| n.to_string_lossy());
+            let label = format!(" {} {} ", i + 1, name);
+            let len = label.chars().count();
+            if used + len > width {
+                break;
+            }
+
+            line += &match i == current {
+                true => label.reverse().to_string(),
+                false => label,
+            };
+            used += len;
+        }
+        line += &" ".repeat(width.saturating_sub(used));
+
+        self.out.execute(MoveTo(0, 0)).unwrap();
+        write!(self.out, "{}", line).unwrap();
+    }
+
+    fn draw_overlay(&mut self, lines: &[String]) {
+        let (w, h) = self.size;
+        let max_width = (w as usize).saturating_sub(4);
+        let max_height = (h as usize).saturating_sub(3);
+
+        let inner_width = lines
+            .iter()
+            .map(|l| l.chars().count())
+            .max()
+            .unwrap_or(0)
+            .min(max_width);
+        let shown = &lines[..lines.len().min(max_height)];
+
+        let left = ((w as usize).saturating_sub(inner_width + 4) / 2) as u16;
+        let top = ((h as usize).saturating_sub(shown.len() + 3) / 2) as u16;
+        let border = format!("+{}+", "-".repeat(inner_width + 2));
+
+        self.out.execute(cursor::Hide).unwrap();
+        self.out.execute(MoveTo(left, top)).unwrap();
+        write!(self.out, "{}", border).unwrap();
+
+        for (i, line) in shown.iter().enumerate() {
+            self.out.execute(MoveTo(left, top + 1 + i as u16)).unwrap();
+            write!(self.out, "| {} |", fit_width(line, inner_width)).unwrap();
+        }
+
+        self.out
+            .execute(MoveTo(left, top + 1 + shown.len() as u16))
+            .unwrap();
+        write!(self.out, "{}", border).unwrap();
+        self.out.flush().unwrap();
+    }
+
+    fn mouse_binding<T: EditorContentTrait>(
+        &self,
+        mouse: MouseEvent,
+        context: &Editor<T>,
+    ) -> Vec<Action> {
+        let view = &context.view;
+        // only the focused window takes clicks, counted from its top left
+        let (left, top) = self.origin;
+        let row = mouse.row.checked_sub(top).map(u32::from);
+        let col = mouse.column.checked_sub(left).map(u32::from);
+        let width = view.get_width() + self.gutter as u32;
+
+        match (mouse.kind, row, col) {
+            // a click on the gutter lands at the start of the screen row it is on,
+            // wrapped rows being mapped back to their line by `position_at`
+            (MouseEventKind::Down(MouseButton::Left), Some(row), Some(col))
+                if row <= view.get_height() && col <= width =>
+            {
+                let col = col.saturating_sub(self.gutter as u32);
+                let (row, col) = context.position_at(row, col);
+                vec![Action::GotoPosition(row, col)]
+            }
+            (MouseEventKind::ScrollDown, ..) => vec![Action::ScrollBy(MOUSE_SCROLL)],
+            (MouseEventKind::ScrollUp, ..) => vec![Action::ScrollBy(-MOUSE_SCROLL)],
+            _ => Vec::new(),
+        }
+    }
+
+    fn confirm_keybinding(&self, key: KeyEvent) -> Vec<Action> {
         match key.code {
-            KeyCode::Char('k') => vec![Action::Move(Movement::Up)],
-            KeyCode::Char('j') => vec![Action::Move(Movement::Down)],
-            KeyCode::Char('h') => vec![Action::Move(Movement::Left)],
-            KeyCode::Char('l') => vec![Action::Move(Movement::Right)],
-            KeyCode::Char('q') => vec![Action::Quit],
-            KeyCode::Char('i') => vec![Action::ChangeMode(Mode::Insert)],
-            KeyCode::Char('I') => vec![
-                Action::Move(Movement::LineStart),
-                Action::ChangeMode(Mode::Insert),
-            ],
-            KeyCode::Char('a') => vec![
-                Action::Move(Movement::Right),
-                Action::ChangeMode(Mode::Insert),
-            ],
-            KeyCode::Char('A') => vec![
-                Action::Move(Movement::LineEnd),
-                Action::ChangeMode(Mode::Insert),
-            ],
-            KeyCode::Char('s') => vec![Action::SaveFile],
-            KeyCode::PageDown => vec![Action::ScrollBy(1), Action::AskRedraw(Redraw::All)],
-            KeyCode::PageUp => vec![Action::ScrollBy(-1), Action::AskRedraw(Redraw::All)],
-            KeyCode::Backspace => vec![Action::Move(Movement::Left)],
-            KeyCode::Enter => vec![Action::Move(Movement::Down)],
-            KeyCode::Esc => vec![Action::Quit],
-            KeyCode::Up => vec![Action::Move(Movement::Up)],
-            KeyCode::Down => vec![Action::Move(Movement::Down)],
-            KeyCode::Left => vec![Action::Move(Movement::Left)],
-            KeyCode::Right => vec![Action::Move(Movement::Right)],
-            _ => vec![Action::None],
+            KeyCode::Char(c) => vec![Action::ConfirmAnswer(c)],
+            _ => vec![Action::ConfirmAnswer('c')],
         }
     }
 
-    fn insert_mode_keybinding(&self, key: KeyEvent) -> Vec<Action> {
+    fn command_mode_keybinding(
+        &self,
+        key: KeyEvent,
+        completing: bool,
+        readline: bool,
+    ) -> Vec<Action> {
+        if is_interrupt(key) {
+            return vec![Action::CancelCommand];
+        }
+
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            return match key.code {
+                KeyCode::Char('a') if readline => vec![Action::CommandMove(Movement::LineStart)],
+                KeyCode::Char('e') if readline => vec![Action::CommandMove(Movement::LineEnd)],
+                KeyCode::Char('b') if readline => vec![Action::CommandMove(Movement::Left)],
+                KeyCode::Char('f') if readline => vec![Action::CommandMove(Movement::Right)],
+                KeyCode::Char('w') if readline => vec![Action::CommandDeleteBack(false)],
+                KeyCode::Char('u') if readline => vec![Action::CommandDeleteBack(true)],
+                _ => vec![Action::None],
+            };
+        }
+
         match key.code {
-            KeyCode::Char(c) => vec![Action::InsertChar(c)],
-            KeyCode::Backspace => vec![Action::Backspace],
-            KeyCode::Delete => vec![Action::Delete],
-            KeyCode::Up => vec![Action::Move(Movement::Up)],
-            KeyCode::Down => vec![Action::Move(Movement::Down)],
-            KeyCode::Left => vec![Action::Move(Movement::Left)],
-            KeyCode::Right => vec![Action::Move(Movement::Right)],
-            KeyCode::Esc => vec![Action::ChangeMode(Mode::Normal)],
-            KeyCode::Enter => vec![Action::InsertChar('\n')],
+            KeyCode::Char(c) => vec![Action::CommandChar(c)],
+            KeyCode::Tab => vec![Action::CompleteCommand(true)],
+            KeyCode::BackTab => vec![Action::CompleteCommand(false)],
+            KeyCode::Right if completing => vec![Action::CompleteCommand(true)],
+            KeyCode::Left if completing => vec![Action::CompleteCommand(false)],
+            KeyCode::Right => vec![Action::CommandMove(Movement::Right)],
+            KeyCode::Left => vec![Action::CommandMove(Movement::Left)],
+            KeyCode::Backspace => vec![Action::CommandBackspace],
+            KeyCode::Enter => vec![Action::RunCommand],
+            KeyCode::Esc => vec![Action::CancelCommand],
             _ => vec![Action::None],
         }
     }
 }
 
-impl ClientEvent<CharVectorEditor> for ConsoleClient {
-    fn load(&mut self, context: &mut CharVectorEditor) {
+impl<T: EditorContentTrait, E: EventSource, C: Clock, W: Write> ClientEvent<Editor<T>>
+    for ConsoleClient<E, C, W>
+{
+    fn load(&mut self, context: &mut Editor<T>) {
+        self.loaded = true;
+        logger::start_capture();
         enable_raw_mode().unwrap();
+        execute!(self.out, EnableMouseCapture).unwrap();
 
-        let (w, h) = terminal::size().unwrap();
-        context.on_action(vec![Action::Resize(
-            if self.line_numbered { w - 6 } else { w },
-            h - 1,
-        )]);
+        self.size = terminal::size().unwrap();
+        self.resize(context);
 
-        execute!(self.stdout, Clear(ClearType::All)).unwrap();
+        execute!(self.out, Clear(ClearType::All)).unwrap();
     }
 
-    fn update(&mut self, context: &mut CharVectorEditor) -> Option<u8> {
-        let event = crossterm::event::read();
+    fn update(&mut self, context: &mut Editor<T>) -> Option<u8> {
+        // records logged while drawing are shown before waiting for input
+        if context.show_log() {
+            return None;
+        }
+
+        // the gutter grows or shrinks with the line count, e.g. past 9999 lines,
+        // and a command like `:set laststatus` changes the status lines
+        if self.needs_resize(context) {
+            self.resize(context);
+            return None;
+        }
+
+        // wake up for whichever of the idle and autosave deadlines comes first
+        let updatetime = Duration::from_millis(context.options.updatetime as u64);
+        let autosavetime = Duration::from_secs(context.options.autosavetime as u64);
+        let autosave = self.autosave_pending && context.options.autosave && context.modified;
+        let deadline = [
+            (!self.idle).then_some(updatetime),
+            autosave.then_some(autosavetime),
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+
+        if let Some(deadline) = deadline {
+            let waited = self.clock.now().duration_since(self.last_input);
+            if !self
+                .events
+                .poll(deadline.saturating_sub(waited))
+                .unwrap_or(true)
+            {
+                let waited = self.clock.now().duration_since(self.last_input);
+                if !self.idle && waited >= updatetime {
+                    self.idle = true;
+                    context.on_idle();
+                }
+                if autosave && waited >= autosavetime {
+                    self.autosave_pending = false;
+                    context.on_autosave();
+                }
+                return None;
+            }
+        }
+
+        self.idle = false;
+        self.autosave_pending = true;
+        let event = self.events.read();
+        self.last_input = self.clock.now();
 
         match event {
             Ok(Event::Key(key)) => {
@@ -143,80 +782,1249 @@ impl ClientEvent<CharVectorEditor> for ConsoleClient {
                     return None;
                 }
 
-                let actions = match context.mode {
-                    Mode::Normal => self.normal_mode_keybinding(key),
-                    Mode::Insert => self.insert_mode_keybinding(key),
-                    Mode::Visual => todo!(),
+                let remapped = match context.mode() {
+                    _ if context.overlay.is_some() || context.confirm.is_some() => None,
+                    _ if context.pending_op.is_some() => None,
+                    mode => self.keymap.get(mode, key),
                 };
 
+                let actions = remapped.unwrap_or_else(|| match context.mode() {
+                    _ if context.overlay.is_some() => vec![Action::DismissOverlay],
+                    _ if context.confirm.is_some() => self.confirm_keybinding(key),
+                    Mode::Normal => normal_mode_keybinding(key, context.pending_op),
+                    Mode::Insert => insert_mode_keybinding(key, context.options.readline),
+                    Mode::Visual => visual_mode_keybinding(key),
+                    Mode::Command => self.command_mode_keybinding(
+                        key,
+                        context.wildmenu.is_some(),
+                        context.options.readline,
+                    ),
+                });
+
                 context.on_action(actions);
+
+                if context.should_quit {
+                    return Some(0);
+                }
+
+                // split, closed or focused windows are drawn where they now are
+                if self.needs_resize(context) {
+                    self.resize(context);
+                }
+            }
+            Ok(Event::Mouse(mouse))
+                if context.overlay.is_none()
+                    && context.confirm.is_none()
+                    && context.mode() != Mode::Command =>
+            {
+                let actions = self.mouse_binding(mouse, context);
+
+                // every mouse move is reported too, those shouldn't clear the message
+                if !actions.is_empty() {
+                    context.on_action(actions);
+                }
+            }
+            Ok(Event::Resize(w, h)) => {
+                self.size = (w, h);
+                self.resize(context);
             }
-            Ok(Event::Resize(w, h)) => context.on_action(vec![Action::Resize(
-                if self.line_numbered { w - 6 } else { w },
-                h - 1,
-            )]),
             _ => (),
         }
 
         None
     }
 
-    fn draw(&mut self, context: &CharVectorEditor) {
-        if context.file_path.is_none() {
-            println!("no file provided!");
+    fn draw(&mut self, context: &Editor<T>) {
+        self.tabstop = context.options.tabstop as usize;
+
+        let cursor_row = context.cursor().0;
+        let numbers = (context.options.relativenumber, cursor_row);
+        let renumber = self.line_numbered
+            && numbers != self.drawn_numbers
+            && (numbers.0 || self.drawn_numbers.0);
+        self.drawn_numbers = numbers;
+
+        let redraw = match std::mem::take(&mut self.relaid) {
+            true => {
+                execute!(self.out, Clear(ClearType::All)).unwrap();
+                Some(Redraw::All)
+            }
+            false => self.text_redraw(context, renumber),
+        };
+
+        match redraw {
+            Some(Redraw::All) if context.options.wrap => self.draw_wrapped(context),
+            Some(Redraw::All) => {
+                self.out.execute(cursor::Hide).unwrap();
+                self.draw_lines(context, context.view.top, context.view.bottom);
+            }
+            Some(Redraw::Line(line_num)) => self.draw_lines(context, line_num, line_num),
+            Some(Redraw::Range(start, end)) => self.draw_lines(context, start, end),
+            None => (),
+        }
+
+        // the tint moves with the cursor, off the old row and onto the new one
+        let cursor_line = context.options.cursorline.then_some(cursor_row);
+        if cursor_line != self.drawn_cursor_line && redraw != Some(Redraw::All) {
+            for row in [self.drawn_cursor_line, cursor_line].into_iter().flatten() {
+                self.draw_lines(context, row, row);
+            }
+        }
+        self.drawn_cursor_line = cursor_line;
+
+        if self.windows.len() > 1 {
+            self.draw_other_windows(context);
+        }
+        if context.tab_count() > 1 {
+            self.draw_tab_line(context);
+        }
+        if self.status_line {
+            self.draw_status_lines(context);
+        }
+        if let (Some(menu), true) = (&context.wildmenu, context.options.wildmenu) {
+            self.draw_wildmenu(menu, context.options.cmdheight);
+        }
+        self.draw_command_line(context);
+
+        if let Some(lines) = &context.overlay {
+            self.draw_overlay(lines);
             return;
         }
 
-        let mut line_num = context.view.top;
+        if context.mode() != Mode::Command {
+            self.draw_cursor(context);
+        }
+    }
+}
 
-        match context.should_redraw {
-            Some(Redraw::All) => {
-                self.stdout
-                    .execute(MoveTo(0, 0))
-                    .unwrap()
-                    .execute(cursor::Hide)
-                    .unwrap();
-                while let Some(line) = context.content.get_line(line_num) {
-                    if line_num > context.view.bottom {
-                        break;
+/// `Ctrl-c`, which leaves insert, visual and command mode like `Esc`.
+fn is_interrupt(key: KeyEvent) -> bool {
+    key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c')
+}
+
+/// What `key` does in `mode` when the keymap file doesn't remap it, with no
+/// operator pending and the default options.
+pub fn default_binding(mode: Mode, key: KeyEvent) -> Vec<Action> {
+    match mode {
+        Mode::Normal => normal_mode_keybinding(key, None),
+        Mode::Insert => insert_mode_keybinding(key, false),
+        Mode::Visual => visual_mode_keybinding(key),
+        Mode::Command => vec![Action::None],
+    }
+}
+
+fn normal_mode_keybinding(key: KeyEvent, pending_op: Option<char>) -> Vec<Action> {
+    if let Some(op) = pending_op {
+        return operator_pending_keybinding(op, key);
+    }
+
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        return match key.code {
+            KeyCode::Char('r') => vec![Action::Redo],
+            KeyCode::Char('c') => vec![Action::Interrupt],
+            KeyCode::Char('d') => vec![Action::ScrollHalfPage(true)],
+            KeyCode::Char('u') => vec![Action::ScrollHalfPage(false)],
+            KeyCode::Char('w') => vec![Action::PushOperator(CTRL_W)],
+            _ => vec![Action::None],
+        };
+    }
+
+    match key.code {
+        KeyCode::Char('k') => vec![Action::Move(Movement::Up)],
+        KeyCode::Char('j') => vec![Action::Move(Movement::Down)],
+        KeyCode::Char('h') => vec![Action::Move(Movement::CharLeft('h'))],
+        KeyCode::Char('l') => vec![Action::Move(Movement::CharRight('l'))],
+        KeyCode::Char('q') => vec![Action::Quit],
+        KeyCode::Char('u') => vec![Action::Undo],
+        KeyCode::Char('w') => vec![Action::Move(Movement::WordForward)],
+        KeyCode::Char('b') => vec![Action::Move(Movement::WordBackward)],
+        KeyCode::Char('e') => vec![Action::Move(Movement::WordEnd)],
+        KeyCode::Char('i') => vec![Action::ChangeMode(Mode::Insert)],
+        KeyCode::Char('I') => vec![
+            Action::Move(Movement::LineStart),
+            Action::ChangeMode(Mode::Insert),
+        ],
+        KeyCode::Char('a') => vec![
+            Action::Move(Movement::Right),
+            Action::ChangeMode(Mode::Insert),
+        ],
+        KeyCode::Char('A') => vec![
+            Action::ChangeMode(Mode::Insert),
+            Action::Move(Movement::LineEnd),
+        ],
+        KeyCode::Char('s') => vec![Action::SaveFile],
+        KeyCode::Char('$') | KeyCode::End => vec![Action::Move(Movement::LineEnd)],
+        KeyCode::Home => vec![Action::Move(Movement::LineStart)],
+        KeyCode::Char('v') => vec![Action::ChangeMode(Mode::Visual)],
+        KeyCode::Char('%') => vec![Action::GotoPercent],
+        KeyCode::Char(':') => vec![Action::ChangeMode(Mode::Command)],
+        KeyCode::Char('/') => vec![Action::Search(true)],
+        KeyCode::Char('?') => vec![Action::Search(false)],
+        KeyCode::Char('n') => vec![Action::SearchNext(false)],
+        KeyCode::Char('N') => vec![Action::SearchNext(true)],
+        KeyCode::Char('g') => vec![Action::PushOperator('g')],
+        KeyCode::Char('G') => vec![Action::GotoLine(u32::MAX)],
+        KeyCode::Char('d') => vec![Action::PushOperator('d')],
+        KeyCode::Char('y') => vec![Action::PushOperator('y')],
+        KeyCode::Char('>') => vec![Action::PushOperator('>')],
+        KeyCode::Char('<') => vec![Action::PushOperator('<')],
+        KeyCode::Char('x') => vec![Action::DeleteChar(false)],
+        KeyCode::Char('X') => vec![Action::DeleteChar(true)],
+        KeyCode::Char('J') => vec![Action::JoinLines],
+        KeyCode::Char('p') => vec![Action::Paste(false)],
+        KeyCode::Char('P') => vec![Action::Paste(true)],
+        KeyCode::Char(c) if c.is_ascii_digit() => {
+            vec![Action::PushCount(c.to_digit(10).unwrap())]
+        }
+        KeyCode::PageDown => vec![Action::ScrollBy(1), Action::AskRedraw(Redraw::All)],
+        KeyCode::PageUp => vec![Action::ScrollBy(-1), Action::AskRedraw(Redraw::All)],
+        KeyCode::Backspace => vec![Action::Move(Movement::CharLeft('b'))],
+        KeyCode::Enter => vec![Action::OpenEntry],
+        KeyCode::Esc => vec![Action::Quit],
+        KeyCode::Up => vec![Action::Move(Movement::Up)],
+        KeyCode::Down => vec![Action::Move(Movement::Down)],
+        KeyCode::Left => vec![Action::Move(Movement::CharLeft('<'))],
+        KeyCode::Right => vec![Action::Move(Movement::CharRight('>'))],
+        KeyCode::Char(' ') => vec![Action::Move(Movement::CharRight('s'))],
+        _ => vec![Action::None],
+    }
+}
+
+/// A status line `width` wide: the mode, the file name and whether it has
+/// changes on the left, the 1-based `(row, col)` on the right.
+fn status_line(
+    mode: Option<&str>,
+    path: &str,
+    modified: bool,
+    (row, col): (u32, u32),
+    width: usize,
+) -> String {
+    let mode = mode.map_or(String::new(), |mode| format!(" {} ", mode));
+    let position = format!(" {}:{} ", row, col);
+    let modified = if modified { " [+]" } else { "" };
+
+    let room = width.saturating_sub(mode.len() + modified.len() + position.len() + 2);
+    let path: String = match path.chars().count() {
+        len if len > room => {
+            // keep the end of long paths, where the file name is
+            let tail: String = path.chars().skip(len - room + 1).collect();
+            format!("<{}", tail)
+        }
+        _ => path.to_string(),
+    };
+
+    let left = format!("{} {}{}", mode, path, modified);
+    let line = fit_width(&left, width.saturating_sub(position.len())) + &position;
+    fit_width(&line, width)
+}
+
+fn operator_pending_keybinding(op: char, key: KeyEvent) -> Vec<Action> {
+    match (op, key.code) {
+        ('g', KeyCode::Char('g')) => vec![Action::GotoLine(1)],
+        ('g', KeyCode::Char('f')) => vec![Action::GotoFile],
+        ('g', KeyCode::Char('a')) => vec![Action::ShowChar],
+        ('g', KeyCode::Char('0')) => vec![Action::Move(Movement::ScreenLineStart)],
+        ('g', KeyCode::Char('$')) => vec![Action::Move(Movement::ScreenLineEnd)],
+        ('g', KeyCode::Char('-')) => vec![Action::Travel(false)],
+        ('g', KeyCode::Char('+')) => vec![Action::Travel(true)],
+        ('g', KeyCode::Char('t')) => vec![Action::Tab(true)],
+        ('g', KeyCode::Char('T')) => vec![Action::Tab(false)],
+        ('d', KeyCode::Char('d')) => vec![Action::DeleteLine],
+        ('y', KeyCode::Char('y')) => vec![Action::Yank],
+        ('>', KeyCode::Char('>')) => vec![Action::ShiftLines(true)],
+        ('<', KeyCode::Char('<')) => vec![Action::ShiftLines(false)],
+        // `Ctrl-w Ctrl-s` is `Ctrl-w s` too
+        (CTRL_W, KeyCode::Char(c)) => vec![Action::Window(c)],
+        _ => vec![Action::None],
+    }
+}
+
+fn visual_mode_keybinding(key: KeyEvent) -> Vec<Action> {
+    if is_interrupt(key) {
+        return vec![Action::ChangeMode(Mode::Normal)];
+    }
+
+    match key.code {
+        KeyCode::Char('k') | KeyCode::Up => vec![Action::Move(Movement::Up)],
+        KeyCode::Char('j') | KeyCode::Down => vec![Action::Move(Movement::Down)],
+        KeyCode::Char('h') | KeyCode::Left => vec![Action::Move(Movement::CharLeft('h'))],
+        KeyCode::Char('l') | KeyCode::Right => vec![Action::Move(Movement::CharRight('l'))],
+        KeyCode::Char('$') | KeyCode::End => vec![Action::Move(Movement::LineEnd)],
+        KeyCode::Char('0') | KeyCode::Home => vec![Action::Move(Movement::LineStart)],
+        KeyCode::Char('w') => vec![Action::Move(Movement::WordForward)],
+        KeyCode::Char('b') => vec![Action::Move(Movement::WordBackward)],
+        KeyCode::Char('e') => vec![Action::Move(Movement::WordEnd)],
+        KeyCode::Char(':') => vec![Action::ChangeMode(Mode::Command)],
+        KeyCode::Char('y') => vec![Action::Yank, Action::ChangeMode(Mode::Normal)],
+        KeyCode::Char('d') | KeyCode::Char('x') => vec![Action::DeleteSelection],
+        // without block selections `O` is the same as `o`
+        KeyCode::Char('o') | KeyCode::Char('O') => vec![Action::SwapSelectionEnds],
+        KeyCode::Char('v') | KeyCode::Esc => vec![Action::ChangeMode(Mode::Normal)],
+        _ => vec![Action::None],
+    }
+}
+
+fn insert_mode_keybinding(key: KeyEvent, readline: bool) -> Vec<Action> {
+    if is_interrupt(key) {
+        return vec![Action::ChangeMode(Mode::Normal)];
+    }
+
+    if readline && key.modifiers.contains(KeyModifiers::CONTROL) {
+        return match key.code {
+            KeyCode::Char('a') => vec![Action::Move(Movement::LineStart)],
+            KeyCode::Char('e') => vec![Action::Move(Movement::LineEnd)],
+            KeyCode::Char('b') => vec![Action::Move(Movement::CharLeft('['))],
+            KeyCode::Char('f') => vec![Action::Move(Movement::CharRight(']'))],
+            _ => vec![Action::None],
+        };
+    }
+
+    match key.code {
+        KeyCode::Char(c) => vec![Action::InsertChar(c)],
+        KeyCode::Backspace => vec![Action::Backspace],
+        KeyCode::Delete => vec![Action::Delete],
+        KeyCode::Up => vec![Action::Move(Movement::Up)],
+        KeyCode::Down => vec![Action::Move(Movement::Down)],
+        KeyCode::Left => vec![Action::Move(Movement::CharLeft('['))],
+        KeyCode::Right => vec![Action::Move(Movement::CharRight(']'))],
+        KeyCode::Home => vec![Action::Move(Movement::LineStart)],
+        KeyCode::End => vec![Action::Move(Movement::LineEnd)],
+        KeyCode::Esc => vec![Action::ChangeMode(Mode::Normal)],
+        KeyCode::Enter => vec![Action::InsertChar('\n')],
+        KeyCode::Tab => vec![Action::InsertChar('\t')],
+        _ => vec![Action::None],
+    }
+}
+
+/// Splits `text` into screen cells, each with the index of the char it shows;
+/// a tab fills the cells up to the next tab stop.
+fn expand_tabs(text: &str, tabstop: usize) -> Vec<(char, usize)> {
+    let mut cells = Vec::new();
+
+    for (i, c) in text.chars().enumerate() {
+        match c {
+            '\t' => {
+                let width = display_width("\t", cells.len(), tabstop);
+                cells.extend(iter::repeat_n((' ', i), width));
+            }
+            c => cells.push((c, i)),
+        }
+    }
+
+    cells
+}
+
+// applies highlights, given in line columns, to the visible cells of a line
+fn style_line(
+    cells: &[(char, usize)],
+    highlights: &[(Range<usize>, Highlight)],
+    theme: &Theme,
+) -> String {
+    let mut styled = String::new();
+    let mut plain = String::new();
+    let mut current: (Option<Highlight>, bool) = (None, false);
+
+    for &(c, i) in cells {
+        let covers = |range: &Range<usize>| range.contains(&i);
+        // the cursor line is a background under the other highlights
+        let highlight = highlights
+            .iter()
+            .rev()
+            .find(|(range, highlight)| *highlight != Highlight::CursorLine && covers(range))
+            .map(|(_, highlight)| *highlight);
+        let cursor_line = highlights
+            .iter()
+            .any(|(range, highlight)| *highlight == Highlight::CursorLine && covers(range));
+
+        if (highlight, cursor_line) != current {
+            styled += &theme
+                .style(current.0, current.1)
+                .apply(plain.as_str())
+                .to_string();
+            plain.clear();
+            current = (highlight, cursor_line);
+        }
+
+        plain.push(c);
+    }
+
+    styled += &theme
+        .style(current.0, current.1)
+        .apply(plain.as_str())
+        .to_string();
+
+    styled
+}
+
+impl<E: EventSource, C: Clock, W: Write> Drop for ConsoleClient<E, C, W> {
+    fn drop(&mut self) {
+        if !self.loaded {
+            return;
+        }
+
+        execute!(self.out, DisableMouseCapture).unwrap();
+        disable_raw_mode().unwrap();
+
+        // records logged after the last draw would be lost otherwise
+        for record in logger::stop_capture() {
+            println!("{}", record);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, collections::VecDeque, fs, rc::Rc};
+
+    use super::*;
+    use crate::editor::vector::CharVectorEditor;
+
+    // time shared by a scripted event source and its clock
+    type Elapsed = Rc<Cell<Duration>>;
+
+    struct ScriptedEvents {
+        events: VecDeque<Event>,
+        elapsed: Elapsed,
+    }
+
+    // waiting with nothing left to read lets the whole timeout pass
+    impl EventSource for ScriptedEvents {
+        fn poll(&mut self, timeout: Duration) -> io::Result<bool> {
+            if self.events.is_empty() {
+                self.elapsed.set(self.elapsed.get() + timeout);
+            }
+            Ok(!self.events.is_empty())
+        }
+
+        fn read(&mut self) -> io::Result<Event> {
+            self.events
+                .pop_front()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "no more events"))
+        }
+    }
+
+    struct FakeClock {
+        start: Instant,
+        elapsed: Elapsed,
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            self.start + self.elapsed.get()
+        }
+    }
+
+    // draws into a buffer instead of the terminal
+    type TestClient = ConsoleClient<ScriptedEvents, FakeClock, Vec<u8>>;
+
+    fn client() -> (TestClient, Elapsed) {
+        let elapsed = Elapsed::default();
+        let events = ScriptedEvents {
+            events: VecDeque::new(),
+            elapsed: elapsed.clone(),
+        };
+        let clock = FakeClock {
+            start: Instant::now(),
+            elapsed: elapsed.clone(),
+        };
+
+        let mut client = ConsoleClient::new(
+            false,
+            events,
+            clock,
+            Vec::new(),
+            KeyMap::default(),
+            Theme::default(),
+        );
+        client.size = (80, 24);
+        (client, elapsed)
+    }
+
+    fn editor() -> CharVectorEditor {
+        let mut editor = Editor::new();
+        editor.on_action(vec![Action::Resize(80, 22)]);
+        editor
+    }
+
+    // queues `keys` as key presses, `\x1b` for Esc, `\n` for Enter and `\t` for Tab
+    fn send_keys(client: &mut TestClient, keys: &str) {
+        client.events.events.extend(keys.chars().map(|c| {
+            let code = match c {
+                '\x1b' => KeyCode::Esc,
+                '\n' => KeyCode::Enter,
+                '\t' => KeyCode::Tab,
+                c => KeyCode::Char(c),
+            };
+            Event::Key(KeyEvent::new(code, KeyModifiers::NONE))
+        }));
+    }
+
+    fn send_ctrl(client: &mut TestClient, c: char) {
+        let key = KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+        client.events.events.push_back(Event::Key(key));
+    }
+
+    // runs the client until it has read every queued event
+    fn run_queued(client: &mut TestClient, editor: &mut CharVectorEditor) {
+        while !client.events.events.is_empty() {
+            client.update(editor);
+        }
+    }
+
+    fn screen(client: &TestClient) -> Vec<String> {
+        replay(client).0
+    }
+
+    // replays what the client drew on a blank screen, keeping the text and the
+    // cursor moves and clears but no styles; the cursor is where it was left
+    fn replay(client: &TestClient) -> (Vec<String>, (usize, usize)) {
+        let (w, h) = client.size;
+        let mut cells = vec![vec![' '; w as usize]; h as usize];
+        let (mut row, mut col) = (0, 0);
+
+        let drawn = String::from_utf8_lossy(&client.out);
+        let mut chars = drawn.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                // `ESC [ params command`, which is all crossterm sends
+                '\x1b' => {
+                    chars.next();
+                    let mut params = String::new();
+                    let command = chars.by_ref().find(|c| {
+                        params.push(*c);
+                        c.is_ascii_alphabetic()
+                    });
+                    params.pop();
+
+                    let mut numbers = params.split(';').map(|n| n.parse::<usize>().ok());
+                    match (command, numbers.next().flatten()) {
+                        (Some('H'), first) => {
+                            row = first.unwrap_or(1) - 1;
+                            col = numbers.next().flatten().unwrap_or(1) - 1;
+                        }
+                        // the whole line with 2, from the cursor otherwise
+                        (Some('K'), whole) => {
+                            if let Some(line) = cells.get_mut(row) {
+                                let from = if whole == Some(2) { 0 } else { col };
+                                let from = cmp::min(from, line.len());
+                                line[from..].fill(' ');
+                            }
+                        }
+                        // the whole screen with 2, from the cursor down otherwise
+                        (Some('J'), whole) => {
+                            let whole = whole == Some(2);
+                            for (i, line) in cells.iter_mut().enumerate() {
+                                let from = match i.cmp(&row) {
+                                    _ if whole => 0,
+                                    cmp::Ordering::Less => continue,
+                                    cmp::Ordering::Equal => cmp::min(col, line.len()),
+                                    cmp::Ordering::Greater => 0,
+                                };
+                                line[from..].fill(' ');
+                            }
+                        }
+                        _ => (),
                     }
-                    self.draw_line(
-                        line_num,
-                        line.trucate_at(context.view.left as usize)
-                            .unwrap_or(String::default()),
-                        context.view.get_width(),
-                    );
-                    line_num += 1;
                 }
-            }
-            Some(Redraw::Line(line_num)) => {
-                if let Some(line) = context.content.get_line(line_num) {
-                    let clear_row = cmp::max(0, line_num as i32 - context.view.top as i32);
-                    self.stdout.execute(MoveTo(0, clear_row as u16)).unwrap();
-
-                    self.draw_line(
-                        line_num,
-                        line.trucate_at(context.view.left as usize)
-                            .unwrap_or(String::default()),
-                        context.view.get_width(),
-                    );
+                '\r' => col = 0,
+                '\n' => row += 1,
+                c => {
+                    if let Some(cell) = cells.get_mut(row).and_then(|r| r.get_mut(col)) {
+                        *cell = c;
+                    }
+                    col += 1;
                 }
             }
-            Some(Redraw::Range(_, _)) => todo!(),
-            None => (),
         }
 
-        self.draw_cursor(
-            context.render_col,
-            context.render_row,
-            context.mode,
-            &context.view,
+        let rows = cells
+            .into_iter()
+            .map(|row| row.into_iter().collect::<String>().trim_end().to_string())
+            .collect();
+        (rows, (row, col))
+    }
+
+    #[test]
+    fn autosave_waits_for_autosavetime() {
+        let dir =
+            std::env::temp_dir().join(format!("rust-text-editor-idle-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notes.txt");
+        fs::write(&path, "x\n").unwrap();
+
+        let (mut client, elapsed) = client();
+        let mut editor = editor();
+        editor.on_load_file(path.to_string_lossy().into_owned());
+        editor.options.autosave = true;
+
+        send_keys(&mut client, "ihi\x1b");
+        run_queued(&mut client, &mut editor);
+        assert_eq!(elapsed.get(), Duration::ZERO);
+        assert!(editor.modified);
+
+        // idle after `updatetime`, but not saved before `autosavetime`
+        client.update(&mut editor);
+        assert_eq!(elapsed.get(), Duration::from_millis(4000));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "x\n");
+
+        client.update(&mut editor);
+        assert_eq!(elapsed.get(), Duration::from_secs(5));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "hix\n");
+        assert!(!editor.modified);
+
+        // each edit after a save starts another wait
+        send_keys(&mut client, "a!\x1b");
+        run_queued(&mut client, &mut editor);
+        assert!(editor.modified);
+        client.update(&mut editor);
+        client.update(&mut editor);
+        assert_eq!(elapsed.get(), Duration::from_secs(10));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "hix!\n");
+        assert!(!editor.modified);
+    }
+
+    #[test]
+    fn relative_numbers_count_from_the_cursor_line() {
+        let (mut client, _) = client();
+        client.line_numbered = true;
+        client.gutter = client.gutter_width(20);
+
+        // the cursor on line 10
+        let cursor = Some(9);
+        assert_eq!(client.gutter_text(Some(9), cursor), "  10  ");
+        assert_eq!(client.gutter_text(Some(0), cursor), "   9  ");
+        assert_eq!(client.gutter_text(Some(19), cursor), "  10  ");
+        assert_eq!(client.gutter_text(Some(10), None), "  11  ");
+        assert_eq!(client.gutter_text(None, cursor), "      ");
+    }
+
+    #[test]
+    fn gutter_grows_past_four_digits() {
+        let (mut client, _) = client();
+        assert_eq!(client.gutter_width(12_000), 0);
+
+        client.line_numbered = true;
+        assert_eq!(client.gutter_width(20), 6);
+        assert_eq!(client.gutter_width(9_999), 6);
+        assert_eq!(client.gutter_width(12_000), 7);
+
+        // the editor gets what the gutter leaves of the width
+        let mut editor = editor();
+        editor.load_data("line\n".repeat(12_000).into_bytes());
+        client.resize(&mut editor);
+        assert_eq!(client.gutter, 7);
+        assert_eq!(editor.view.right - editor.view.left + 1, 80 - 7);
+    }
+
+    #[test]
+    fn status_line_shows_the_column_on_screen() {
+        let (client, _) = client();
+        let mut editor = editor();
+        editor.load_data(b"a long line\nab\n".to_vec());
+        assert!(editor.options.show_status());
+
+        editor.on_action(vec![Action::Move(Movement::LineEnd)]);
+        editor.on_action(vec![Action::Move(Movement::Down)]);
+
+        let status = client.status_text(&editor, 80);
+        // at the end of the short line, not at the column kept for moving on
+        assert_eq!(editor.render_cursor(), (1, 2));
+        assert!(status.starts_with(" NORMAL  [No Name] "), "{:?}", status);
+        assert!(status.ends_with(" 2:3 "));
+        assert_eq!(status.chars().count(), 80);
+    }
+
+    #[test]
+    fn click_lands_right_of_the_gutter() {
+        let (mut client, _) = client();
+        client.line_numbered = true;
+        client.gutter = 6;
+        let mut editor = editor();
+        editor
+            .content
+            .load_data(b"first\nsecond\nthird\nfourth\n".to_vec());
+
+        let click = MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column: 8,
+            row: 3,
+            modifiers: KeyModifiers::NONE,
+        };
+        let actions = client.mouse_binding(click, &editor);
+        assert!(matches!(actions[..], [Action::GotoPosition(3, 2)]));
+    }
+
+    #[test]
+    fn click_after_a_tab_lands_on_the_drawn_char() {
+        let (client, _) = client();
+        let mut editor = editor();
+        editor.content.load_data(b"\tabc".to_vec());
+
+        // the tab fills columns 0..4, so column 5 shows `b`
+        let click = MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column: 5,
+            row: 0,
+            modifiers: KeyModifiers::NONE,
+        };
+        let actions = client.mouse_binding(click, &editor);
+        assert!(matches!(actions[..], [Action::GotoPosition(0, 2)]));
+    }
+
+    #[test]
+    fn typed_keys_reach_the_buffer_and_redraw_their_line() {
+        let (mut client, _) = client();
+        let mut editor = editor();
+        editor.load_data(b"one\ntwo\n".to_vec());
+        client.resize(&mut editor);
+        client.draw(&editor);
+        let rows = screen(&client);
+        assert_eq!(rows[..3], ["one", "two", ""]);
+        assert!(
+            rows[22].starts_with(" NORMAL  [No Name] "),
+            "{:?}",
+            rows[22]
+        );
+
+        send_keys(&mut client, "ihi");
+        run_queued(&mut client, &mut editor);
+
+        assert_eq!(editor.content.get_line(0).as_deref(), Some("hione"));
+        assert_eq!(editor.render_cursor(), (0, 2));
+        assert_eq!(editor.mode(), Mode::Insert);
+        assert_eq!(editor.should_redraw, Some(Redraw::Line(0)));
+
+        // only the typed line and the status line are drawn again
+        client.out.clear();
+        client.draw(&editor);
+        let rows = screen(&client);
+        assert_eq!(rows[..2], ["hione", ""]);
+        assert!(
+            rows[22].starts_with(" INSERT  [No Name] [+]"),
+            "{:?}",
+            rows[22]
+        );
+    }
+
+    #[test]
+    fn line_end_keys_stop_on_or_past_the_last_char() {
+        let (mut client, _) = client();
+        let mut editor = editor();
+        editor.load_data(b"0123456789".to_vec());
+        let key = |code| Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+
+        send_keys(&mut client, "$");
+        run_queued(&mut client, &mut editor);
+        assert_eq!(editor.render_cursor(), (0, 9));
+
+        send_keys(&mut client, "0");
+        run_queued(&mut client, &mut editor);
+        assert_eq!(editor.render_cursor(), (0, 0));
+
+        client.events.events.push_back(key(KeyCode::End));
+        run_queued(&mut client, &mut editor);
+        assert_eq!(editor.render_cursor(), (0, 9));
+
+        send_keys(&mut client, "i");
+        client.events.events.push_back(key(KeyCode::End));
+        run_queued(&mut client, &mut editor);
+        assert_eq!(editor.render_cursor(), (0, 10));
+
+        client.events.events.push_back(key(KeyCode::Home));
+        run_queued(&mut client, &mut editor);
+        assert_eq!(editor.render_cursor(), (0, 0));
+    }
+
+    #[test]
+    fn counts_repeat_motions_and_zero_only_counts_after_a_digit() {
+        let (mut client, _) = client();
+        let mut editor = editor();
+        let text: String = (1..=30).map(|i| format!("line {}\n", i)).collect();
+        editor.load_data(text.into_bytes());
+
+        send_keys(&mut client, "5j");
+        run_queued(&mut client, &mut editor);
+        assert_eq!(editor.render_cursor(), (5, 0));
+
+        // `line 6` has 6 chars, `l` stops at its end instead of wrapping
+        send_keys(&mut client, "10l");
+        run_queued(&mut client, &mut editor);
+        assert_eq!(editor.render_cursor(), (5, 6));
+
+        send_keys(&mut client, "0");
+        run_queued(&mut client, &mut editor);
+        assert_eq!(editor.render_cursor(), (5, 0));
+
+        send_keys(&mut client, "10j");
+        run_queued(&mut client, &mut editor);
+        assert_eq!(editor.render_cursor(), (15, 0));
+    }
+
+    #[test]
+    fn moving_the_wrapped_cursor_line_redraws_everything() {
+        let (mut client, _) = client();
+        let mut editor = editor();
+        editor.load_data(b"one\ntwo\n".to_vec());
+        editor.options.cursorline = true;
+        client.drawn_cursor_line = Some(0);
+
+        editor.on_action(vec![Action::Move(Movement::Down)]);
+        editor.should_redraw = None;
+        // without wrap the two tinted rows are redrawn on their own
+        assert_eq!(client.text_redraw(&editor, false), None);
+
+        editor.options.wrap = true;
+        assert_eq!(client.text_redraw(&editor, false), Some(Redraw::All));
+
+        client.drawn_cursor_line = Some(1);
+        assert_eq!(client.text_redraw(&editor, false), None);
+    }
+
+    #[test]
+    fn ctrl_c_leaves_insert_mode_without_typing() {
+        let (mut client, _) = client();
+        let mut editor = editor();
+
+        send_keys(&mut client, "ia");
+        client.events.events.push_back(Event::Key(KeyEvent::new(
+            KeyCode::Char('c'),
+            KeyModifiers::CONTROL,
+        )));
+        run_queued(&mut client, &mut editor);
+
+        assert_eq!(editor.mode(), Mode::Normal);
+        assert_eq!(editor.content.get_line(0).as_deref(), Some("a"));
+    }
+
+    #[test]
+    fn records_logged_while_drawing_show_before_the_next_key() {
+        // the logger only exists once set, another test may have set it
+        let _ = logger::init(None);
+        logger::start_capture();
+
+        let (mut client, _) = client();
+        let mut editor = editor();
+        editor.load_data(b"one\ntwo\n".to_vec());
+        editor.set_cursor(1, 2);
+
+        client.draw(&editor);
+        log::warn!("logged while drawing");
+        client.draw(&editor);
+        assert_eq!(editor.render_cursor(), (1, 2));
+        assert_eq!(editor.message, None);
+
+        // shown without reading the queued key, which waits for the redraw
+        send_keys(&mut client, "h");
+        client.update(&mut editor);
+        assert_eq!(
+            editor.message.as_deref(),
+            Some("WARN: logged while drawing")
+        );
+        assert_eq!(client.events.events.len(), 1);
+        assert_eq!(editor.render_cursor(), (1, 2));
+
+        run_queued(&mut client, &mut editor);
+        assert_eq!(editor.render_cursor(), (1, 1));
+        assert!(logger::stop_capture().is_empty());
+    }
+
+    #[test]
+    fn clicks_on_the_gutter_and_wrapped_rows_land_in_their_line() {
+        let (mut client, _) = client();
+        client.line_numbered = true;
+        let mut editor = editor();
+        editor.load_data(format!("{}\nshort\n", "abcdefghij".repeat(10)).into_bytes());
+        editor.options.wrap = true;
+        client.resize(&mut editor);
+
+        // 73 text columns right of a 6 column gutter, the first line takes two rows
+        assert_eq!(editor.row_starts(0), [0, 73]);
+        let click = |client: &mut TestClient, editor: &mut CharVectorEditor, row, column| {
+            client.events.events.push_back(Event::Mouse(MouseEvent {
+                kind: MouseEventKind::Down(MouseButton::Left),
+                column,
+                row,
+                modifiers: KeyModifiers::NONE,
+            }));
+            client.update(editor);
+            editor.render_cursor()
+        };
+
+        assert_eq!(click(&mut client, &mut editor, 1, 11), (0, 78));
+        // the gutter is left of the screen row it is on
+        assert_eq!(click(&mut client, &mut editor, 1, 2), (0, 73));
+        assert_eq!(click(&mut client, &mut editor, 0, 0), (0, 0));
+        assert_eq!(click(&mut client, &mut editor, 2, 7), (1, 1));
+        assert_eq!(click(&mut client, &mut editor, 2, 40), (1, 5));
+
+        // rows are counted from the first line in view
+        editor.on_action(vec![Action::ScrollBy(1)]);
+        assert_eq!(editor.view.top, 1);
+        assert_eq!(click(&mut client, &mut editor, 0, 8), (1, 2));
+    }
+
+    #[test]
+    fn idle_fires_after_updatetime_counted_from_the_last_key() {
+        let (mut client, elapsed) = client();
+        let mut editor = editor();
+        editor.load_data(b"one two\n".to_vec());
+        client.resize(&mut editor);
+        editor.options.updatetime = 300;
+
+        client.update(&mut editor);
+        assert_eq!(elapsed.get(), Duration::from_millis(300));
+        assert!(client.idle);
+
+        // a key ends the idle time and restarts the wait
+        elapsed.set(Duration::from_millis(1000));
+        send_keys(&mut client, "w");
+        client.update(&mut editor);
+        assert!(!client.idle);
+        assert_eq!(editor.render_cursor(), (0, 4));
+
+        elapsed.set(Duration::from_millis(1100));
+        client.update(&mut editor);
+        assert_eq!(elapsed.get(), Duration::from_millis(1300));
+        assert!(client.idle);
+    }
+
+    #[test]
+    fn command_line_cursor_counts_chars_and_follows_completion() {
+        let (mut client, _) = client();
+        let mut editor = editor();
+        client.resize(&mut editor);
+
+        send_keys(&mut client, ":qu\t");
+        run_queued(&mut client, &mut editor);
+        assert_eq!(editor.command, "quit ");
+        client.draw(&editor);
+        let (rows, cursor) = replay(&client);
+        assert_eq!(rows[23], ":quit");
+        assert_eq!(cursor, (23, 6));
+
+        // `é` is two bytes but one cell
+        send_keys(&mut client, "\x1b:s/é/e");
+        run_queued(&mut client, &mut editor);
+        client.out.clear();
+        client.draw(&editor);
+        let (rows, cursor) = replay(&client);
+        assert_eq!(rows[23], ":s/é/e");
+        assert_eq!(cursor, (23, 6));
+    }
+
+    #[test]
+    fn readline_keys_edit_the_command_line_when_set() {
+        let (mut client, _) = client();
+        let mut editor = editor();
+
+        send_keys(&mut client, ":set ts=2");
+        for c in ['a', 'e', 'w', 'u'] {
+            send_ctrl(&mut client, c);
+        }
+        run_queued(&mut client, &mut editor);
+        assert_eq!(
+            (editor.command.as_str(), editor.command_cursor),
+            ("set ts=2", 8)
+        );
+
+        editor.options.readline = true;
+        send_ctrl(&mut client, 'w');
+        run_queued(&mut client, &mut editor);
+        assert_eq!(
+            (editor.command.as_str(), editor.command_cursor),
+            ("set ", 4)
+        );
+
+        send_ctrl(&mut client, 'a');
+        send_keys(&mut client, "x");
+        run_queued(&mut client, &mut editor);
+        assert_eq!(
+            (editor.command.as_str(), editor.command_cursor),
+            ("xset ", 1)
         );
+
+        send_ctrl(&mut client, 'e');
+        send_keys(&mut client, "ai");
+        send_ctrl(&mut client, 'b');
+        run_queued(&mut client, &mut editor);
+        assert_eq!(
+            (editor.command.as_str(), editor.command_cursor),
+            ("xset ai", 6)
+        );
+
+        // only what is before the cursor goes
+        send_ctrl(&mut client, 'u');
+        run_queued(&mut client, &mut editor);
+        assert_eq!((editor.command.as_str(), editor.command_cursor), ("i", 0));
+        assert_eq!(editor.mode(), Mode::Command);
     }
-}
 
-impl Drop for ConsoleClient {
-    fn drop(&mut self) {
-        disable_raw_mode().unwrap()
+    #[test]
+    fn tab_opens_the_wildmenu_and_cycles_through_it() {
+        let (mut client, _) = client();
+        let mut editor = editor();
+        client.resize(&mut editor);
+
+        send_keys(&mut client, ":set re\t");
+        run_queued(&mut client, &mut editor);
+        let menu = editor.wildmenu.as_ref().unwrap();
+        assert_eq!(menu.candidates, ["readline", "readonly", "relativenumber"]);
+        assert_eq!(editor.command, "set readline");
+
+        client.draw(&editor);
+        let rows = screen(&client);
+        assert_eq!(rows[22], "readline  readonly  relativenumber");
+        assert_eq!(rows[23], ":set readline");
+
+        send_keys(&mut client, "\t\t");
+        run_queued(&mut client, &mut editor);
+        assert_eq!(editor.wildmenu.as_ref().unwrap().selected, 2);
+        assert_eq!(editor.command, "set relativenumber");
+
+        // past the last one it starts over, Shift-Tab goes back
+        send_keys(&mut client, "\t");
+        run_queued(&mut client, &mut editor);
+        assert_eq!(editor.command, "set readline");
+        client.events.events.push_back(Event::Key(KeyEvent::new(
+            KeyCode::BackTab,
+            KeyModifiers::SHIFT,
+        )));
+        run_queued(&mut client, &mut editor);
+        assert_eq!(editor.command, "set relativenumber");
+
+        // any other key closes the menu and keeps the completed text
+        send_keys(&mut client, "\n");
+        run_queued(&mut client, &mut editor);
+        assert!(editor.wildmenu.is_none());
+        assert!(editor.options.relativenumber);
+    }
+
+    #[test]
+    fn showcmd_shows_the_pending_command_until_it_runs() {
+        let (mut client, _) = client();
+        let mut editor = editor();
+        editor.load_data(b"one\ntwo\nthree\n".to_vec());
+        client.resize(&mut editor);
+
+        send_keys(&mut client, "2d");
+        run_queued(&mut client, &mut editor);
+        assert_eq!(editor.pending_command(), "2d");
+        client.draw(&editor);
+        assert_eq!(screen(&client)[23].find("2d"), Some(70));
+
+        send_keys(&mut client, "d");
+        run_queued(&mut client, &mut editor);
+        assert_eq!(editor.pending_command(), "");
+        assert_eq!(editor.content.get_line(0).as_deref(), Some("three"));
+        client.draw(&editor);
+        assert_eq!(screen(&client)[23], "");
+    }
+
+    #[test]
+    fn dismissing_the_overlay_redraws_the_text_under_it() {
+        let (mut client, _) = client();
+        let mut editor = editor();
+        editor.load_data(
+            "a long line of text under the popup\n"
+                .repeat(30)
+                .into_bytes(),
+        );
+        client.resize(&mut editor);
+        client.draw(&editor);
+        let before = screen(&client);
+
+        send_keys(&mut client, ":ls\n");
+        run_queued(&mut client, &mut editor);
+        client.draw(&editor);
+        let shown = screen(&client);
+        assert!(editor.overlay.is_some());
+        assert!(shown.iter().any(|row| row.contains("+--")));
+        assert!(shown
+            .iter()
+            .any(|row| row.contains("|   1 %  \"[No Name]\" |")));
+
+        // any key closes it and the text comes back where the box was
+        send_keys(&mut client, "x");
+        run_queued(&mut client, &mut editor);
+        assert!(editor.overlay.is_none());
+        client.draw(&editor);
+        assert_eq!(
+            editor.content.get_line(0).unwrap(),
+            "a long line of text under the popup"
+        );
+        let after = screen(&client);
+        assert!(!after
+            .iter()
+            .any(|row| row.contains("+--") || row.contains('|')));
+        assert_eq!(after[..22], before[..22]);
+    }
+
+    #[test]
+    fn leaving_the_command_line_restores_the_mode_and_clears_it() {
+        let (mut client, _) = client();
+        let mut editor = editor();
+        editor.load_data(b"one\ntwo\n".to_vec());
+        client.resize(&mut editor);
+
+        send_keys(&mut client, ":set cul");
+        run_queued(&mut client, &mut editor);
+        client.draw(&editor);
+        assert_eq!(editor.mode(), Mode::Command);
+        assert_eq!(screen(&client)[23], ":set cul");
+
+        send_keys(&mut client, "\x1b");
+        run_queued(&mut client, &mut editor);
+        client.draw(&editor);
+        assert_eq!(editor.mode(), Mode::Normal);
+        assert_eq!(editor.command, "");
+        assert_eq!(screen(&client)[23], "");
+        assert!(!editor.options.cursorline);
+
+        send_keys(&mut client, ":set cul\n");
+        run_queued(&mut client, &mut editor);
+        client.draw(&editor);
+        assert_eq!(editor.mode(), Mode::Normal);
+        assert_eq!(editor.command, "");
+        assert!(!screen(&client)[23].contains("set cul"));
+        assert!(editor.options.cursorline);
+
+        // from visual mode it goes back to visual, keeping the selection
+        send_keys(&mut client, "vl:\x1b");
+        run_queued(&mut client, &mut editor);
+        assert_eq!(editor.mode(), Mode::Visual);
+        assert_eq!(editor.command, "");
+        send_keys(&mut client, ":\n");
+        run_queued(&mut client, &mut editor);
+        assert_eq!(editor.mode(), Mode::Visual);
+    }
+
+    #[test]
+    fn ga_shows_the_code_of_the_char_under_the_cursor() {
+        let (mut client, _) = client();
+        let mut editor = editor();
+        editor.load_data("aé日\n".as_bytes().to_vec());
+        client.resize(&mut editor);
+
+        send_keys(&mut client, "ga");
+        run_queued(&mut client, &mut editor);
+        client.draw(&editor);
+        assert_eq!(editor.message.as_deref(), Some("<a> 97, Hex 61, Oct 141"));
+        assert_eq!(screen(&client)[23], "<a> 97, Hex 61, Oct 141");
+
+        send_keys(&mut client, "lga");
+        run_queued(&mut client, &mut editor);
+        assert_eq!(
+            editor.message.as_deref(),
+            Some("<é> 233, Hex e9, Oct 351, U+00E9")
+        );
+
+        send_keys(&mut client, "lga");
+        run_queued(&mut client, &mut editor);
+        client.draw(&editor);
+        assert_eq!(
+            screen(&client)[23],
+            "<日> 26085, Hex 65e5, Oct 62745, U+65E5"
+        );
+    }
+
+    #[test]
+    fn split_windows_are_drawn_side_by_side() {
+        let (mut client, _) = client();
+        let mut editor = editor();
+        editor.load_data(b"left and right\nsecond line\n".to_vec());
+        client.resize(&mut editor);
+
+        send_keys(&mut client, ":vsplit\nj");
+        run_queued(&mut client, &mut editor);
+        client.draw(&editor);
+        let (rows, cursor) = replay(&client);
+
+        // each window is 39 columns wide, with a separator between them
+        assert_eq!(rows[0], format!("{:39}|left and right", "left and right"));
+        assert_eq!(rows[1], format!("{:39}|second line", "second line"));
+        assert_eq!(rows[5], format!("{:39}|", ""));
+        assert!(rows[22].starts_with(" NORMAL  [No Name] "));
+        assert_eq!(&rows[22][40..], format!("{:35} 1:1", " [No Name]"));
+        assert_eq!(editor.view.get_width() + 1, 39);
+        assert_eq!(cursor, (1, 0));
+
+        // the other window has the focus, and the cursor, after `Ctrl-w w`
+        send_ctrl(&mut client, 'w');
+        send_keys(&mut client, "w");
+        run_queued(&mut client, &mut editor);
+        assert_eq!(editor.pending_command(), "");
+        client.draw(&editor);
+        let (rows, cursor) = replay(&client);
+        assert_eq!(rows[1], format!("{:39}|second line", "second line"));
+        assert!(rows[22][40..].starts_with(" NORMAL  [No Name] "));
+        assert_eq!(cursor, (0, 40));
+    }
+
+    #[test]
+    fn only_window_gives_the_focused_one_the_whole_screen() {
+        let (mut client, _) = client();
+        let mut editor = editor();
+        editor.load_data(b"left and right\nsecond line\n".to_vec());
+        client.resize(&mut editor);
+        let full = (editor.view.get_width(), editor.view.get_height());
+
+        send_keys(&mut client, ":split\n:vsplit\nj");
+        run_queued(&mut client, &mut editor);
+        assert_eq!(editor.windows.count(), 3);
+
+        send_ctrl(&mut client, 'w');
+        send_keys(&mut client, "o");
+        run_queued(&mut client, &mut editor);
+        client.draw(&editor);
+        let (rows, cursor) = replay(&client);
+
+        assert_eq!(editor.windows.count(), 1);
+        assert_eq!((editor.view.get_width(), editor.view.get_height()), full);
+        assert_eq!(rows[0], "left and right");
+        assert_eq!(rows[12], "");
+        assert_eq!(cursor, (1, 0));
+    }
+
+    #[test]
+    fn each_window_has_its_own_status_line() {
+        let (mut client, _) = client();
+        let mut editor = editor();
+        editor.load_data(b"left and right\nsecond line\n".to_vec());
+        client.resize(&mut editor);
+
+        send_keys(&mut client, "w:vsplit\njll");
+        run_queued(&mut client, &mut editor);
+        client.draw(&editor);
+        let rows = screen(&client);
+
+        // the focused window's name comes after the mode
+        let (left, right) = rows[22].split_at(40);
+        assert_eq!(left, format!("{:34} 2:8 |", " NORMAL  [No Name]"));
+        assert_eq!(right, format!("{:35} 1:6", " [No Name]"));
+    }
+
+    #[test]
+    fn tab_pages_are_listed_above_the_windows() {
+        let (mut client, _) = client();
+        let mut editor = editor();
+        editor.load_data(b"first line\n".to_vec());
+        client.resize(&mut editor);
+
+        send_keys(&mut client, ":vsplit\n");
+        send_ctrl(&mut client, 'w');
+        send_keys(&mut client, "T");
+        run_queued(&mut client, &mut editor);
+        client.draw(&editor);
+        let (rows, cursor) = replay(&client);
+
+        assert_eq!(rows[0], " 1 [No Name]  2 [No Name]");
+        assert_eq!(rows[1], "first line");
+        assert_eq!(cursor, (1, 0));
+
+        send_keys(&mut client, "gT");
+        run_queued(&mut client, &mut editor);
+        client.draw(&editor);
+        assert_eq!(screen(&client)[1], "first line");
+        assert_eq!(editor.tab_labels().1, 0);
     }
 }
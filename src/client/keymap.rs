@@ -0,0 +1,328 @@
+use std::{collections::HashMap, fs, io, path::Path};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use log::error;
+
+use crate::{
+    client::console::default_binding,
+    editor::{Action, Mode, Movement, Redraw},
+    utils::unquote,
+};
+
+pub const KEYMAP_PATH: &str = "~/.config/rust-text-editor/keys.toml";
+
+const MODES: [(&str, Mode); 3] = [
+    ("normal", Mode::Normal),
+    ("insert", Mode::Insert),
+    ("visual", Mode::Visual),
+];
+
+// names of the keys that aren't a single char, as written in the keymap file
+const KEY_NAMES: [(&str, KeyCode); 14] = [
+    ("esc", KeyCode::Esc),
+    ("enter", KeyCode::Enter),
+    ("tab", KeyCode::Tab),
+    ("backspace", KeyCode::Backspace),
+    ("delete", KeyCode::Delete),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("space", KeyCode::Char(' ')),
+];
+
+/// Names used for actions in the keymap file.
+fn actions() -> Vec<(&'static str, Action)> {
+    vec![
+        ("up", Action::Move(Movement::Up)),
+        ("down", Action::Move(Movement::Down)),
+        ("left", Action::Move(Movement::CharLeft('h'))),
+        ("right", Action::Move(Movement::CharRight('l'))),
+        ("line_start", Action::Move(Movement::LineStart)),
+        ("line_end", Action::Move(Movement::LineEnd)),
+        ("screen_line_start", Action::Move(Movement::ScreenLineStart)),
+        ("screen_line_end", Action::Move(Movement::ScreenLineEnd)),
+        ("word_forward", Action::Move(Movement::WordForward)),
+        ("word_backward", Action::Move(Movement::WordBackward)),
+        ("word_end", Action::Move(Movement::WordEnd)),
+        ("normal_mode", Action::ChangeMode(Mode::Normal)),
+        ("interrupt", Action::Interrupt),
+        ("insert_mode", Action::ChangeMode(Mode::Insert)),
+        ("visual_mode", Action::ChangeMode(Mode::Visual)),
+        ("swap_selection_ends", Action::SwapSelectionEnds),
+        ("command_mode", Action::ChangeMode(Mode::Command)),
+        ("search_forward", Action::Search(true)),
+        ("search_backward", Action::Search(false)),
+        ("search_next", Action::SearchNext(false)),
+        ("search_previous", Action::SearchNext(true)),
+        ("goto_match", Action::GotoPercent),
+        ("first_line", Action::GotoLine(1)),
+        ("last_line", Action::GotoLine(u32::MAX)),
+        ("goto_file", Action::GotoFile),
+        ("show_char", Action::ShowChar),
+        ("newline", Action::InsertChar('\n')),
+        ("tab", Action::InsertChar('\t')),
+        ("backspace", Action::Backspace),
+        ("delete", Action::Delete),
+        ("delete_line", Action::DeleteLine),
+        ("delete_char", Action::DeleteChar(false)),
+        ("delete_char_before", Action::DeleteChar(true)),
+        ("delete_selection", Action::DeleteSelection),
+        ("join_lines", Action::JoinLines),
+        ("shift_right", Action::ShiftLines(true)),
+        ("shift_left", Action::ShiftLines(false)),
+        ("yank", Action::Yank),
+        ("paste", Action::Paste(false)),
+        ("paste_before", Action::Paste(true)),
+        ("undo", Action::Undo),
+        ("redo", Action::Redo),
+        ("earlier", Action::Travel(false)),
+        ("later", Action::Travel(true)),
+        ("save", Action::SaveFile),
+        ("quit", Action::Quit),
+        ("split_window", Action::Window('s')),
+        ("vsplit_window", Action::Window('v')),
+        ("next_window", Action::Window('w')),
+        ("previous_window", Action::Window('W')),
+        ("close_window", Action::Window('c')),
+        ("quit_window", Action::Window('q')),
+        ("only_window", Action::Window('o')),
+        ("equalize_windows", Action::Window('=')),
+        ("tab_window", Action::Window('T')),
+        ("next_tab", Action::Tab(true)),
+        ("previous_tab", Action::Tab(false)),
+        ("page_down", Action::ScrollBy(1)),
+        ("page_up", Action::ScrollBy(-1)),
+        ("half_page_down", Action::ScrollHalfPage(true)),
+        ("half_page_up", Action::ScrollHalfPage(false)),
+        ("redraw", Action::AskRedraw(Redraw::All)),
+        ("nop", Action::None),
+    ]
+}
+
+/// Keys remapped by the user, consulted before the built-in bindings.
+#[derive(Default)]
+pub struct KeyMap {
+    bindings: HashMap<(Mode, KeyCode, KeyModifiers), Vec<Action>>,
+}
+
+impl KeyMap {
+    /// Reads the keymap at `path`; a missing file gives an empty keymap and
+    /// invalid lines are logged and skipped.
+    pub fn load(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text, &path.to_string_lossy()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Self::default(),
+            Err(e) => {
+                error!("can't read keymap {}: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    fn parse(text: &str, source: &str) -> Self {
+        let mut keymap = Self::default();
+        let mut mode = None;
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let result = match line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                Some(section) => find_mode(section.trim()).map(|m| mode = Some(m)),
+                None => keymap.parse_binding(mode, line),
+            };
+
+            if let Err(message) = result {
+                error!("{}:{}: {}", source, i + 1, message);
+            }
+        }
+
+        keymap
+    }
+
+    fn parse_binding(&mut self, mode: Option<Mode>, line: &str) -> Result<(), String> {
+        let mode = mode.ok_or("binding outside of a [normal], [insert] or [visual] section")?;
+        let (key, value) = line
+            .split_once('=')
+            .ok_or("expected `\"key\" = \"action\"`")?;
+
+        let (code, modifiers) = parse_key(unquote(key.trim())?)?;
+        let value = value.trim();
+        let names: Vec<&str> = match value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+            Some(list) => list
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .collect(),
+            None => vec![value],
+        };
+
+        let actions = names
+            .into_iter()
+            .map(|name| find_action(unquote(name)?))
+            .collect::<Result<Vec<Action>, String>>()?;
+
+        self.bindings.insert((mode, code, modifiers), actions);
+        Ok(())
+    }
+
+    pub fn get(&self, mode: Mode, key: KeyEvent) -> Option<Vec<Action>> {
+        // shift is already part of the char for letters and symbols
+        let modifiers = match key.code {
+            KeyCode::Char(_) => key.modifiers - KeyModifiers::SHIFT,
+            _ => key.modifiers,
+        };
+
+        self.bindings.get(&(mode, key.code, modifiers)).cloned()
+    }
+}
+
+/// A keymap file listing every action name in comments, then the built-in
+/// bindings of each mode. Keys whose actions have no name, e.g. typing a char
+/// in insert mode or the first key of `dd`, are left out.
+pub fn starter_config() -> String {
+    let mut config = format!("# {}\n#\n# actions:\n", KEYMAP_PATH);
+    for (name, _) in actions() {
+        config += &format!("#   {}\n", name);
+    }
+
+    config += "#\n# keys are chars, `ctrl-x`, `alt-x` or one of esc, enter, tab, backspace,\n";
+    config += "# delete, up, down, left, right, pageup, pagedown, home, end, space\n";
+    config += "#\n# \"H\" = \"line_start\"\n# \"ctrl-s\" = [\"save\", \"normal_mode\"]\n";
+    for (name, mode) in MODES {
+        config += &format!("\n[{}]\n", name);
+
+        for (code, modifiers) in default_keys() {
+            let bound = default_binding(mode, KeyEvent::new(code, modifiers));
+            // modes that ignore ctrl would list every letter twice
+            let plain = default_binding(mode, KeyEvent::new(code, KeyModifiers::NONE));
+            if modifiers != KeyModifiers::NONE && bound == plain {
+                continue;
+            }
+
+            let names: Option<Vec<String>> = bound
+                .iter()
+                .map(|action| action_name(action).map(|name| format!("\"{}\"", name)))
+                .collect();
+
+            match names.as_deref() {
+                None | Some([]) => {}
+                Some([name]) if name == "\"nop\"" => {}
+                Some([name]) => {
+                    config += &format!("\"{}\" = {}\n", key_name(code, modifiers), name)
+                }
+                Some(names) => {
+                    config += &format!(
+                        "\"{}\" = [{}]\n",
+                        key_name(code, modifiers),
+                        names.join(", ")
+                    )
+                }
+            }
+        }
+    }
+
+    config
+}
+
+// every key the keymap file can name, `=` aside as it can't be quoted
+fn default_keys() -> Vec<(KeyCode, KeyModifiers)> {
+    let chars = (' '..='~')
+        .filter(|c| *c != '=')
+        .map(|c| (KeyCode::Char(c), KeyModifiers::NONE));
+    let named = KEY_NAMES
+        .iter()
+        .filter(|(_, code)| *code != KeyCode::Char(' '))
+        .map(|(_, code)| (*code, KeyModifiers::NONE));
+    let ctrl = ('a'..='z').map(|c| (KeyCode::Char(c), KeyModifiers::CONTROL));
+
+    chars.chain(named).chain(ctrl).collect()
+}
+
+fn key_name(code: KeyCode, modifiers: KeyModifiers) -> String {
+    let name = match KEY_NAMES.iter().find(|(_, named)| *named == code) {
+        Some((name, _)) => name.to_string(),
+        None => match code {
+            KeyCode::Char(c) => c.to_string(),
+            _ => String::new(),
+        },
+    };
+
+    match modifiers {
+        KeyModifiers::CONTROL => format!("ctrl-{}", name),
+        KeyModifiers::ALT => format!("alt-{}", name),
+        _ => name,
+    }
+}
+
+fn action_name(action: &Action) -> Option<&'static str> {
+    actions()
+        .into_iter()
+        .find(|(_, named)| named == action)
+        .map(|(name, _)| name)
+}
+
+fn find_mode(name: &str) -> Result<Mode, String> {
+    MODES
+        .iter()
+        .find(|(mode_name, _)| *mode_name == name)
+        .map(|(_, mode)| *mode)
+        .ok_or(format!("unknown section: {}", name))
+}
+
+fn find_action(name: &str) -> Result<Action, String> {
+    actions()
+        .into_iter()
+        .find(|(action_name, _)| *action_name == name)
+        .map(|(_, action)| action)
+        .ok_or(format!("unknown action: {}", name))
+}
+
+fn parse_key(key: &str) -> Result<(KeyCode, KeyModifiers), String> {
+    let (modifiers, name) = match (key.strip_prefix("ctrl-"), key.strip_prefix("alt-")) {
+        (Some(name), _) => (KeyModifiers::CONTROL, name),
+        (_, Some(name)) => (KeyModifiers::ALT, name),
+        _ => (KeyModifiers::NONE, key),
+    };
+
+    let mut chars = name.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        _ => KEY_NAMES
+            .iter()
+            .find(|(key_name, _)| *key_name == name)
+            .map(|(_, code)| *code)
+            .ok_or(format!("unknown key: {}", key))?,
+    };
+
+    Ok((code, modifiers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dumped_keys_load_back_as_the_defaults() {
+        let keymap = KeyMap::parse(&starter_config(), "dump");
+        assert!(!keymap.bindings.is_empty());
+
+        for ((mode, code, modifiers), actions) in &keymap.bindings {
+            let key = KeyEvent::new(*code, *modifiers);
+            assert!(*actions == default_binding(*mode, key), "{:?}", code);
+        }
+
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        assert!(keymap.get(Mode::Normal, key('u')) == Some(vec![Action::Undo]));
+        assert!(keymap.get(Mode::Visual, key('y')).is_some());
+        // typed chars have no action name to write
+        assert!(keymap.get(Mode::Insert, key('u')).is_none());
+    }
+}
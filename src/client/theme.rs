@@ -0,0 +1,163 @@
+use std::{fs, io, path::Path};
+
+use crossterm::style::{Color, ContentStyle, Stylize};
+use log::error;
+
+use crate::{editor::Highlight, utils::unquote};
+
+pub const THEME_PATH: &str = "~/.config/rust-text-editor/theme.toml";
+
+/// Colors used when drawing, `None` leaving the terminal's own color.
+pub struct Theme {
+    pub text: Option<Color>,
+    pub gutter: Option<Color>,
+    // background of the selection, reverse video when unset
+    pub selection: Option<Color>,
+    // background of the line under the cursor with `cursorline`
+    pub cursor_line: Option<Color>,
+    pub keyword: Option<Color>,
+    pub string: Option<Color>,
+    pub number: Option<Color>,
+    pub comment: Option<Color>,
+    pub spell: Option<Color>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            text: None,
+            gutter: Some(Color::DarkGrey),
+            selection: None,
+            cursor_line: Some(Color::AnsiValue(236)),
+            keyword: Some(Color::Magenta),
+            string: Some(Color::Green),
+            number: Some(Color::Yellow),
+            comment: Some(Color::DarkGrey),
+            spell: Some(Color::Red),
+        }
+    }
+}
+
+impl Theme {
+    /// Reads the theme at `path` over the default one; a missing file keeps
+    /// the default and invalid lines are logged and skipped.
+    pub fn load(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text, &path.to_string_lossy()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Self::default(),
+            Err(e) => {
+                error!("can't read theme {}: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    fn parse(text: &str, source: &str) -> Self {
+        let mut theme = Self::default();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Err(message) = theme.parse_entry(line) {
+                error!("{}:{}: {}", source, i + 1, message);
+            }
+        }
+
+        theme
+    }
+
+    fn parse_entry(&mut self, line: &str) -> Result<(), String> {
+        let (name, value) = line.split_once('=').ok_or("expected `name = \"color\"`")?;
+        let color = parse_color(unquote(value.trim())?)?;
+
+        match name.trim() {
+            "text" => self.text = color,
+            "gutter" => self.gutter = color,
+            "selection" => self.selection = color,
+            "cursor_line" => self.cursor_line = color,
+            "keyword" => self.keyword = color,
+            "string" => self.string = color,
+            "number" => self.number = color,
+            "comment" => self.comment = color,
+            "spell" => self.spell = color,
+            name => return Err(format!("unknown theme entry: {}", name)),
+        }
+
+        Ok(())
+    }
+
+    pub fn gutter_style(&self) -> ContentStyle {
+        with_color(ContentStyle::new(), self.gutter)
+    }
+
+    /// Style of text with `highlight`, or of plain text, on the cursor line or not.
+    pub fn style(&self, highlight: Option<Highlight>, cursor_line: bool) -> ContentStyle {
+        let mut text = with_color(ContentStyle::new(), self.text);
+        if let (true, Some(color)) = (cursor_line, self.cursor_line) {
+            text = text.on(color);
+        }
+
+        match highlight {
+            None | Some(Highlight::CursorLine) => text,
+            Some(Highlight::Keyword) => with_color(text, self.keyword),
+            Some(Highlight::String) => with_color(text, self.string),
+            Some(Highlight::Number) => with_color(text, self.number),
+            Some(Highlight::Comment) => with_color(text, self.comment),
+            Some(Highlight::SpellError) => with_color(text, self.spell).underlined(),
+            Some(Highlight::Selection) => match self.selection {
+                Some(color) => text.on(color),
+                None => text.reverse(),
+            },
+        }
+    }
+}
+
+fn with_color(style: ContentStyle, color: Option<Color>) -> ContentStyle {
+    match color {
+        Some(color) => style.with(color),
+        None => style,
+    }
+}
+
+/// A crossterm color name such as `dark_grey`, `#rrggbb`, or `default`.
+fn parse_color(name: &str) -> Result<Option<Color>, String> {
+    if name == "default" {
+        return Ok(None);
+    }
+
+    if let Some(hex) = name.strip_prefix('#') {
+        let channel = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|c| u8::from_str_radix(c, 16).ok())
+        };
+        return match (hex.len(), channel(0), channel(2), channel(4)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(Some(Color::Rgb { r, g, b })),
+            _ => Err(format!("invalid color: {}", name)),
+        };
+    }
+
+    Color::try_from(name)
+        .map(Some)
+        .map_err(|_| format!("unknown color: {}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn theme_file_overrides_the_default_colors() {
+        let theme = Theme::parse(
+            "# colors\ngutter = \"blue\"\ntext = \"default\"\nnumber = blue\n",
+            "theme.toml",
+        );
+
+        assert_eq!(theme.gutter, Some(Color::Blue));
+        assert_eq!(theme.text, None);
+        // the unquoted value is skipped
+        assert_eq!(theme.number, Theme::default().number);
+    }
+}
@@ -1,13 +1,52 @@
 use std::{
+    borrow::Cow,
     cmp,
-    fs::File,
-    io::{Read, Write},
+    collections::VecDeque,
+    fs::{self, File},
+    io::{ErrorKind, Read, Write},
+    iter,
+    ops::Range,
+    path::{Path, PathBuf},
 };
 
-use log::{error, info};
+use log::{error, info, warn};
 
+use crate::{
+    logger,
+    utils::{display_width, expand_home, is_path_char},
+};
+
+use self::{
+    buffer::{Buffer, BufferList},
+    completion::Wildmenu,
+    layout::{Axis, Layout, TabList, Window, WindowId},
+    options::Options,
+    spell::Dictionary,
+    syntax::Highlighter,
+    undo::{Change, Restore, UndoTree},
+};
+
+pub mod buffer;
+pub mod completion;
+pub mod gap;
+pub mod layout;
+pub mod motion;
+pub mod options;
+pub mod rope;
+pub mod spell;
+pub mod syntax;
+pub mod undo;
 pub mod vector;
 
+const MESSAGE_HISTORY: usize = 200;
+const MEGABYTE: u64 = 1024 * 1024;
+const MODELINES: u32 = 5;
+const BINARY_SAMPLE: usize = 8 * 1024;
+// closed as they are typed with `autopairs`
+const AUTO_PAIRS: [(char, char); 5] =
+    [('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')];
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
 pub struct Container {
     pub top: u32,
     pub left: u32,
@@ -15,24 +54,27 @@ pub struct Container {
     pub right: u32,
 }
 
-impl Default for Container {
-    fn default() -> Self {
-        Self {
-            top: 0,
-            left: 0,
-            bottom: 0,
-            right: 0,
-        }
-    }
-}
-
 impl Container {
     pub fn get_width(&self) -> u32 {
-        self.right - self.left
+        if self.right < self.left {
+            warn!(
+                "inverted container: left {} > right {}",
+                self.left, self.right
+            );
+        }
+
+        self.right.saturating_sub(self.left)
     }
 
     pub fn get_height(&self) -> u32 {
-        self.bottom - self.top
+        if self.bottom < self.top {
+            warn!(
+                "inverted container: top {} > bottom {}",
+                self.top, self.bottom
+            );
+        }
+
+        self.bottom.saturating_sub(self.top)
     }
 }
 
@@ -44,38 +86,117 @@ pub enum Movement {
     Right,
     LineEnd,
     LineStart,
+    ScreenLineEnd,
+    ScreenLineStart,
+    WordForward,
+    WordBackward,
+    WordEnd,
+    // left/right from a key, wrapping across lines when its `whichwrap` flag is set
+    CharLeft(char),
+    CharRight(char),
 }
 
 #[allow(unused)]
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Mode {
     Normal,
     Insert,
     Visual,
+    Command,
 }
 
 #[allow(unused)]
-#[derive(PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq)]
 pub enum Action {
     Move(Movement),
     ChangeMode(Mode),
     InsertChar(char),
+    PushCount(u32),
+    PushOperator(char),
+    GotoPercent,
+    // 1-based, a count typed before it wins; past the end means the last line
+    GotoLine(u32),
+    GotoFile,
+    // opens the entry under the cursor in a directory listing, moves down elsewhere
+    OpenEntry,
+    // (row, col) in the buffer, e.g. from a mouse click
+    GotoPosition(u32, u32),
+    ShowChar,
+    Search(bool),
+    SearchNext(bool),
+    CommandChar(char),
+    CommandBackspace,
+    // the readline keys on the command line: a cursor move, or `Ctrl-w` when
+    // false and `Ctrl-u` when true, deleting back a word or to the start
+    CommandMove(Movement),
+    CommandDeleteBack(bool),
+    CompleteCommand(bool),
+    RunCommand,
+    CancelCommand,
+    ConfirmAnswer(char),
+    DismissOverlay,
     Backspace,
     Delete,
+    DeleteLine,
+    // `x`, or `X` when true: delete within the line, never joining lines
+    DeleteChar(bool),
+    JoinLines,
+    // `>>` when true, `<<` otherwise: one `shiftwidth` more or less indentation
+    ShiftLines(bool),
+    Yank,
+    Paste(bool),
+    // `d` in visual mode
+    DeleteSelection,
+    // `o` in visual mode: the cursor goes to the other end of the selection
+    SwapSelectionEnds,
     Quit,
+    // `Ctrl-c` in normal mode, which only tells how to quit
+    Interrupt,
+    // `Ctrl-w` followed by the char, e.g. `s` to split the window
+    Window(char),
+    // `gt` when true, `gT` otherwise: the next / previous tab page
+    Tab(bool),
     None,
 
     ScrollBy(i32),
+    // `Ctrl-d` when true, `Ctrl-u` otherwise: the view and cursor move together
+    ScrollHalfPage(bool),
     // ScrollTo(u32),
     Resize(u16, u16),
 
-    OpenFile(String),
-    WriteFile(String),
     SaveFile,
+    Undo,
+    Redo,
+    // `g-` when false, `g+` otherwise: the previous / next state in time,
+    // whatever undo branch it's on
+    Travel(bool),
 
     AskRedraw(Redraw),
 }
 
+impl Action {
+    /// Whether the action changes the buffer or the file, refused with `readonly`.
+    fn is_edit(&self) -> bool {
+        matches!(
+            self,
+            Action::InsertChar(_)
+                | Action::Backspace
+                | Action::Delete
+                | Action::DeleteLine
+                | Action::DeleteSelection
+                | Action::DeleteChar(_)
+                | Action::JoinLines
+                | Action::ShiftLines(_)
+                | Action::Paste(_)
+                | Action::Undo
+                | Action::Redo
+                | Action::Travel(_)
+                | Action::SaveFile
+                | Action::ChangeMode(Mode::Insert)
+        )
+    }
+}
+
 #[allow(unused)]
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Redraw {
@@ -84,6 +205,120 @@ pub enum Redraw {
     Range(u32, u32),
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Highlight {
+    Keyword,
+    String,
+    Number,
+    Comment,
+    SpellError,
+    Selection,
+    // the whole row the cursor is on, with `cursorline`
+    CursorLine,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FileFormat {
+    Unix,
+    Dos,
+    Mac,
+}
+
+impl FileFormat {
+    /// Guesses the format from the line endings in `raw_data`, picking the
+    /// most common one when they are mixed.
+    pub fn detect(raw_data: &[u8]) -> Option<Self> {
+        // counts for unix, dos and mac endings
+        let mut counts = [0usize; 3];
+        let mut bytes = raw_data.iter().peekable();
+
+        while let Some(c) = bytes.next() {
+            match c {
+                b'\r' if bytes.peek() == Some(&&b'\n') => {
+                    bytes.next();
+                    counts[1] += 1;
+                }
+                b'\r' => counts[2] += 1,
+                b'\n' => counts[0] += 1,
+                _ => (),
+            }
+        }
+
+        let found = counts.iter().filter(|count| **count > 0).count();
+        if found == 0 {
+            return None;
+        }
+
+        let formats = [Self::Unix, Self::Dos, Self::Mac];
+        let (format, _) = formats
+            .into_iter()
+            .zip(counts)
+            .rev()
+            .max_by_key(|(_, count)| *count)?;
+
+        if found > 1 {
+            warn!(
+                "mixed line endings (unix: {}, dos: {}, mac: {}), using {}",
+                counts[0],
+                counts[1],
+                counts[2],
+                format.name()
+            );
+        }
+
+        Some(format)
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "unix" => Some(Self::Unix),
+            "dos" => Some(Self::Dos),
+            "mac" => Some(Self::Mac),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Unix => "unix",
+            Self::Dos => "dos",
+            Self::Mac => "mac",
+        }
+    }
+
+    pub fn line_ending(self) -> &'static [u8] {
+        match self {
+            Self::Unix => b"\n",
+            Self::Dos => b"\r\n",
+            Self::Mac => b"\r",
+        }
+    }
+}
+
+/// The platform's own line endings, kept by new files and files without any.
+impl Default for FileFormat {
+    fn default() -> Self {
+        if cfg!(windows) {
+            Self::Dos
+        } else {
+            Self::Unix
+        }
+    }
+}
+
+/// Text copied by `y` or cut by `d`. Whole lines from `yy` are pasted as
+/// lines below or above the cursor line, the rest inside it.
+#[derive(Default)]
+pub struct Clipboard {
+    pub text: String,
+    pub linewise: bool,
+}
+
+pub enum Confirm {
+    Quit,
+    LargeFile { path: String, startup: bool },
+}
+
 pub trait EditorIO {
     fn open_file(&mut self, path: &str) -> Result<(), std::io::Error>;
     fn save_file(&self) -> Result<(), std::io::Error>;
@@ -93,23 +328,65 @@ pub trait EditorIO {
 pub trait EditorEvent {
     fn on_load_file(&mut self, path: String);
     fn on_action(&mut self, action: Vec<Action>);
+    /// Called once after `updatetime` milliseconds without input.
+    fn on_idle(&mut self) {}
+    /// Called once after `autosavetime` seconds without input, when
+    /// `autosave` is on and the buffer has changes.
+    fn on_autosave(&mut self) {}
 }
 
 pub struct EditorContent<T> {
     data: T,
-    is_crlf: bool,
+    format: FileFormat,
 }
 
 pub struct Editor<T: EditorContentTrait> {
-    pub file_path: Option<String>,
-    pub content: T,
-    pub render_row: u32,
-    pub row: u32,
-    pub render_col: u32,
-    pub col: u32,
-    pub mode: Mode,
-    pub should_redraw: Option<Redraw>,
-    pub view: Container,
+    file_path: Option<String>,
+    pub(crate) content: T,
+    render_row: u32,
+    row: u32,
+    render_col: u32,
+    col: u32,
+    mode: Mode,
+    mode_before_command: Mode,
+    pub(crate) should_redraw: Option<Redraw>,
+    pub(crate) view: Container,
+    pending_count: Option<u32>,
+    pub(crate) pending_op: Option<char>,
+    pub(crate) options: Options,
+    pub(crate) command: String,
+    // chars of `command` before the cursor
+    pub(crate) command_cursor: usize,
+    pub(crate) prompt: char,
+    last_search: Option<(String, bool)>,
+    pub(crate) message: Option<String>,
+    messages: VecDeque<String>,
+    pub(crate) confirm: Option<Confirm>,
+    pub(crate) overlay: Option<Vec<String>>,
+    pub(crate) wildmenu: Option<Wildmenu>,
+    // lines changed by the `inccommand` preview: row, text before, replacements
+    preview: Vec<(u32, String, Vec<Range<usize>>)>,
+    selection_anchor: Option<(u32, u32)>,
+    // text yanked with `y`, a trailing newline marks whole lines
+    clipboard: Clipboard,
+    undo: UndoTree,
+    dictionary: Option<Dictionary>,
+    // picked from the file extension when a file is opened
+    highlighter: Option<Box<dyn Highlighter>>,
+    pub(crate) modified: bool,
+    insert_repeat: u32,
+    inserted: String,
+    pub(crate) should_quit: bool,
+    // the buffer lists the directory at `file_path`
+    directory: bool,
+    // the file looked binary, read-only until `:set noreadonly`
+    binary: bool,
+    // files open besides the shown one
+    buffers: BufferList<T>,
+    // the windows on screen, the focused one showing the buffer above
+    pub(crate) windows: Layout,
+    // tab pages besides the shown one, each with its own windows
+    tabs: TabList,
     // pub view_start: u32,
     // pub view_end: u32,
 }
@@ -124,21 +401,66 @@ impl<T: EditorContentTrait> Editor<T> {
             render_col: 0,
             col: 0,
             mode: Mode::Normal,
+            mode_before_command: Mode::Normal,
             should_redraw: None,
             view: Container::default(),
+            pending_count: None,
+            pending_op: None,
+            options: Options::default(),
+            command: String::new(),
+            command_cursor: 0,
+            prompt: ':',
+            last_search: None,
+            message: None,
+            messages: VecDeque::new(),
+            confirm: None,
+            overlay: None,
+            wildmenu: None,
+            preview: Vec::new(),
+            selection_anchor: None,
+            clipboard: Clipboard::default(),
+            undo: UndoTree::default(),
+            dictionary: None,
+            highlighter: None,
+            modified: false,
+            insert_repeat: 1,
+            inserted: String::new(),
+            should_quit: false,
+            directory: false,
+            binary: false,
+            buffers: BufferList::new(),
+            windows: Layout::new(),
+            tabs: TabList::new(),
             // view_start: 0,
             // view_end: 0,
         }
     }
 
     fn move_cursor(&mut self, movement: Movement) {
-        let line = self
-            .content
-            .get_line(self.render_row)
-            .unwrap_or(String::from("\n"));
-        let mut line_len = line.len() as u32;
+        let mut line_len = self.content.get_line_len(self.render_row).unwrap_or(1);
         let mut wrap_left = false;
 
+        // the char motions only cross line ends listed in `whichwrap`
+        let blocked = match movement {
+            Movement::CharLeft(flag) => {
+                self.render_col == 0 && !self.options.whichwrap.contains(flag)
+            }
+            Movement::CharRight(flag) => {
+                self.render_col >= line_len
+                    && !self.options.whichwrap.contains(flag)
+                    && !self.options.virtualedit_all()
+            }
+            _ => false,
+        };
+        if blocked {
+            return;
+        }
+
+        if self.options.wrap && matches!(movement, Movement::Up | Movement::Down) {
+            self.move_display_row(movement == Movement::Down);
+            return;
+        }
+
         if self.render_row != self.row {
             self.row = self.render_row;
             self.col = self.render_col;
@@ -149,9 +471,12 @@ impl<T: EditorContentTrait> Editor<T> {
         //     self.col = self.render_col;
         // }
 
+        // lines kept visible above and below the cursor, at most half the view
+        let scrolloff = cmp::min(self.options.scrolloff, self.view.get_height() / 2);
+
         match movement {
             Movement::Up => {
-                if self.render_row == self.view.top {
+                if self.view.top > 0 && self.render_row <= self.view.top + scrolloff {
                     self.scroll_to(self.view.left as i32, self.view.top as i32 - 1);
                     self.should_redraw = Some(Redraw::All);
                 }
@@ -159,19 +484,21 @@ impl<T: EditorContentTrait> Editor<T> {
                 self.row = cmp::max(0, self.row as i32 - 1) as u32;
             }
             Movement::Down => {
-                if self.render_row == self.view.bottom {
+                if self.row + 1 >= self.content.line_count() {
+                    return;
+                }
+
+                if self.view.bottom + 1 < self.content.line_count()
+                    && self.render_row + scrolloff >= self.view.bottom
+                {
                     self.scroll_to(self.view.left as i32, self.view.top as i32 + 1);
                     self.should_redraw = Some(Redraw::All);
                 }
 
                 self.row += 1;
             }
-            Movement::Left => {
-                if self.render_col == self.view.left {
-                    self.scroll_to(self.view.left as i32 - 1, self.view.top as i32);
-                    self.should_redraw = Some(Redraw::All);
-                }
-
+            // `goto_cursor` scrolls sideways once the cursor has moved
+            Movement::Left | Movement::CharLeft(_) => {
                 if self.col == 0 && self.row != 0 {
                     self.row = cmp::max(0, self.row as i32 - 1) as u32;
 
@@ -180,203 +507,4504 @@ impl<T: EditorContentTrait> Editor<T> {
                     self.col = cmp::max(0, cmp::min(self.render_col, self.col) as i32 - 1) as u32;
                 }
             }
-            Movement::Right => {
-                if self.render_col == self.view.right {
-                    self.scroll_to(self.view.left as i32 + 1, self.view.top as i32);
-                    self.should_redraw = Some(Redraw::All);
-                }
-
+            Movement::Right | Movement::CharRight(_) => {
                 self.col += 1;
 
-                // TODO: skiping single-line file
-                if self.col > line_len {
-                    self.col = 0;
-                    self.row += 1;
+                if self.col > line_len && !self.options.virtualedit_all() {
+                    if self.row + 1 < self.content.line_count() {
+                        self.col = 0;
+                        self.row += 1;
+                    } else {
+                        self.col = line_len;
+                    }
                 }
             }
+            // on the last char in normal mode, past it where text can be added
             Movement::LineEnd => {
-                self.col = line_len;
+                self.col = match self.mode {
+                    Mode::Normal => line_len.saturating_sub(1),
+                    _ => line_len,
+                };
             }
             Movement::LineStart => {
                 self.col = 0;
             }
+            Movement::ScreenLineEnd => {
+                let (_, end) = self.screen_line();
+                self.col = cmp::min(self.char_col(self.row, end), line_len);
+            }
+            // a tab cut by the left edge is not on screen
+            Movement::ScreenLineStart => {
+                let (start, _) = self.screen_line();
+                let col = self.char_col(self.row, start);
+                let col = match self.display_col(self.row, col) < start {
+                    true => col + 1,
+                    false => col,
+                };
+                self.col = cmp::min(col, line_len);
+            }
+            // the word motions place the cursor themselves
+            Movement::WordForward => {
+                let (row, col) =
+                    motion::word_forward(&self.content, self.render_row, self.render_col);
+                self.set_cursor(row, col);
+                return;
+            }
+            Movement::WordBackward => {
+                let (row, col) =
+                    motion::word_backward(&self.content, self.render_row, self.render_col);
+                self.set_cursor(row, col);
+                return;
+            }
+            Movement::WordEnd => {
+                let (row, col) = motion::word_end(&self.content, self.render_row, self.render_col);
+                self.set_cursor(row, col);
+                return;
+            }
         }
 
-        match self.content.get_line_len(self.row) {
-            Some(n) => line_len = n,
-            None => self.row -= 1,
-        }
+        // `col` is left alone so moving back to a longer line restores it
+        self.row = cmp::min(self.row, self.content.line_count().saturating_sub(1));
+        line_len = self.content.get_line_len(self.row).unwrap_or(0);
 
         if wrap_left {
             self.col = line_len;
         }
 
-        self.render_col = cmp::min(line_len, self.col);
+        self.render_col = self.clamp_col(self.col, line_len);
 
         self.goto_cursor();
 
         self.render_row = cmp::min(cmp::max(self.view.top, self.row), self.view.bottom);
     }
 
-    fn write_char(&mut self, c: char) {
-        self.content.write_char(c, self.render_col, self.row);
-    }
+    // with `wrap`, up and down step through the rows a long line is broken
+    // into, keeping the column on screen
+    fn move_display_row(&mut self, down: bool) {
+        let display = self.display_col(self.render_row, self.render_col);
+        let starts = self.row_starts(self.render_row);
+        let line_row = row_at(&starts, display);
+        let offset = display - starts[line_row];
 
-    fn delete_char(&mut self) -> Option<char> {
-        self.content.delete_char(self.render_col, self.row)
+        let (row, line_row) = match down {
+            true if line_row + 1 < starts.len() => (self.render_row, line_row + 1),
+            true if self.render_row + 1 < self.content.line_count() => (self.render_row + 1, 0),
+            false if line_row > 0 => (self.render_row, line_row - 1),
+            false if self.render_row > 0 => (
+                self.render_row - 1,
+                self.display_rows(self.render_row - 1) as usize - 1,
+            ),
+            _ => return,
+        };
+
+        self.set_cursor(row, self.char_col_on_row(row, line_row, offset));
     }
 
-    fn scroll_to(&mut self, horizontal: i32, vertical: i32) {
-        let horizontal_size = self.view.get_width();
-        self.view.left = cmp::max(0, horizontal) as u32;
-        self.view.right = self.view.left + horizontal_size;
+    // first and last screen columns of the cursor's screen line: its wrapped
+    // row with `wrap`, the visible part of the line without
+    fn screen_line(&self) -> (u32, u32) {
+        if !self.options.wrap {
+            return (self.view.left, self.view.right);
+        }
 
-        self.render_col = cmp::min(cmp::max(self.view.left, self.col), self.view.right);
+        let starts = self.row_starts(self.row);
+        let line_row = row_at(&starts, self.display_col(self.row, self.render_col));
+        let start = starts[line_row];
+        match starts.get(line_row + 1) {
+            Some(next) => (start, next - 1),
+            None => (start, start + self.view.get_width()),
+        }
+    }
 
-        let vertical_size = self.view.get_height();
-        self.view.top = cmp::max(0, vertical) as u32;
-        self.view.bottom = self.view.top + vertical_size;
+    // char of line `row` at `offset` cells into its wrapped row `line_row`,
+    // staying on that row when it's shorter
+    fn char_col_on_row(&self, row: u32, line_row: usize, offset: u32) -> u32 {
+        let starts = self.row_starts(row);
+        let start = starts[line_row];
+        let display = match starts.get(line_row + 1) {
+            Some(next) => cmp::min(start + offset, next - 1),
+            None => start + offset,
+        };
 
-        self.render_row = cmp::min(cmp::max(self.view.top, self.row), self.view.bottom);
+        // a tab started on the row above belongs to that row
+        let col = self.char_col(row, display);
+        match self.display_col(row, col) < start {
+            true => col + 1,
+            false => col,
+        }
+    }
 
-        match self.content.get_line_len(self.render_row) {
-            Some(n) => self.render_col = cmp::min(self.col, n),
-            None => (),
+    /// Buffer `(row, col)` drawn at a cell of the text area, counted from its
+    /// top left corner; with `wrap` the rows below a long line belong to it.
+    pub fn position_at(&self, screen_row: u32, screen_col: u32) -> (u32, u32) {
+        if !self.options.wrap {
+            let row = screen_row + self.view.top;
+            return (row, self.char_col(row, screen_col + self.view.left));
+        }
+
+        let mut row = self.view.top;
+        let mut above = 0;
+        while row + 1 < self.content.line_count() && above + self.display_rows(row) <= screen_row {
+            above += self.display_rows(row);
+            row += 1;
         }
+
+        let line_row = cmp::min(screen_row - above, self.display_rows(row) - 1);
+        (
+            row,
+            self.char_col_on_row(row, line_row as usize, screen_col),
+        )
     }
 
-    fn goto_cursor(&mut self) {
-        if self.render_col < self.view.left {
-            self.scroll_to(self.render_col as i32, self.view.top as i32);
-        } else if self.render_col > self.view.right {
-            self.scroll_to(
-                (self.render_col - self.view.get_width()) as i32,
-                self.view.top as i32,
-            );
+    // `readonly` for every buffer, or just this one for a binary file
+    fn readonly(&self) -> bool {
+        self.options.readonly || self.binary
+    }
+
+    fn clamp_col(&self, col: u32, line_len: u32) -> u32 {
+        if self.options.virtualedit_all() {
+            col
+        } else {
+            cmp::min(col, line_len)
         }
+    }
 
-        self.should_redraw = Some(Redraw::All);
+    fn first_non_blank(&self, row: u32) -> u32 {
+        self.content
+            .get_line(row)
+            .map(|l| l.chars().take_while(|c| c.is_whitespace()).count() as u32)
+            .unwrap_or(0)
     }
-}
 
-impl<T: EditorContentTrait> EditorIO for Editor<T> {
-    fn open_file(&mut self, path: &str) -> Result<(), std::io::Error> {
-        self.file_path = Some(path.to_string());
-        let mut file = File::open(path)?;
-        let mut buf: Vec<u8> = Vec::new();
-        file.read_to_end(&mut buf)?;
-        self.content.load_data(buf);
-        Ok(())
+    /// Logical cursor position as `(row, col)`, both 0-based, the column being
+    /// the one kept while moving up and down through shorter lines.
+    pub fn cursor(&self) -> (u32, u32) {
+        (self.row, self.col)
     }
 
-    fn save_file(&self) -> Result<(), std::io::Error> {
-        if let Some(path) = &self.file_path {
-            let mut file = File::create(path)?;
-            let mut buf: Vec<u8> = Vec::new();
-            self.content.read_data(&mut buf);
-            file.write_all(&buf)?;
-            return Ok(());
+    /// Where the cursor is drawn as `(row, col)`, both 0-based, never past the
+    /// end of its line.
+    pub fn render_cursor(&self) -> (u32, u32) {
+        (self.render_row, self.render_col)
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Path of the open file as given when opening it, `None` for a new buffer.
+    pub fn file_path(&self) -> Option<&Path> {
+        self.file_path.as_deref().map(Path::new)
+    }
+
+    pub fn line_count(&self) -> u32 {
+        self.content.line_count()
+    }
+
+    /// Moves the cursor to `(row, col)`, clamped to the content, scrolling the
+    /// view when the target is off-screen.
+    pub fn set_cursor(&mut self, row: u32, col: u32) {
+        let last_row = self.content.line_count().saturating_sub(1);
+        self.row = cmp::min(row, last_row);
+
+        let line_len = self.content.get_line_len(self.row).unwrap_or(0);
+        self.col = self.clamp_col(col, line_len);
+
+        if self.row < self.view.top || self.row > self.view.bottom {
+            let half_height = (self.view.get_height() / 2) as i32;
+            self.scroll_to(self.view.left as i32, self.row as i32 - half_height);
         }
 
-        error!("there isn't any file opened!");
-        Ok(())
+        self.render_row = self.row;
+        self.render_col = self.col;
+        self.goto_cursor();
     }
 
-    fn write_file(&self, path: &str) -> Result<(), std::io::Error> {
-        let mut file = File::create(path)?;
-        let mut buf: Vec<u8> = Vec::new();
-        self.content.read_data(&mut buf);
-        file.write_all(&buf)?;
-        Ok(())
+    fn goto_line(&mut self, row: u32) {
+        let row = cmp::min(row, self.content.line_count().saturating_sub(1));
+        self.set_cursor(row, self.first_non_blank(row));
     }
-}
 
-impl<T: EditorContentTrait> EditorEvent for Editor<T> {
-    fn on_load_file(&mut self, path: String) {
-        info!("loading file '{}'", path);
+    /// Jumps to the 1-based `line` typed by the user, saying so when the file
+    /// is shorter and the jump stops at the last line.
+    fn goto_line_number(&mut self, line: u32) {
+        let line_count = self.content.line_count();
+        if line > line_count {
+            info!("line {} is past the end, going to {}", line, line_count);
+            self.message = Some(format!("only {} lines, went to the last one", line_count));
+        }
+        self.goto_line(line.saturating_sub(1));
+    }
 
-        self.open_file(path.as_str()).unwrap();
+    fn goto_percent(&mut self, percent: u32) {
+        // same rounding as vim, 1-based
+        let percent = cmp::min(percent, 100);
+        let line_count = self.content.line_count();
+        let line = (percent * line_count).div_ceil(100);
 
-        self.file_path = Some(path);
+        self.goto_line(line.saturating_sub(1));
     }
 
-    fn on_action(&mut self, actions: Vec<Action>) {
-        self.should_redraw = None;
+    /// Jumps from the first bracket at or after the cursor on the current line
+    /// to its counterpart, following the `matchpairs` option.
+    fn goto_match(&mut self) {
+        let pairs = self.options.match_pairs();
+        let Some(line) = self.content.get_line(self.row) else {
+            return;
+        };
 
-        actions.iter().for_each(|action| {
-            match *action {
-                Action::Move(mov) => {
-                    self.move_cursor(mov);
-                }
-                Action::ChangeMode(mode) => {
-                    self.mode = mode;
-                }
-                Action::InsertChar(c) => {
-                    if c == '\n' {
-                        self.should_redraw = Some(Redraw::All);
+        let found = line
+            .chars()
+            .enumerate()
+            .skip(self.render_col as usize)
+            .find_map(|(col, c)| {
+                pairs.iter().find_map(|&(open, close)| {
+                    if c == open {
+                        Some((col, open, close, true))
+                    } else if c == close {
+                        Some((col, close, open, false))
                     } else {
-                        self.should_redraw = Some(Redraw::Line(self.row));
+                        None
                     }
+                })
+            });
 
-                    self.write_char(c);
-                    self.move_cursor(Movement::Right);
-                }
-                Action::Backspace => {
-                    if self.render_col == 0 {
-                        self.should_redraw = Some(Redraw::All);
-                    } else {
-                        self.should_redraw = Some(Redraw::Line(self.row));
-                    }
+        let Some((col, this, other, forward)) = found else {
+            return;
+        };
 
-                    self.move_cursor(Movement::Left);
-                    self.delete_char();
-                }
-                Action::Delete => {
-                    let deleted_char = self.delete_char();
-                    match deleted_char {
-                        Some('\n') => {
-                            self.should_redraw = Some(Redraw::All);
-                        }
-                        _ => {
-                            self.should_redraw = Some(Redraw::Line(self.row));
-                        }
-                    }
-                }
-                Action::ScrollBy(steps) => {
-                    self.scroll_to(self.view.left as i32, self.view.top as i32 + steps);
-                }
-                // Action::ScrollTo(line_num) => {
-                // self.scroll_to(self.view.left as i32, line_num as i32);
-                // }
-                Action::Resize(width, height) => {
-                    self.view.bottom = self.view.top + height as u32 - 1;
-                    self.view.right = self.view.left + width as u32 - 1;
+        if let Some((row, col)) = self.find_match(self.row, col, this, other, forward) {
+            self.set_cursor(row, col);
+        }
+    }
 
-                    self.should_redraw = Some(Redraw::All);
-                }
-                Action::SaveFile => {
-                    self.save_file().unwrap();
-                }
-                Action::AskRedraw(redraw) => {
-                    self.should_redraw = Some(redraw);
+    fn find_match(
+        &self,
+        row: u32,
+        col: usize,
+        this: char,
+        other: char,
+        forward: bool,
+    ) -> Option<(u32, u32)> {
+        let mut depth = 0;
+        let mut closes = |c: char| {
+            if c == this {
+                depth += 1;
+            } else if c == other {
+                depth -= 1;
+            }
+            c == other && depth == 0
+        };
+
+        if forward {
+            for (i, line) in self.content.lines().enumerate().skip(row as usize) {
+                let start = if i == row as usize { col } else { 0 };
+                let found = line.iter().skip(start).position(|c| closes(*c));
+
+                if let Some(offset) = found {
+                    return Some((i as u32, (start + offset) as u32));
                 }
-                Action::Quit => {
-                    panic!("program exited!");
+            }
+        } else {
+            for i in (0..=row).rev() {
+                let line: Vec<char> = self.content.get_line(i)?.chars().collect();
+                let end = if i == row { col + 1 } else { line.len() };
+
+                if let Some(found) = line[..end].iter().rposition(|c| closes(*c)) {
+                    return Some((i, found as u32));
                 }
-                Action::None => {}
-                _ => todo!(),
+            }
+        }
+
+        None
+    }
+
+    pub fn pending_command(&self) -> String {
+        let mut pending = self
+            .pending_count
+            .map(|count| count.to_string())
+            .unwrap_or_default();
+
+        match self.pending_op {
+            // `^W` for `Ctrl-w`
+            Some(op) if op.is_control() => {
+                pending.push_str(&format!("^{}", (op as u8 + 0x40) as char))
+            }
+            Some(op) => pending.push(op),
+            None => (),
+        }
+
+        pending
+    }
+
+    pub fn line_highlights(&self, row: u32) -> Vec<(Range<usize>, Highlight)> {
+        let mut highlights = Vec::new();
+        let line = self.content.get_line(row).unwrap_or_default();
+
+        if self.options.cursorline && row == self.render_row {
+            highlights.push((0..usize::MAX, Highlight::CursorLine));
+        }
+
+        if let Some(highlighter) = &self.highlighter {
+            highlights.extend(highlighter.highlight(&line));
+        }
+
+        if let (true, Some(dictionary)) = (self.options.spell, &self.dictionary) {
+            highlights.extend(
+                dictionary
+                    .misspelled(&line)
+                    .into_iter()
+                    .map(|range| (range, Highlight::SpellError)),
+            );
+        }
+
+        // the replacements `inccommand` shows, marked like a selection
+        if let Some((_, _, replaced)) = self.preview.iter().find(|(r, ..)| *r == row) {
+            highlights.extend(
+                replaced
+                    .iter()
+                    .map(|range| (range.clone(), Highlight::Selection)),
+            );
+        }
+
+        if let Some(((start_row, start_col), (end_row, end_col))) = self.selection() {
+            if (start_row..=end_row).contains(&row) {
+                let line_len = self.content.get_line_len(row).unwrap_or(0) as usize;
+                let from = if row == start_row {
+                    start_col as usize
+                } else {
+                    0
+                };
+                // the line break counts as one selected cell, like in vim
+                let to = if row == end_row {
+                    end_col as usize + 1
+                } else {
+                    line_len + 1
+                };
+
+                highlights.push((from..to, Highlight::Selection));
+            }
+        }
+
+        highlights
+    }
+
+    /// The visual selection as ordered, inclusive `(row, col)` ends.
+    pub fn selection(&self) -> Option<((u32, u32), (u32, u32))> {
+        let anchor = self.selection_anchor?;
+        let cursor = (self.render_row, self.render_col);
+
+        Some((cmp::min(anchor, cursor), cmp::max(anchor, cursor)))
+    }
+
+    /// Text covered by the selection, the line break after a line counting as
+    /// its last cell.
+    fn selected_text(&self) -> Option<String> {
+        let ((start_row, start_col), (end_row, end_col)) = self.selection()?;
+        let last_row = self.content.line_count().saturating_sub(1);
+        let mut text = String::new();
+
+        for row in start_row..=end_row {
+            let line: Vec<char> = self.content.get_line(row)?.chars().collect();
+            let from = if row == start_row {
+                start_col as usize
+            } else {
+                0
             };
-        });
+            let to = if row == end_row {
+                end_col as usize
+            } else {
+                line.len()
+            };
+
+            text.extend(line.iter().take(to + 1).skip(from));
+            if to >= line.len() && row < last_row {
+                text.push('\n');
+            }
+        }
+
+        Some(text)
     }
-}
 
-pub trait EditorContentTrait {
-    fn new() -> Self;
+    fn path_under_cursor(&self) -> Option<String> {
+        let line: Vec<char> = self.content.get_line(self.row)?.chars().collect();
+        let col = cmp::min(self.render_col as usize, line.len().checked_sub(1)?);
 
-    fn load_data(&mut self, raw_data: Vec<u8>);
-    fn read_data(&self, buffer: &mut Vec<u8>);
-    fn get_line(&self, i: u32) -> Option<String>;
-    fn get_line_len(&self, i: u32) -> Option<u32>;
-    fn write_char(&mut self, c: char, col: u32, row: u32);
-    fn delete_char(&mut self, col: u32, row: u32) -> Option<char>;
+        for quote in ['"', '\''] {
+            let quotes: Vec<usize> = (0..line.len()).filter(|i| line[*i] == quote).collect();
+
+            // quotes pair up from the line start, `"a" x "b"` has no string around the x
+            let enclosing = quotes
+                .chunks_exact(2)
+                .find(|pair| pair[0] <= col && col <= pair[1]);
+            if let Some(&[open, close]) = enclosing {
+                if close > open + 1 {
+                    return Some(line[open + 1..close].iter().collect());
+                }
+            }
+        }
+
+        if !is_path_char(line[col]) {
+            return None;
+        }
+
+        let start = line[..col]
+            .iter()
+            .rposition(|c| !is_path_char(*c))
+            .map_or(0, |i| i + 1);
+        let end = line[col..]
+            .iter()
+            .position(|c| !is_path_char(*c))
+            .map_or(line.len(), |i| col + i);
+
+        Some(line[start..end].iter().collect())
+    }
+
+    fn resolve_path(&self, path: &str) -> Option<PathBuf> {
+        let path = expand_home(path);
+
+        if path.is_absolute() {
+            return path.exists().then_some(path);
+        }
+
+        let relative_to_file = self
+            .file_path
+            .as_ref()
+            .map(Path::new)
+            .and_then(|file| {
+                if self.directory {
+                    Some(file)
+                } else {
+                    file.parent()
+                }
+            })
+            .map(|dir| dir.join(&path));
+
+        relative_to_file
+            .into_iter()
+            .chain([path])
+            .find(|candidate| candidate.exists())
+    }
+
+    fn read_modelines(&mut self) {
+        if !self.options.modeline {
+            return;
+        }
+
+        let line_count = self.content.line_count();
+        let head = 0..cmp::min(MODELINES, line_count);
+        let tail = line_count.saturating_sub(MODELINES).max(head.end)..line_count;
+
+        for row in head.chain(tail) {
+            if let Some(line) = self.content.get_line(row) {
+                if self.options.apply_modeline(&line) {
+                    info!("applied modeline from line {}", row + 1);
+                }
+            }
+        }
+    }
+
+    /// The bytes of `content` written to disk, ending with a line ending under
+    /// `fixendofline` unless it is empty.
+    fn file_data(&self, content: &T) -> Vec<u8> {
+        let mut buf = Vec::new();
+        content.read_data(&mut buf);
+
+        let line_ending = content.file_format().line_ending();
+        if self.options.fixendofline && !buf.is_empty() && !buf.ends_with(line_ending) {
+            buf.extend_from_slice(line_ending);
+        }
+
+        buf
+    }
+
+    fn backup_path(&self, path: &Path) -> PathBuf {
+        let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+        file_name.push("~");
+
+        let backup_dir = match self.options.backupdir.as_str() {
+            "." => None,
+            dir => Some(expand_home(dir)),
+        };
+
+        if let Some(dir) = backup_dir {
+            match fs::create_dir_all(&dir) {
+                Ok(()) => return dir.join(file_name),
+                Err(e) => warn!(
+                    "can't use backupdir {:?} ({}), writing backup alongside the file",
+                    dir, e
+                ),
+            }
+        }
+
+        path.with_file_name(file_name)
+    }
+
+    fn write_backup(&self, path: &str) {
+        let path = Path::new(path);
+        if !self.options.backup || !path.exists() {
+            return;
+        }
+
+        let backup = self.backup_path(path);
+        if let Err(e) = fs::copy(path, &backup) {
+            warn!("failed to write backup {:?}: {}", backup, e);
+        }
+    }
+
+    /// Asks before loading a file larger than `maxfilesize`, returning whether
+    /// a confirmation is now pending.
+    fn confirm_large_file(&mut self, path: &str, startup: bool) -> bool {
+        let size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        if size <= self.options.maxfilesize as u64 * MEGABYTE {
+            return false;
+        }
+
+        self.message = Some(format!(
+            "\"{}\" is {} MB, open anyway? (y/n)",
+            path,
+            size.div_ceil(MEGABYTE)
+        ));
+        self.confirm = Some(Confirm::LargeFile {
+            path: path.to_string(),
+            startup,
+        });
+        true
+    }
+
+    /// Opens `path` in a new buffer or switches to it; `force` reads the shown
+    /// file again even when that throws its changes away (`:e!`).
+    fn edit_file(&mut self, path: &str, force: bool) -> Result<(), String> {
+        // the shown file was already let through once
+        let shown = self
+            .file_path
+            .as_deref()
+            .is_some_and(|file| same_file(file, path));
+        if !shown && self.confirm_large_file(path, false) {
+            return Ok(());
+        }
+
+        self.load_file(path, force)
+    }
+
+    fn load_file(&mut self, path: &str, force: bool) -> Result<(), String> {
+        if let Some(index) = self.find_buffer(path) {
+            return self.switch_buffer(index);
+        }
+
+        // the shown file is read again, unless that would lose changes
+        let reload = self
+            .file_path
+            .as_deref()
+            .is_some_and(|file| same_file(file, path));
+        if reload && self.modified && !force {
+            return self.switch_buffer(self.buffers.current());
+        }
+
+        // an empty unnamed buffer or a directory listing gives way to the file,
+        // unless another window shows it
+        let previous = self.buffers.current();
+        let keep = !reload
+            && (!self.directory && (self.file_path.is_some() || self.modified)
+                || self.shown_elsewhere(previous));
+        if keep {
+            let shown = self.take_buffer();
+            self.buffers.push(shown);
+        }
+
+        if let Err(e) = self.open_file(path) {
+            if keep {
+                let shown = self.buffers.replace_current(previous);
+                self.show_buffer(shown);
+            }
+            return Err(format!("\"{}\": {}", path, e));
+        }
+
+        self.modified = false;
+        self.undo = UndoTree::default();
+        self.scroll_to(0, 0);
+        self.set_cursor(0, 0);
+        self.should_redraw = Some(Redraw::All);
+        Ok(())
+    }
+
+    /// Fills the shown buffer with `data` that has no file; `:w {path}` is
+    /// needed to save it.
+    pub fn load_data(&mut self, data: Vec<u8>) {
+        self.content.load_data(data);
+        self.read_modelines();
+        self.set_cursor(0, 0);
+        self.should_redraw = Some(Redraw::All);
+    }
+
+    /// Loads text piped to stdin, which counts as unsaved until written
+    /// somewhere, even after undoing every change.
+    pub fn load_stdin(&mut self, data: Vec<u8>) {
+        if looks_binary(&data) {
+            warn!("stdin looks like binary data, opening it read-only");
+            self.binary = true;
+            self.message = Some(String::from("stdin is binary data, opened readonly"));
+        }
+        self.load_data(data);
+        self.modified = true;
+        self.undo.forget_saved();
+    }
+
+    /// Opens `path` in a new window left of the focused one, as files given
+    /// together on the command line are.
+    pub fn split_file(&mut self, path: &str) {
+        let split = self.split_window(Axis::Vertical);
+        if let Err(e) = split.and_then(|_| self.edit_file(path, false)) {
+            self.message = Some(e);
+        }
+    }
+
+    /// Moves the shown file out of the editor, leaving an empty buffer.
+    fn take_buffer(&mut self) -> Buffer<T> {
+        Buffer {
+            content: std::mem::replace(&mut self.content, T::new()),
+            file_path: self.file_path.take(),
+            cursor: (self.row, self.col),
+            scroll: (self.view.left, self.view.top),
+            undo: std::mem::take(&mut self.undo),
+            modified: std::mem::take(&mut self.modified),
+            directory: std::mem::take(&mut self.directory),
+            binary: std::mem::take(&mut self.binary),
+            highlighter: self.highlighter.take(),
+        }
+    }
+
+    fn show_buffer(&mut self, buffer: Buffer<T>) {
+        self.content = buffer.content;
+        self.file_path = buffer.file_path;
+        self.undo = buffer.undo;
+        self.modified = buffer.modified;
+        self.directory = buffer.directory;
+        self.binary = buffer.binary;
+        self.highlighter = buffer.highlighter;
+        self.selection_anchor = None;
+
+        self.scroll_to(buffer.scroll.0 as i32, buffer.scroll.1 as i32);
+        self.set_cursor(buffer.cursor.0, buffer.cursor.1);
+        self.should_redraw = Some(Redraw::All);
+    }
+
+    fn switch_buffer(&mut self, index: usize) -> Result<(), String> {
+        if index >= self.buffers.count() {
+            return Err(format!("buffer {} does not exist", index + 1));
+        }
+
+        if index != self.buffers.current() {
+            let shown = self.take_buffer();
+            let buffer = self.buffers.swap(shown, index);
+            self.show_buffer(buffer);
+        }
+
+        self.message = Some(self.buffer_summary());
+        Ok(())
+    }
+
+    /// The hidden buffer holding the file at `path`.
+    fn find_buffer(&self, path: &str) -> Option<usize> {
+        self.buffers
+            .hidden()
+            .find(|(_, buffer)| {
+                buffer
+                    .file_path
+                    .as_deref()
+                    .is_some_and(|file| same_file(file, path))
+            })
+            .map(|(index, _)| index)
+    }
+
+    /// The buffer numbered `name`, or the only one whose path contains it.
+    fn find_buffer_named(&self, name: &str) -> Result<usize, String> {
+        // buffers are numbered from 1
+        if let Ok(number) = name.parse::<usize>() {
+            return number
+                .checked_sub(1)
+                .ok_or_else(|| format!("buffer {} does not exist", number));
+        }
+
+        let current = (self.buffers.current(), self.file_path.as_deref());
+        let hidden = self
+            .buffers
+            .hidden()
+            .map(|(index, buffer)| (index, buffer.file_path.as_deref()));
+        let matches: Vec<usize> = iter::once(current)
+            .chain(hidden)
+            .filter(|(_, path)| path.is_some_and(|path| path.contains(name)))
+            .map(|(index, _)| index)
+            .collect();
+
+        match matches.as_slice() {
+            [index] => Ok(*index),
+            [] => Err(format!("no matching buffer for {}", name)),
+            _ => Err(format!("more than one match for {}", name)),
+        }
+    }
+
+    fn check_hidden_buffers(&self) -> Result<(), String> {
+        match self.buffers.hidden().find(|(_, buffer)| buffer.modified) {
+            Some((index, buffer)) => Err(format!(
+                "no write since last change for buffer {} \"{}\" (add ! to override)",
+                index + 1,
+                buffer.file_path.as_deref().unwrap_or("[No Name]")
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn buffer_summary(&self) -> String {
+        format!(
+            "buffer {} of {}: \"{}\"{}",
+            self.buffers.current() + 1,
+            self.buffers.count(),
+            self.file_path.as_deref().unwrap_or("[No Name]"),
+            if self.modified { " [+]" } else { "" }
+        )
+    }
+
+    /// One line per buffer for `:ls`, `%` marking the shown one and `+` changes.
+    fn buffer_list(&self) -> Vec<String> {
+        let current = (
+            self.buffers.current(),
+            self.file_path.as_deref(),
+            self.modified,
+        );
+        let hidden = self
+            .buffers
+            .hidden()
+            .map(|(index, buffer)| (index, buffer.file_path.as_deref(), buffer.modified));
+        let mut buffers: Vec<_> = iter::once(current).chain(hidden).collect();
+        buffers.sort_by_key(|(index, _, _)| *index);
+
+        buffers
+            .into_iter()
+            .map(|(index, path, modified)| {
+                format!(
+                    "{:>3} {}{} \"{}\"",
+                    index + 1,
+                    if index == self.buffers.current() {
+                        '%'
+                    } else {
+                        ' '
+                    },
+                    if modified { '+' } else { ' ' },
+                    path.unwrap_or("[No Name]")
+                )
+            })
+            .collect()
+    }
+
+    fn goto_file(&mut self) -> Result<(), String> {
+        let token = self
+            .path_under_cursor()
+            .ok_or("no file name under cursor")?;
+        let path = self
+            .resolve_path(&token)
+            .ok_or(format!("can't find file \"{}\"", token))?;
+
+        self.edit_file(&path.to_string_lossy(), false)
+    }
+
+    /// Opens the file or directory named on the cursor line of a listing.
+    fn open_entry(&mut self) -> Result<(), String> {
+        let dir = PathBuf::from(self.file_path.as_deref().unwrap_or("."));
+        let entry = self.content.get_line(self.row).unwrap_or_default();
+        let path = match entry.as_str() {
+            "../" => dir
+                .canonicalize()
+                .ok()
+                .and_then(|dir| dir.parent().map(Path::to_path_buf))
+                .unwrap_or_else(|| dir.join("..")),
+            name => dir.join(name.trim_end_matches('/')),
+        };
+
+        self.edit_file(&path.to_string_lossy(), false)
+    }
+
+    /// The window commands, typed after `Ctrl-w`: `s` and `v` split the
+    /// window, `w` and `W` go to the next and previous one, `c` closes the
+    /// focused one and `q` quits it like `:q`, `o` keeps only the focused
+    /// one, `=` gives them all the same room and `T` moves the focused one
+    /// to a new tab page.
+    fn window_command(&mut self, c: char) -> Result<(), String> {
+        let ids = self.windows.ids();
+        let focused = ids.iter().position(|id| *id == self.windows.focused());
+        let focused = focused.expect("the focused window is in the layout");
+
+        match c {
+            's' | 'S' => self.split_window(Axis::Horizontal),
+            'v' => self.split_window(Axis::Vertical),
+            'w' => self.focus_window(ids[(focused + 1) % ids.len()]),
+            'W' => self.focus_window(ids[(focused + ids.len() - 1) % ids.len()]),
+            'c' => self.close_window(false),
+            'q' => self.quit_window(false),
+            'o' => self.only_window(false),
+            '=' => {
+                self.windows.equalize();
+                Ok(())
+            }
+            'T' => {
+                self.move_window_to_tab();
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Splits the focused window in two showing the same, the focus going to
+    /// the new one, above or on the left.
+    fn split_window(&mut self, axis: Axis) -> Result<(), String> {
+        // a row of text and a status line in each half, or a column of text
+        // each beside the separator
+        let room = match axis {
+            Axis::Horizontal => self.view.get_height() + 1 >= 4,
+            Axis::Vertical => self.view.get_width() + 1 >= 3,
+        };
+        if !room {
+            return Err(String::from("not enough room"));
+        }
+
+        self.save_window();
+        let focused = self.windows.focused();
+        let new = match axis {
+            Axis::Horizontal => self.windows.split_horizontal(focused),
+            Axis::Vertical => self.windows.split_vertical(focused),
+        };
+
+        if let Some(new) = new {
+            self.windows.focus(new);
+        }
+        if self.options.equalalways {
+            self.windows.equalize();
+        }
+        self.should_redraw = Some(Redraw::All);
+        Ok(())
+    }
+
+    /// Closes the focused window, the focus going to the one next to it,
+    /// unless it shows changes no other window does. Closing the last window
+    /// of a tab page closes the tab.
+    fn close_window(&mut self, force: bool) -> Result<(), String> {
+        let last = self.windows.count() == 1;
+        if last && self.tabs.count() == 1 {
+            return Err(String::from("can't close the last window"));
+        }
+
+        let shown = self.buffers.current();
+        if !force && self.modified && !self.shown_elsewhere(shown) {
+            return Err(String::from(
+                "no write since last change (add ! to override)",
+            ));
+        }
+
+        if last {
+            // the tab on the left takes its place, unless it was the first
+            let current = self.tabs.current();
+            let index = if current == 0 { 1 } else { current - 1 };
+            self.windows = self.tabs.replace_current(index);
+        } else {
+            self.windows.close(self.windows.focused());
+            if self.options.equalalways {
+                self.windows.equalize();
+            }
+        }
+        self.show_window();
+        Ok(())
+    }
+
+    /// `:q`, closing the focused window, or the editor with the last one.
+    fn quit_window(&mut self, force: bool) -> Result<(), String> {
+        match self.windows.count() + self.tabs.count() {
+            2 => self.confirm_unsaved(Confirm::Quit, force),
+            _ => self.close_window(force),
+        }
+    }
+
+    /// Moves the focused window to a new tab page after the shown one, the
+    /// only window there. A window alone in its tab stays where it is.
+    fn move_window_to_tab(&mut self) {
+        if self.windows.count() == 1 {
+            return;
+        }
+
+        self.save_window();
+        let focused = self.windows.focused();
+        let Some(window) = self.windows.close(focused) else {
+            return;
+        };
+        if self.options.equalalways {
+            self.windows.equalize();
+        }
+
+        let shown = std::mem::replace(&mut self.windows, Layout::single(window));
+        self.tabs.push(shown);
+        self.should_redraw = Some(Redraw::All);
+    }
+
+    /// Shows the next tab page, or the previous one, going around at the ends.
+    fn switch_tab(&mut self, forward: bool) {
+        let count = self.tabs.count();
+        if count == 1 {
+            return;
+        }
+
+        let current = self.tabs.current();
+        let index = match forward {
+            true => (current + 1) % count,
+            false => (current + count - 1) % count,
+        };
+
+        self.save_window();
+        let shown = std::mem::replace(&mut self.windows, Layout::new());
+        self.windows = self.tabs.swap(shown, index);
+        self.show_window();
+    }
+
+    pub(crate) fn tab_count(&self) -> usize {
+        self.tabs.count()
+    }
+
+    /// The name of the buffer in the focused window of each tab page, and
+    /// which tab is shown, for the tab line.
+    pub(crate) fn tab_labels(&self) -> (Vec<&str>, usize) {
+        let mut labels: Vec<_> = self
+            .tabs
+            .hidden()
+            .map(|(_, tab)| {
+                let window = tab.window(tab.focused()).copied().unwrap_or_default();
+                self.buffer_name(window.buffer)
+            })
+            .collect();
+
+        let current = self.tabs.current();
+        labels.insert(current, self.buffer_name(self.buffers.current()));
+        (labels, current)
+    }
+
+    /// Closes every window but the focused one, unless one of them shows
+    /// changes no other window does.
+    fn only_window(&mut self, force: bool) -> Result<(), String> {
+        let focused = self.windows.focused();
+        if !force {
+            let unsaved = self
+                .windows
+                .ids()
+                .into_iter()
+                .filter(|id| *id != focused)
+                .filter_map(|id| self.windows.window(id))
+                .map(|window| window.buffer)
+                .find(|index| *index != self.buffers.current() && self.buffer_modified(*index));
+
+            if let Some(index) = unsaved {
+                return Err(format!(
+                    "no write since last change for buffer {} \"{}\" (add ! to override)",
+                    index + 1,
+                    self.buffer_name(index)
+                ));
+            }
+        }
+
+        self.save_window();
+        self.windows.only(focused);
+        self.should_redraw = Some(Redraw::All);
+        Ok(())
+    }
+
+    /// Whether buffer `index` has changes that aren't written.
+    pub(crate) fn buffer_modified(&self, index: usize) -> bool {
+        match index == self.buffers.current() {
+            true => self.modified,
+            false => self
+                .buffers
+                .hidden()
+                .any(|(i, buffer)| i == index && buffer.modified),
+        }
+    }
+
+    /// Keeps where the focused window is, for when it gets the focus back.
+    fn save_window(&mut self) {
+        let window = Window {
+            buffer: self.buffers.current(),
+            cursor: (self.row, self.col),
+            scroll: (self.view.left, self.view.top),
+        };
+
+        if let Some(saved) = self.windows.window_mut(self.windows.focused()) {
+            *saved = window;
+        }
+    }
+
+    fn focus_window(&mut self, id: WindowId) -> Result<(), String> {
+        self.save_window();
+        if !self.windows.focus(id) {
+            return Err(format!("window {} does not exist", id));
+        }
+
+        self.show_window();
+        Ok(())
+    }
+
+    /// Brings back the buffer, cursor and scroll of the focused window.
+    fn show_window(&mut self) {
+        let Some(window) = self.windows.window(self.windows.focused()).copied() else {
+            return;
+        };
+
+        if window.buffer != self.buffers.current() {
+            let shown = self.take_buffer();
+            let buffer = self.buffers.swap(shown, window.buffer);
+            self.show_buffer(buffer);
+        }
+
+        self.scroll_to(window.scroll.0 as i32, window.scroll.1 as i32);
+        self.set_cursor(window.cursor.0, window.cursor.1);
+        self.should_redraw = Some(Redraw::All);
+    }
+
+    /// Whether a window besides the focused one, in any tab page, shows
+    /// buffer `index`.
+    fn shown_elsewhere(&self, index: usize) -> bool {
+        let shows = |tab: &Layout, except: Option<WindowId>| {
+            tab.ids()
+                .into_iter()
+                .filter(|id| Some(*id) != except)
+                .filter_map(|id| tab.window(id))
+                .any(|window| window.buffer == index)
+        };
+
+        shows(&self.windows, Some(self.windows.focused()))
+            || self.tabs.hidden().any(|(_, tab)| shows(tab, None))
+    }
+
+    /// What window `id` shows, the focused one as it is now.
+    pub(crate) fn window_content(&self, id: WindowId) -> Option<(&T, Window)> {
+        if id == self.windows.focused() {
+            let window = Window {
+                buffer: self.buffers.current(),
+                cursor: (self.row, self.col),
+                scroll: (self.view.left, self.view.top),
+            };
+            return Some((&self.content, window));
+        }
+
+        let window = *self.windows.window(id)?;
+        if window.buffer == self.buffers.current() {
+            return Some((&self.content, window));
+        }
+
+        self.buffers
+            .hidden()
+            .find(|(index, _)| *index == window.buffer)
+            .map(|(_, buffer)| (&buffer.content, window))
+    }
+
+    /// The 1-based `(row, col)` of the cursor of window `id`, tabs counted at
+    /// their drawn width, for its status line.
+    pub(crate) fn window_position(&self, id: WindowId) -> Option<(u32, u32)> {
+        let (content, window) = self.window_content(id)?;
+        let (row, col) = window.cursor;
+        let line = content.get_line(row).unwrap_or_default();
+        let before: String = line.chars().take(col as usize).collect();
+        let col = display_width(&before, 0, self.options.tabstop as usize) as u32;
+        Some((row + 1, col + 1))
+    }
+
+    /// The path of buffer `index`, `[No Name]` if it has none.
+    pub(crate) fn buffer_name(&self, index: usize) -> &str {
+        let path = match index == self.buffers.current() {
+            true => self.file_path.as_deref(),
+            false => self
+                .buffers
+                .hidden()
+                .find(|(i, _)| *i == index)
+                .and_then(|(_, buffer)| buffer.file_path.as_deref()),
+        };
+        path.unwrap_or("[No Name]")
+    }
+
+    fn leave_command_mode(&mut self) {
+        self.mode = self.mode_before_command;
+        self.should_redraw.get_or_insert(Redraw::Line(self.row));
+    }
+
+    /// Adds `message` to the history listed by `:messages`.
+    fn remember_message(&mut self, message: String) {
+        if self.messages.len() == MESSAGE_HISTORY {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(message);
+    }
+
+    /// Moves the log records captured while the editor is drawn to the message
+    /// history, the last one being shown unless there is a message already.
+    /// Returns whether there were any.
+    pub(crate) fn show_log(&mut self) -> bool {
+        let records = logger::take_captured();
+        if records.is_empty() {
+            return false;
+        }
+
+        if self.message.is_none() {
+            self.message = records.last().cloned();
+        }
+        for record in records {
+            self.remember_message(record);
+        }
+        true
+    }
+
+    fn complete_command(&mut self, forward: bool) {
+        if self.prompt != ':' {
+            return;
+        }
+
+        match &mut self.wildmenu {
+            Some(menu) => menu.select_next(forward),
+            None => {
+                let mut names = self.options.names();
+                names.extend(["all", "fileformat"].map(String::from));
+                self.wildmenu = Wildmenu::new(&self.command, &names);
+            }
+        }
+
+        if let Some(menu) = &self.wildmenu {
+            self.command.truncate(menu.start);
+            self.command.push_str(menu.current());
+
+            // a single match is final, so the next Tab completes from it: for
+            // a command name that means its arguments, past a space
+            if menu.candidates.len() == 1 {
+                if menu.start == 0 {
+                    self.command.push(' ');
+                }
+                self.wildmenu = None;
+            }
+        }
+        self.command_cursor = self.command.chars().count();
+    }
+
+    // byte index in `command` of its char `cursor`
+    fn command_index(&self, cursor: usize) -> usize {
+        self.command
+            .char_indices()
+            .nth(cursor)
+            .map_or(self.command.len(), |(i, _)| i)
+    }
+
+    fn move_command_cursor(&mut self, movement: Movement) {
+        let len = self.command.chars().count();
+        self.command_cursor = match movement {
+            Movement::LineStart => 0,
+            Movement::LineEnd => len,
+            Movement::Left | Movement::CharLeft(_) => self.command_cursor.saturating_sub(1),
+            Movement::Right | Movement::CharRight(_) => cmp::min(self.command_cursor + 1, len),
+            _ => self.command_cursor,
+        };
+    }
+
+    // deletes the blanks and then the word before the cursor, or all of the
+    // command line before it with `to_start`
+    fn delete_command_back(&mut self, to_start: bool) {
+        let end = self.command_index(self.command_cursor);
+        let start = match to_start {
+            true => 0,
+            false => {
+                let before = self.command[..end].trim_end();
+                before.rfind(char::is_whitespace).map_or(0, |i| i + 1)
+            }
+        };
+
+        self.command_cursor -= self.command[start..end].chars().count();
+        self.command.replace_range(start..end, "");
+    }
+
+    // an empty query repeats the last search in the new direction
+    fn search(&mut self, query: String, forward: bool) -> Result<(), String> {
+        let query = match (query.is_empty(), &self.last_search) {
+            (true, Some((last, _))) => last.clone(),
+            (true, None) => return Err(String::from("no previous search")),
+            (false, _) => query,
+        };
+
+        self.last_search = Some((query.clone(), forward));
+        self.find_next(&query, forward)
+    }
+
+    /// Moves to the next match of `query` after (or before) the cursor,
+    /// wrapping around the file. Matching is case-sensitive.
+    fn find_next(&mut self, query: &str, forward: bool) -> Result<(), String> {
+        let query: Vec<char> = query.chars().collect();
+        let line_count = self.content.line_count();
+        let (row, col) = (self.render_row, self.render_col as usize);
+
+        for step in 0..=line_count {
+            let r = match forward {
+                true => (row + step) % line_count,
+                false => (row + line_count - step % line_count) % line_count,
+            };
+            let Some(line) = self.content.get_line(r) else {
+                continue;
+            };
+
+            let line: Vec<char> = line.chars().collect();
+            let matches_at = |i: &usize| line[*i..].starts_with(&query);
+            let found = match (forward, step) {
+                (true, 0) => (col + 1..line.len()).find(matches_at),
+                (true, _) => (0..line.len()).find(matches_at),
+                (false, 0) => (0..cmp::min(col, line.len())).rev().find(matches_at),
+                (false, _) => (0..line.len()).rev().find(matches_at),
+            };
+
+            if let Some(i) = found {
+                let wrapped = step > 0 && if forward { r <= row } else { r >= row };
+                if wrapped {
+                    self.message = Some(String::from(match forward {
+                        true => "search hit BOTTOM, continuing at TOP",
+                        false => "search hit TOP, continuing at BOTTOM",
+                    }));
+                }
+
+                self.set_cursor(r, i as u32);
+                return Ok(());
+            }
+        }
+
+        Err(format!(
+            "pattern not found: {}",
+            query.iter().collect::<String>()
+        ))
+    }
+
+    fn run_command(&mut self, command: &str) -> Result<(), String> {
+        if let Some(substitute) = Substitute::parse(command) {
+            if self.directory {
+                return Err(String::from("can't change a directory listing"));
+            }
+            if self.readonly() {
+                return Err(String::from("'readonly' option is set"));
+            }
+            return self.substitute(substitute?);
+        }
+
+        let mut args = command.split_whitespace();
+        let (name, force) = match args.next() {
+            Some(name) => match name.strip_suffix('!') {
+                Some(name) => (Some(name), true),
+                None => (Some(name), false),
+            },
+            None => (None, false),
+        };
+
+        let edits = matches!(
+            name,
+            Some("w" | "write" | "wq" | "x" | "xit" | "truncate" | "pad" | "retab" | "ret")
+        );
+        // writing somewhere else leaves the file alone
+        let other_file = matches!(name, Some("w" | "write")) && args.clone().next().is_some();
+        // a listing is never modified, so `:x` just closes it
+        if self.directory && edits && !matches!(name, Some("x" | "xit")) {
+            return Err(String::from("can't change a directory listing"));
+        }
+        if self.readonly() && edits && !force && !other_file {
+            return Err(String::from("'readonly' option is set (add ! to override)"));
+        }
+
+        match name {
+            Some("set" | "se") => {
+                let args: Vec<&str> = args.collect();
+                if args == ["all"] {
+                    let mut list = self.options.list();
+                    list.push(format!("fileformat={}", self.content.file_format().name()));
+                    self.overlay = Some(list);
+                    return Ok(());
+                }
+
+                let cmdheight = self.options.cmdheight;
+                for arg in args {
+                    // a binary file is writable once asked for explicitly
+                    if matches!(arg, "noreadonly" | "noro") {
+                        self.binary = false;
+                    }
+                    match arg.split_once('=') {
+                        Some(("fileformat" | "ff", name)) => self.set_file_format(name)?,
+                        _ => self.options.set(arg)?,
+                    }
+                }
+
+                if self.options.cmdheight != cmdheight {
+                    self.resize_command_area(cmdheight)?;
+                }
+
+                if self.options.spell && self.dictionary.is_none() {
+                    let dictionary = Dictionary::load(&self.options.spellfile).map_err(|e| {
+                        self.options.spell = false;
+                        format!("can't load spellfile {}: {}", self.options.spellfile, e)
+                    })?;
+
+                    self.dictionary = Some(dictionary);
+                }
+                self.should_redraw = Some(Redraw::All);
+            }
+            Some("w" | "write") => match args.next() {
+                Some(path) => self.write_file(path).map_err(|e| e.to_string())?,
+                None => self.save()?,
+            },
+            Some("e" | "edit") => match (args.next(), self.file_path.clone()) {
+                (Some(path), _) => self.edit_file(path, force)?,
+                (None, Some(path)) if force => self.edit_file(&path, true)?,
+                _ => return Err(String::from("no file name")),
+            },
+            Some("bn" | "bnext") => {
+                let next = (self.buffers.current() + 1) % self.buffers.count();
+                self.switch_buffer(next)?;
+            }
+            Some("bp" | "bprevious" | "bN" | "bNext") => {
+                let count = self.buffers.count();
+                self.switch_buffer((self.buffers.current() + count - 1) % count)?;
+            }
+            Some("b" | "buffer") => match args.next() {
+                Some(name) => {
+                    let index = self.find_buffer_named(name)?;
+                    self.switch_buffer(index)?;
+                }
+                None => self.message = Some(self.buffer_summary()),
+            },
+            Some("ls" | "buffers" | "files") => self.overlay = Some(self.buffer_list()),
+            Some(name @ ("sp" | "split" | "vs" | "vsplit")) => {
+                let axis = match name {
+                    "sp" | "split" => Axis::Horizontal,
+                    _ => Axis::Vertical,
+                };
+                self.split_window(axis)?;
+
+                if let Some(path) = args.next() {
+                    self.edit_file(path, force)?;
+                }
+            }
+            Some("on" | "only") => self.only_window(force)?,
+            Some("truncate") => {
+                let removed = self.truncate_lines(parse_line_count(args.next())?);
+                self.message = Some(format!("{} lines removed", removed));
+            }
+            Some("pad") => {
+                let added = self.pad_lines(parse_line_count(args.next())?);
+                self.message = Some(format!("{} lines added", added));
+            }
+            Some("retab" | "ret") => {
+                let changed = self.retab(force, args.next() == Some("all"));
+                self.message = Some(format!("{} lines changed", changed));
+            }
+            Some("q" | "quit") => self.quit_window(force)?,
+            Some("clo" | "close") => self.close_window(force)?,
+            Some(name @ ("wq" | "x" | "xit")) => {
+                if !force {
+                    self.check_hidden_buffers()?;
+                }
+
+                // `:x` only writes when there is something to write
+                if name == "wq" || self.modified {
+                    match args.next() {
+                        Some(path) => self.write_file(path).map_err(|e| e.to_string())?,
+                        None => self.save()?,
+                    }
+                }
+                self.run_confirmed(Confirm::Quit)?;
+            }
+            Some("mes" | "messages") => {
+                // keep the latest messages when they don't all fit on screen
+                let fits = self.view.get_height().saturating_sub(1) as usize;
+                let skip = self.messages.len().saturating_sub(fits);
+
+                if self.messages.len() > skip {
+                    self.overlay = Some(self.messages.iter().skip(skip).cloned().collect());
+                }
+            }
+            Some(name) => match name.parse::<u32>() {
+                Ok(line) => self.goto_line_number(line),
+                Err(_) => return Err(format!("not an editor command: {}", name)),
+            },
+            None => (),
+        }
+
+        Ok(())
+    }
+
+    /// Gives the rows taken or freed by a new `cmdheight` to the view.
+    fn resize_command_area(&mut self, old_cmdheight: u32) -> Result<(), String> {
+        let rows = self.view.get_height() + 1 + old_cmdheight;
+        if self.options.cmdheight >= rows {
+            self.options.cmdheight = old_cmdheight;
+            return Err(String::from("cmdheight is larger than the screen"));
+        }
+
+        self.view.bottom = self.view.top + rows - self.options.cmdheight - 1;
+        self.set_cursor(self.row, self.col);
+        Ok(())
+    }
+
+    fn set_file_format(&mut self, name: &str) -> Result<(), String> {
+        let format = FileFormat::parse(name)
+            .ok_or_else(|| format!("invalid value for fileformat: {}", name))?;
+
+        if format != self.content.file_format() {
+            self.content.set_file_format(format);
+            self.undo.forget_saved();
+            self.modified = true;
+        }
+        self.message = Some(format!("fileformat={}", format.name()));
+
+        Ok(())
+    }
+
+    fn save(&mut self) -> Result<(), String> {
+        if self.file_path.is_none() {
+            return Err(String::from("no file name"));
+        }
+
+        self.prepare_write();
+        self.save_file().map_err(|e| e.to_string())?;
+        self.modified = false;
+        self.undo.mark_saved();
+        Ok(())
+    }
+
+    /// Cleans up the text as the options ask before it is written.
+    fn prepare_write(&mut self) {
+        if self.options.trimws {
+            self.trim_trailing_whitespace();
+        }
+    }
+
+    /// A copy of the buffer without spaces and tabs at line ends.
+    fn trimmed_copy(&self) -> T {
+        let mut data = Vec::new();
+        self.content.read_data(&mut data);
+        let mut copy = T::new();
+        copy.load_data(data);
+        copy.set_file_format(self.content.file_format());
+
+        for row in 0..copy.line_count() {
+            let line = copy.get_line(row).unwrap_or_default();
+            let trimmed = line.trim_end_matches([' ', '\t']);
+            if trimmed.len() != line.len() {
+                copy.replace_line(row, trimmed);
+            }
+        }
+
+        copy
+    }
+
+    /// Removes spaces and tabs at line ends, except on the line being typed in.
+    fn trim_trailing_whitespace(&mut self) -> u32 {
+        let mut changed = 0;
+
+        for row in 0..self.content.line_count() {
+            if row == self.row && self.mode == Mode::Insert {
+                continue;
+            }
+
+            let Some(line) = self.content.get_line(row) else {
+                continue;
+            };
+            let new_line = line.trim_end_matches([' ', '\t']).to_string();
+
+            if new_line != line {
+                self.content.replace_line(row, &new_line);
+                self.undo.record(Change::ReplaceLine {
+                    row,
+                    old: line,
+                    new: new_line,
+                });
+                changed += 1;
+            }
+        }
+
+        if changed > 0 {
+            self.modified = true;
+            self.set_cursor(self.row, self.col);
+            self.should_redraw = Some(Redraw::All);
+        }
+
+        changed
+    }
+
+    fn confirm_unsaved(&mut self, after: Confirm, force: bool) -> Result<(), String> {
+        if !force && matches!(after, Confirm::Quit) {
+            self.check_hidden_buffers()?;
+        }
+
+        if force || !self.modified {
+            return self.run_confirmed(after);
+        }
+
+        if !self.options.confirm {
+            return Err(String::from(
+                "no write since last change (add ! to override)",
+            ));
+        }
+
+        let file_name = self.file_path.as_deref().unwrap_or("Untitled");
+        self.message = Some(format!(
+            "Save changes to \"{}\"? [Y]es, (N)o, (C)ancel",
+            file_name
+        ));
+        self.confirm = Some(after);
+        Ok(())
+    }
+
+    fn run_confirmed(&mut self, after: Confirm) -> Result<(), String> {
+        match after {
+            Confirm::Quit => {
+                self.should_quit = true;
+                Ok(())
+            }
+            Confirm::LargeFile { path, .. } => self.load_file(&path, false),
+        }
+    }
+
+    /// Replaces `pattern` on the cursor line or in the whole file (`:s`, `:%s`).
+    fn substitute(&mut self, substitute: Substitute) -> Result<(), String> {
+        if substitute.pattern.is_empty() {
+            return Ok(());
+        }
+
+        let (mut count, mut last_row) = (0, None);
+
+        for (row, line) in self.substitute_rows(&substitute) {
+            let Some((new_line, replaced)) = substitute.replace(&line) else {
+                continue;
+            };
+            count += replaced.len();
+            last_row = Some(row);
+
+            self.content.replace_line(row, &new_line);
+            self.undo.record(Change::ReplaceLine {
+                row,
+                old: line,
+                new: new_line,
+            });
+        }
+
+        let Some(last_row) = last_row else {
+            return Err(format!("pattern not found: {}", substitute.pattern));
+        };
+
+        self.modified = true;
+        self.goto_line(last_row);
+        self.should_redraw = Some(Redraw::All);
+        self.message = Some(format!("{} substitutions", count));
+        Ok(())
+    }
+
+    /// The lines `substitute` applies to, with their row.
+    fn substitute_rows(&self, substitute: &Substitute) -> Vec<(u32, String)> {
+        let rows = match substitute.whole_file {
+            true => 0..self.content.line_count(),
+            false => self.row..self.row + 1,
+        };
+
+        rows.filter_map(|row| Some((row, self.content.get_line(row)?)))
+            .collect()
+    }
+
+    /// Shows what the `:s` being typed would do, with `inccommand`. The text is
+    /// only changed on screen: the lines are put back before the next preview
+    /// and when the command is run or cancelled.
+    fn preview_substitute(&mut self) {
+        self.clear_preview();
+        if !self.options.inccommand || self.mode != Mode::Command || self.prompt != ':' {
+            return;
+        }
+
+        let Some(Ok(substitute)) = Substitute::parse(&self.command) else {
+            return;
+        };
+        if substitute.pattern.is_empty() || self.readonly() || self.directory {
+            return;
+        }
+
+        for (row, line) in self.substitute_rows(&substitute) {
+            if let Some((new_line, replaced)) = substitute.replace(&line) {
+                self.content.replace_line(row, &new_line);
+                self.preview.push((row, line, replaced));
+            }
+        }
+
+        if !self.preview.is_empty() {
+            self.should_redraw = Some(Redraw::All);
+        }
+    }
+
+    fn clear_preview(&mut self) {
+        if self.preview.is_empty() {
+            return;
+        }
+
+        for (row, line, _) in std::mem::take(&mut self.preview) {
+            self.content.replace_line(row, &line);
+        }
+        self.should_redraw = Some(Redraw::All);
+    }
+
+    fn truncate_lines(&mut self, line_count: u32) -> u32 {
+        let end = self.content.line_count();
+        let lines = self.content.delete_lines(line_count..end);
+        let removed = lines.len() as u32;
+
+        // recorded from the end, as if the lines were deleted one at a time
+        for (row, line) in (line_count..end).zip(lines).rev() {
+            self.undo.record(Change::DeleteLine { row, line });
+        }
+
+        self.modified |= removed > 0;
+        self.set_cursor(self.row, self.col);
+        self.should_redraw = Some(Redraw::All);
+        removed
+    }
+
+    /// Deletes `count` lines from the cursor down (`dd`), leaving a single
+    /// empty line when the whole file goes.
+    fn delete_lines(&mut self, count: u32) {
+        let row = self.row;
+
+        for _ in 0..count {
+            if self.content.line_count() == 1 {
+                let old = self.content.get_line(0).unwrap_or_default();
+                self.content.replace_line(0, "");
+                self.undo.record(Change::ReplaceLine {
+                    row: 0,
+                    old,
+                    new: String::new(),
+                });
+                break;
+            }
+
+            if let Some(line) = self.content.delete_line(row) {
+                self.undo.record(Change::DeleteLine { row, line });
+            }
+
+            if row >= self.content.line_count() {
+                break;
+            }
+        }
+
+        self.modified = true;
+        self.goto_line(row);
+        self.should_redraw = Some(Redraw::All);
+    }
+
+    /// Deletes `count` chars under the cursor (`x`) or before it (`X`),
+    /// stopping at the line's ends.
+    fn delete_chars(&mut self, before: bool, count: u32) {
+        // `$` leaves the cursor past the last char, `x` still means that char
+        let line_len = self.content.get_line_len(self.row).unwrap_or(0);
+        if !before && self.render_col == line_len && !self.options.virtualedit_all() {
+            self.render_col = line_len.saturating_sub(1);
+        }
+
+        for _ in 0..count {
+            let line_len = self.content.get_line_len(self.row).unwrap_or(0);
+            match before {
+                true if self.render_col > 0 => self.render_col -= 1,
+                false if self.render_col < line_len => (),
+                _ => break,
+            }
+            self.delete_char();
+        }
+
+        // stay on the last char when the deleted ones were at the line end
+        let line_len = self.content.get_line_len(self.row).unwrap_or(0);
+        let col = cmp::min(self.render_col, line_len.saturating_sub(1));
+        self.set_cursor(self.row, col);
+        // unless the view scrolled to keep the cursor on screen
+        self.should_redraw.get_or_insert(Redraw::Line(self.row));
+    }
+
+    /// Joins `count` lines from the cursor down into one (`J`), replacing the
+    /// indentation of each joined line with a single space.
+    fn join_lines(&mut self, count: u32) {
+        let row = self.row;
+        let mut col = None;
+
+        for _ in 1..cmp::max(count, 2) {
+            if row + 1 >= self.content.line_count() {
+                break;
+            }
+
+            let line = self.content.get_line(row).unwrap_or_default();
+            let next = self.content.get_line(row + 1).unwrap_or_default();
+            let next = next.trim_start();
+
+            let mut joined = line.clone();
+            if !line.is_empty() && !next.is_empty() {
+                joined.push(' ');
+            }
+            col = Some(line.chars().count() as u32);
+            joined.push_str(next);
+
+            self.content.replace_line(row, &joined);
+            self.undo.record(Change::ReplaceLine {
+                row,
+                old: line,
+                new: joined,
+            });
+            if let Some(line) = self.content.delete_line(row + 1) {
+                self.undo.record(Change::DeleteLine { row: row + 1, line });
+            }
+        }
+
+        if let Some(col) = col {
+            self.modified = true;
+            self.set_cursor(row, col);
+            self.should_redraw = Some(Redraw::All);
+        }
+    }
+
+    /// Indents `count` lines from the cursor down by one `shiftwidth`, or
+    /// dedents them by at most that much. The indentation is rebuilt with tabs
+    /// unless `expandtab` is set, and the cursor stays on the same char.
+    fn shift_lines(&mut self, right: bool, count: u32) {
+        let tabstop = self.options.tabstop as usize;
+        let shiftwidth = self.options.shiftwidth as usize;
+        let last_row = self.content.line_count().saturating_sub(1);
+        let mut changed = false;
+
+        for row in self.row..=cmp::min(self.row + count.saturating_sub(1), last_row) {
+            let Some(line) = self.content.get_line(row) else {
+                continue;
+            };
+            // empty lines aren't given indentation
+            if right && line.is_empty() {
+                continue;
+            }
+
+            let indent_len = line.len() - line.trim_start_matches([' ', '\t']).len();
+            let (indent, rest) = line.split_at(indent_len);
+
+            let indent_width = display_width(indent, 0, tabstop);
+            let width = match right {
+                true => indent_width + shiftwidth,
+                false => indent_width.saturating_sub(shiftwidth),
+            };
+            let mut new_line = match self.options.expandtab {
+                true => " ".repeat(width),
+                false => "\t".repeat(width / tabstop) + &" ".repeat(width % tabstop),
+            };
+            new_line.push_str(rest);
+
+            if new_line == line {
+                continue;
+            }
+
+            if row == self.row {
+                let old_len = indent.chars().count() as u32;
+                let new_len = (new_line.len() - rest.len()) as u32;
+                self.col = (self.col + new_len).saturating_sub(old_len);
+            }
+
+            self.content.replace_line(row, &new_line);
+            self.undo.record(Change::ReplaceLine {
+                row,
+                old: line,
+                new: new_line,
+            });
+            changed = true;
+        }
+
+        if changed {
+            self.modified = true;
+            self.set_cursor(self.row, self.col);
+            self.should_redraw = Some(Redraw::All);
+        }
+    }
+
+    /// Deletes the selection into the clipboard (`d` in visual mode), the line
+    /// break after its last line joining the next one when it's selected.
+    fn delete_selection(&mut self) {
+        let (Some(text), Some((start, end))) = (self.selected_text(), self.selection()) else {
+            return;
+        };
+        let line = |row| self.content.get_line(row).unwrap_or_default();
+
+        let end_line = line(end.0);
+        let joins_next =
+            end.1 as usize >= end_line.chars().count() && end.0 + 1 < self.content.line_count();
+        let (last_row, rest): (u32, String) = match joins_next {
+            true => (end.0 + 1, line(end.0 + 1)),
+            false => (end.0, end_line.chars().skip(end.1 as usize + 1).collect()),
+        };
+
+        let old = line(start.0);
+        let new: String = old
+            .chars()
+            .take(start.1 as usize)
+            .chain(rest.chars())
+            .collect();
+        self.content.replace_line(start.0, &new);
+        self.undo.record(Change::ReplaceLine {
+            row: start.0,
+            old,
+            new,
+        });
+
+        for _ in start.0..last_row {
+            if let Some(line) = self.content.delete_line(start.0 + 1) {
+                self.undo.record(Change::DeleteLine {
+                    row: start.0 + 1,
+                    line,
+                });
+            }
+        }
+
+        self.clipboard = Clipboard {
+            text,
+            linewise: false,
+        };
+        self.modified = true;
+        self.selection_anchor = None;
+        self.mode = Mode::Normal;
+        self.set_cursor(start.0, start.1);
+        self.should_redraw = Some(Redraw::All);
+    }
+
+    /// Puts the cursor back after an undo or redo, or the selection the
+    /// undone change was made from.
+    fn restore(&mut self, restore: Restore) {
+        match restore {
+            Restore::Cursor(row, col) => {
+                self.selection_anchor = None;
+                if self.mode == Mode::Visual {
+                    self.mode = Mode::Normal;
+                }
+                self.set_cursor(row, col);
+            }
+            Restore::Selection((anchor, (row, col))) => {
+                self.set_cursor(row, col);
+                self.selection_anchor = Some(anchor);
+                self.mode = Mode::Visual;
+            }
+        }
+    }
+
+    /// Copies the selection, or `count` lines from the cursor down (`yy`).
+    fn yank(&mut self, count: u32) {
+        if let Some(text) = self.selected_text() {
+            let (start, _) = self.selection().unwrap_or_default();
+            self.clipboard = Clipboard {
+                text,
+                linewise: false,
+            };
+            self.set_cursor(start.0, start.1);
+            return;
+        }
+
+        let last_row = self.content.line_count().saturating_sub(1);
+        let rows = self.row..=cmp::min(self.row + count.saturating_sub(1), last_row);
+
+        self.clipboard = Clipboard {
+            text: rows
+                .filter_map(|row| self.content.get_line(row))
+                .map(|line| line + "\n")
+                .collect(),
+            linewise: true,
+        };
+    }
+
+    /// Inserts the clipboard `count` times after the cursor, or before it with
+    /// `P`; whole lines go below or above the current one.
+    fn paste(&mut self, before: bool, count: u32) {
+        let text = self.clipboard.text.repeat(count as usize);
+        if text.is_empty() {
+            self.message = Some(String::from("nothing to paste"));
+            return;
+        }
+
+        if self.clipboard.linewise {
+            let first_row = if before { self.row } else { self.row + 1 };
+            for (i, line) in text.lines().enumerate() {
+                let row = first_row + i as u32;
+                self.content.insert_line(row, line);
+                self.undo.record(Change::InsertLine {
+                    row,
+                    line: line.to_string(),
+                });
+            }
+
+            self.modified = true;
+            self.goto_line(first_row);
+            self.should_redraw = Some(Redraw::All);
+            return;
+        }
+
+        let line_len = self.content.get_line_len(self.row).unwrap_or(0);
+        let (mut row, mut col) = match before {
+            true => (self.row, cmp::min(self.render_col, line_len)),
+            false => (self.row, cmp::min(self.render_col + 1, line_len)),
+        };
+        let mut end = (row, col);
+
+        for c in text.chars() {
+            self.row = row;
+            self.render_col = col;
+            self.write_char(c);
+            end = (row, col);
+
+            if c == '\n' {
+                row += 1;
+                col = 0;
+            } else {
+                col += 1;
+            }
+        }
+
+        self.set_cursor(end.0, end.1);
+        self.should_redraw = Some(Redraw::All);
+    }
+
+    fn pad_lines(&mut self, line_count: u32) -> u32 {
+        let mut added = 0;
+
+        while self.content.line_count() < line_count {
+            let row = self.content.line_count();
+            self.content.insert_line(row, "");
+            self.undo.record(Change::InsertLine {
+                row,
+                line: String::new(),
+            });
+            added += 1;
+        }
+
+        self.modified |= added > 0;
+        self.should_redraw = Some(Redraw::All);
+        added
+    }
+
+    fn retab(&mut self, to_tabs: bool, every_tab: bool) -> u32 {
+        let tabstop = self.options.tabstop as usize;
+        let mut changed = 0;
+
+        for row in 0..self.content.line_count() {
+            let Some(line) = self.content.get_line(row) else {
+                continue;
+            };
+
+            let indent_len = line.len() - line.trim_start_matches([' ', '\t']).len();
+            let (indent, rest) = line.split_at(indent_len);
+
+            let indent_width = display_width(indent, 0, tabstop);
+            let mut new_line = if to_tabs {
+                "\t".repeat(indent_width / tabstop) + &" ".repeat(indent_width % tabstop)
+            } else {
+                " ".repeat(indent_width)
+            };
+
+            if every_tab && !to_tabs {
+                for c in rest.chars() {
+                    match c {
+                        '\t' => {
+                            let width = display_width("\t", new_line.chars().count(), tabstop);
+                            new_line.push_str(&" ".repeat(width));
+                        }
+                        c => new_line.push(c),
+                    }
+                }
+            } else {
+                new_line.push_str(rest);
+            }
+
+            if new_line != line {
+                self.content.replace_line(row, &new_line);
+                self.undo.record(Change::ReplaceLine {
+                    row,
+                    old: line,
+                    new: new_line,
+                });
+                changed += 1;
+            }
+        }
+
+        if changed > 0 {
+            self.modified = true;
+            self.set_cursor(self.row, self.col);
+            self.should_redraw = Some(Redraw::All);
+        }
+
+        changed
+    }
+
+    fn insert_char(&mut self, c: char) {
+        if c == '\t' && self.options.expandtab {
+            self.should_redraw = Some(Redraw::Line(self.row));
+
+            for _ in 0..self.options.tabstop {
+                self.write_char(' ');
+                self.move_cursor(Movement::Right);
+            }
+            return;
+        }
+
+        if c == '\n' {
+            self.should_redraw = Some(Redraw::All);
+        } else {
+            self.should_redraw = Some(Redraw::Line(self.row));
+        }
+
+        if self.options.autopairs && self.insert_pair(c) {
+            return;
+        }
+
+        // the new line starts with the indentation before the cursor
+        let indent: Vec<char> = match c == '\n' && self.options.autoindent {
+            true => self
+                .content
+                .get_line(self.row)
+                .unwrap_or_default()
+                .chars()
+                .take(self.render_col as usize)
+                .take_while(|c| *c == ' ' || *c == '\t')
+                .collect(),
+            false => Vec::new(),
+        };
+
+        self.write_char(c);
+
+        if c == '\n' {
+            self.set_cursor(self.row + 1, 0);
+            for c in indent {
+                self.write_char(c);
+                self.set_cursor(self.row, self.render_col + 1);
+            }
+        } else {
+            self.move_cursor(Movement::Right);
+        }
+    }
+
+    /// Types over a closing char already under the cursor, or inserts an
+    /// opening char along with its closing one. False when `c` isn't a pair.
+    fn insert_pair(&mut self, c: char) -> bool {
+        let line: Vec<char> = self
+            .content
+            .get_line(self.row)
+            .unwrap_or_default()
+            .chars()
+            .collect();
+        let col = self.render_col as usize;
+        let prev = col.checked_sub(1).and_then(|i| line.get(i));
+
+        if line.get(col) == Some(&c) && AUTO_PAIRS.iter().any(|(_, close)| *close == c) {
+            self.move_cursor(Movement::Right);
+            return true;
+        }
+
+        match AUTO_PAIRS.iter().find(|(open, _)| *open == c) {
+            // an apostrophe inside a word isn't a quote
+            Some((open, close)) if open == close && prev.is_some_and(|c| c.is_alphanumeric()) => {
+                false
+            }
+            Some((open, close)) => {
+                self.write_char(*open);
+                self.move_cursor(Movement::Right);
+                self.write_char(*close);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether the cursor sits between an opening char and its closing one.
+    fn in_empty_pair(&self) -> bool {
+        let line: Vec<char> = self
+            .content
+            .get_line(self.row)
+            .unwrap_or_default()
+            .chars()
+            .collect();
+        let col = self.render_col as usize;
+
+        col > 0
+            && AUTO_PAIRS.iter().any(|(open, close)| {
+                line.get(col - 1) == Some(open) && line.get(col) == Some(close)
+            })
+    }
+
+    // replays the text typed since entering insert mode for a `{count}i`
+    fn repeat_insert(&mut self) {
+        let text = std::mem::take(&mut self.inserted);
+
+        for _ in 1..std::mem::replace(&mut self.insert_repeat, 1) {
+            text.chars().for_each(|c| self.insert_char(c));
+        }
+
+        self.should_redraw = Some(Redraw::All);
+    }
+
+    fn write_char(&mut self, c: char) {
+        let line_len = self.content.get_line_len(self.row).unwrap_or(0);
+        for col in line_len..self.render_col {
+            self.content.write_char(' ', col, self.row);
+            self.undo.record(Change::InsertChar {
+                row: self.row,
+                col,
+                c: ' ',
+            });
+        }
+
+        self.content.write_char(c, self.render_col, self.row);
+        self.undo.record(Change::InsertChar {
+            row: self.row,
+            col: self.render_col,
+            c,
+        });
+        self.modified = true;
+    }
+
+    fn delete_char(&mut self) -> Option<char> {
+        if self.render_col > self.content.get_line_len(self.row).unwrap_or(0) {
+            return None;
+        }
+
+        let deleted = self.content.delete_char(self.render_col, self.row);
+        if let Some(c) = deleted {
+            self.undo.record(Change::DeleteChar {
+                row: self.row,
+                col: self.render_col,
+                c,
+            });
+            self.modified = true;
+        }
+        deleted
+    }
+
+    /// Adds the rows between `a` and `b` to what needs redrawing.
+    fn redraw_rows(&mut self, a: u32, b: u32) {
+        let (start, end) = (cmp::min(a, b), cmp::max(a, b));
+
+        self.should_redraw = match self.should_redraw {
+            Some(Redraw::All) => Some(Redraw::All),
+            Some(Redraw::Line(row)) => {
+                Some(Redraw::Range(cmp::min(row, start), cmp::max(row, end)))
+            }
+            Some(Redraw::Range(from, to)) => {
+                Some(Redraw::Range(cmp::min(from, start), cmp::max(to, end)))
+            }
+            None => Some(Redraw::Range(start, end)),
+        };
+    }
+
+    fn scroll_to(&mut self, horizontal: i32, vertical: i32) {
+        let horizontal_size = self.view.get_width();
+        self.view.left = cmp::max(0, horizontal) as u32;
+        self.view.right = self.view.left + horizontal_size;
+
+        self.render_col = cmp::min(cmp::max(self.view.left, self.col), self.view.right);
+
+        let vertical_size = self.view.get_height();
+        self.view.top = cmp::max(0, vertical) as u32;
+        self.view.bottom = self.view.top + vertical_size;
+
+        self.render_row = cmp::min(cmp::max(self.view.top, self.row), self.view.bottom);
+
+        if let Some(n) = self.content.get_line_len(self.render_row) {
+            self.render_col = self.clamp_col(self.col, n);
+        }
+
+        self.should_redraw = Some(Redraw::All);
+    }
+
+    /// Scrolls the view and the cursor by `count` lines, half the view by default.
+    fn scroll_half_page(&mut self, down: bool, count: Option<u32>) {
+        let rows = self.view.get_height() + 1;
+        let lines = count.unwrap_or(cmp::max(rows / 2, 1)) as i32;
+        let lines = if down { lines } else { -lines };
+
+        let last_top = self.content.line_count().saturating_sub(rows) as i32;
+        let top = (self.view.top as i32 + lines).clamp(0, cmp::max(last_top, 0));
+        let row = cmp::max(self.row as i32 + lines, 0) as u32;
+
+        self.scroll_to(self.view.left as i32, top);
+        self.set_cursor(row, self.col);
+    }
+
+    /// Screen column where char `col` of line `row` is drawn, tabs counted at
+    /// their drawn width and columns past the end one cell each.
+    pub fn display_col(&self, row: u32, col: u32) -> u32 {
+        let line = self.content.get_line(row).unwrap_or_default();
+        let before: String = line.chars().take(col as usize).collect();
+        let past_end = col as usize - before.chars().count();
+
+        (display_width(&before, 0, self.options.tabstop as usize) + past_end) as u32
+    }
+
+    /// Char of line `row` drawn at screen column `display`, the inverse of
+    /// `display_col`; a tab is hit anywhere in the cells it fills.
+    pub fn char_col(&self, row: u32, display: u32) -> u32 {
+        let line = self.content.get_line(row).unwrap_or_default();
+        let tabstop = self.options.tabstop as usize;
+
+        let mut width = 0;
+        for (i, c) in line.chars().enumerate() {
+            let next = match c {
+                '\t' => (width / tabstop + 1) * tabstop,
+                _ => width + 1,
+            };
+            if next > display as usize {
+                return i as u32;
+            }
+            width = next;
+        }
+
+        line.chars().count() as u32 + (display as usize - width) as u32
+    }
+
+    /// Screen rows taken by line `row`, more than one when `wrap` breaks it.
+    pub fn display_rows(&self, row: u32) -> u32 {
+        self.row_starts(row).len() as u32
+    }
+
+    /// Screen columns where the rows of line `row` start, just `[0]` unless
+    /// `wrap` breaks it; with `linebreak` a row ends after a blank rather than
+    /// in the middle of a word.
+    pub fn row_starts(&self, row: u32) -> Vec<u32> {
+        let width = self.view.get_width() as usize;
+        if !self.options.wrap || width == 0 {
+            return vec![0];
+        }
+
+        // whether each cell is part of a blank, tabs filling several
+        let line = self.content.get_line(row).unwrap_or_default();
+        let tabstop = self.options.tabstop as usize;
+        let mut blanks = Vec::new();
+        for c in line.chars() {
+            let cells = match c {
+                '\t' => (blanks.len() / tabstop + 1) * tabstop - blanks.len(),
+                _ => 1,
+            };
+            blanks.extend(iter::repeat_n(c == ' ' || c == '\t', cells));
+        }
+
+        let mut starts = vec![0];
+        let mut start = 0;
+        while blanks.len() - start > width {
+            let mut next = start + width;
+            // a word cut by the edge moves down whole, unless it fills the row
+            if self.options.linebreak && !blanks[next] {
+                if let Some(blank) = (start + 1..next).rev().find(|&i| blanks[i]) {
+                    next = blank + 1;
+                }
+            }
+
+            starts.push(next as u32);
+            start = next;
+        }
+
+        starts
+    }
+
+    // with `wrap` the view never scrolls sideways, and its top moves down until
+    // the wrapped rows up to the cursor line fit on screen
+    fn scroll_wrapped(&mut self) {
+        let screen_rows = self.view.get_height() + 1;
+        // every line takes at least one row, no need to look further up
+        let mut top = self
+            .view
+            .top
+            .clamp(self.row.saturating_sub(screen_rows), self.row);
+        let mut used: u32 = (top..=self.row).map(|row| self.display_rows(row)).sum();
+
+        while used > screen_rows && top < self.row {
+            used -= self.display_rows(top);
+            top += 1;
+        }
+
+        if top != self.view.top || self.view.left != 0 {
+            self.scroll_to(0, top as i32);
+        }
+    }
+
+    fn goto_cursor(&mut self) {
+        if self.options.wrap {
+            return;
+        }
+
+        // the view scrolls by screen columns, so tabs count at their width
+        let col = self.display_col(self.row, self.render_col);
+        if col < self.view.left {
+            self.scroll_to(col as i32, self.view.top as i32);
+        } else if col > self.view.right {
+            self.scroll_to((col - self.view.get_width()) as i32, self.view.top as i32);
+        }
+    }
+}
+
+impl<T: EditorContentTrait> EditorIO for Editor<T> {
+    fn open_file(&mut self, path: &str) -> Result<(), std::io::Error> {
+        self.binary = false;
+        self.directory = Path::new(path).is_dir();
+        if self.directory {
+            self.content = T::new();
+            self.content.load_data(list_directory(path)?.into_bytes());
+            self.file_path = Some(path.to_string());
+            self.highlighter = None;
+            return Ok(());
+        }
+
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            // a new file, created on the first save
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                self.content = T::new();
+                self.file_path = Some(path.to_string());
+                self.highlighter = syntax::for_path(path);
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+        let mut buf: Vec<u8> = Vec::new();
+        file.read_to_end(&mut buf)?;
+
+        if looks_binary(&buf) {
+            warn!("'{}' looks like a binary file, opening it read-only", path);
+            self.binary = true;
+            self.message = Some(format!("\"{}\" is a binary file, opened readonly", path));
+        }
+        self.content.load_data(buf);
+        self.file_path = Some(path.to_string());
+        self.highlighter = syntax::for_path(path);
+        self.read_modelines();
+        Ok(())
+    }
+
+    fn save_file(&self) -> Result<(), std::io::Error> {
+        if let Some(path) = &self.file_path {
+            self.write_backup(path);
+            let mut file = File::create(path)?;
+            file.write_all(&self.file_data(&self.content))?;
+            return Ok(());
+        }
+
+        error!("there isn't any file opened!");
+        Ok(())
+    }
+
+    fn write_file(&self, path: &str) -> Result<(), std::io::Error> {
+        self.write_backup(path);
+        let mut file = File::create(path)?;
+
+        // another file gets trimmed lines, the buffer keeps its own
+        let data = match self.options.trimws {
+            true => self.file_data(&self.trimmed_copy()),
+            false => self.file_data(&self.content),
+        };
+        file.write_all(&data)?;
+        Ok(())
+    }
+}
+
+impl<T: EditorContentTrait> EditorEvent for Editor<T> {
+    fn on_idle(&mut self) {
+        self.show_log();
+    }
+
+    fn on_autosave(&mut self) {
+        if self.modified && !self.readonly() {
+            if let Some(path) = self.file_path.clone() {
+                match self.save() {
+                    Ok(()) => info!("autosaved '{}'", path),
+                    Err(message) => self.message = Some(message),
+                }
+            }
+        }
+
+        self.show_log();
+    }
+
+    fn on_load_file(&mut self, path: String) {
+        info!("loading file '{}'", path);
+
+        // the path is only kept once the file is loaded, a declined large
+        // file leaves the editor without one
+        if !self.confirm_large_file(&path, true) {
+            if let Err(e) = self.open_file(path.as_str()) {
+                self.message = Some(format!("\"{}\": {}", path, e));
+            }
+        }
+        self.show_log();
+    }
+
+    fn on_action(&mut self, actions: Vec<Action>) {
+        self.should_redraw = None;
+        // laying the windows out again keeps what the last command said
+        if !matches!(actions[..], [Action::Resize(..)]) {
+            self.message = None;
+        }
+
+        // undoing an edit made from visual mode brings its selection back
+        let selection = self
+            .selection_anchor
+            .map(|anchor| (anchor, (self.render_row, self.render_col)));
+        self.undo.set_selection(selection);
+
+        let completing = actions
+            .iter()
+            .any(|action| matches!(action, Action::CompleteCommand(_)));
+        if !completing && self.wildmenu.take().is_some() {
+            self.should_redraw = Some(Redraw::All);
+        }
+
+        let is_prefix = actions
+            .iter()
+            .any(|action| matches!(action, Action::PushCount(_) | Action::PushOperator(_)));
+        let count = if is_prefix {
+            None
+        } else {
+            self.pending_op = None;
+            self.pending_count.take()
+        };
+
+        // a count repeats a motion typed on its own, not the moves `a` or `A` come with
+        let repeat = match actions.as_slice() {
+            [Action::Move(
+                Movement::LineStart
+                | Movement::LineEnd
+                | Movement::ScreenLineStart
+                | Movement::ScreenLineEnd,
+            )] => 1,
+            [Action::Move(_)] => count.unwrap_or(1),
+            _ => 1,
+        };
+
+        actions.iter().for_each(|action| {
+            match *action {
+                _ if (self.readonly() || self.directory) && action.is_edit() => {
+                    self.message = Some(String::from("buffer is read-only"));
+                }
+                Action::PushCount(digit) => match self.pending_count {
+                    Some(count) => {
+                        self.pending_count = Some(count.saturating_mul(10).saturating_add(digit))
+                    }
+                    None if digit == 0 => self.move_cursor(Movement::LineStart),
+                    None => self.pending_count = Some(digit),
+                },
+                Action::PushOperator(op) => {
+                    self.pending_op = Some(op);
+                }
+                Action::Move(mov) => {
+                    let row = self.render_row;
+                    for _ in 0..repeat {
+                        self.move_cursor(mov);
+                    }
+
+                    // the selection changed on every line the cursor crossed
+                    if self.selection_anchor.is_some() {
+                        self.redraw_rows(row, self.render_row);
+                    }
+                }
+                Action::ChangeMode(Mode::Visual) if self.mode == Mode::Normal => {
+                    self.selection_anchor = Some((self.render_row, self.render_col));
+                    self.mode = Mode::Visual;
+                    self.should_redraw = Some(Redraw::Line(self.render_row));
+                }
+                Action::DeleteSelection => self.delete_selection(),
+                Action::SwapSelectionEnds => {
+                    if let Some((row, col)) = self.selection_anchor {
+                        self.selection_anchor = Some((self.render_row, self.render_col));
+                        self.set_cursor(row, col);
+                    }
+                }
+                Action::ChangeMode(Mode::Command) if self.mode != Mode::Command => {
+                    self.prompt = ':';
+                    self.command.clear();
+                    self.command_cursor = 0;
+                    self.mode_before_command = self.mode;
+                    self.mode = Mode::Command;
+                }
+                Action::ChangeMode(Mode::Insert) if self.mode != Mode::Insert => {
+                    self.insert_repeat = count.unwrap_or(1);
+                    self.inserted.clear();
+                    self.mode = Mode::Insert;
+                }
+                Action::ChangeMode(mode) => {
+                    if self.mode == Mode::Command {
+                        self.command.clear();
+                    }
+
+                    if self.mode == Mode::Insert {
+                        self.repeat_insert();
+                    }
+
+                    if self.mode == Mode::Visual && self.selection_anchor.take().is_some() {
+                        self.should_redraw = Some(Redraw::All);
+                    }
+
+                    self.mode = mode;
+                }
+                Action::Search(forward) if self.mode != Mode::Command => {
+                    self.prompt = if forward { '/' } else { '?' };
+                    self.command.clear();
+                    self.command_cursor = 0;
+                    self.mode_before_command = self.mode;
+                    self.mode = Mode::Command;
+                }
+                // e.g. a binding chaining `command_mode` and `search_forward`,
+                // the command line already being typed is kept
+                Action::Search(_) => {}
+                Action::SearchNext(reverse) => match self.last_search.clone() {
+                    Some((query, forward)) => {
+                        if let Err(message) = self.find_next(&query, forward != reverse) {
+                            self.message = Some(message);
+                        }
+                    }
+                    None => self.message = Some(String::from("no previous search")),
+                },
+                Action::CommandChar(c) => {
+                    let index = self.command_index(self.command_cursor);
+                    self.command.insert(index, c);
+                    self.command_cursor += 1;
+                    self.preview_substitute();
+                }
+                Action::CommandMove(movement) => self.move_command_cursor(movement),
+                Action::CommandDeleteBack(to_start) => {
+                    self.delete_command_back(to_start);
+                    self.preview_substitute();
+                }
+                Action::CompleteCommand(forward) => {
+                    self.complete_command(forward);
+                    self.preview_substitute();
+                }
+                Action::CommandBackspace => {
+                    if self.command.is_empty() {
+                        self.leave_command_mode();
+                    } else if self.command_cursor > 0 {
+                        self.command_cursor -= 1;
+                        let index = self.command_index(self.command_cursor);
+                        self.command.remove(index);
+                    }
+                    self.preview_substitute();
+                }
+                Action::CancelCommand => {
+                    self.clear_preview();
+                    self.command.clear();
+                    self.leave_command_mode();
+                }
+                Action::RunCommand => {
+                    self.clear_preview();
+                    let command = std::mem::take(&mut self.command);
+                    self.leave_command_mode();
+
+                    let result = match self.prompt {
+                        ':' => self.run_command(&command),
+                        prompt => self.search(command, prompt == '/'),
+                    };
+
+                    if let Err(message) = result {
+                        self.message = Some(message);
+                    }
+                }
+                Action::Interrupt => {
+                    self.message = Some(String::from(
+                        "Type :q! and press Enter to abandon all changes and exit",
+                    ));
+                }
+                Action::DismissOverlay => {
+                    self.overlay = None;
+                    self.should_redraw = Some(Redraw::All);
+                }
+                Action::ConfirmAnswer(answer) => {
+                    if let Some(after) = self.confirm.take() {
+                        let result = match (answer.to_ascii_lowercase(), after) {
+                            ('y', after @ Confirm::LargeFile { .. }) => self.run_confirmed(after),
+                            ('n', Confirm::LargeFile { startup, .. }) => {
+                                self.should_quit = startup;
+                                Ok(())
+                            }
+                            ('y', after) => self.save().and_then(|_| self.run_confirmed(after)),
+                            ('n', after) => self.run_confirmed(after),
+                            _ => Ok(()),
+                        };
+
+                        if let Err(message) = result {
+                            self.message = Some(message);
+                        }
+                    }
+                }
+                Action::InsertChar(c) => {
+                    self.inserted.push(c);
+                    self.insert_char(c);
+                }
+                Action::Backspace => {
+                    self.inserted.pop();
+
+                    if self.render_col == 0 {
+                        self.should_redraw = Some(Redraw::All);
+                    } else {
+                        self.should_redraw = Some(Redraw::Line(self.row));
+                    }
+
+                    let pair = self.options.autopairs && self.in_empty_pair();
+                    self.move_cursor(Movement::Left);
+                    self.delete_char();
+                    if pair {
+                        self.delete_char();
+                    }
+                }
+                Action::Delete => {
+                    let deleted_char = self.delete_char();
+                    match deleted_char {
+                        Some('\n') => {
+                            self.should_redraw = Some(Redraw::All);
+                        }
+                        _ => {
+                            self.should_redraw = Some(Redraw::Line(self.row));
+                        }
+                    }
+                }
+                Action::DeleteLine => self.delete_lines(count.unwrap_or(1)),
+                Action::DeleteChar(before) => self.delete_chars(before, count.unwrap_or(1)),
+                Action::JoinLines => self.join_lines(count.unwrap_or(2)),
+                Action::ShiftLines(right) => self.shift_lines(right, count.unwrap_or(1)),
+                Action::Yank => self.yank(count.unwrap_or(1)),
+                Action::Paste(before) => self.paste(before, count.unwrap_or(1)),
+                // a typed count is checked, a bare `G` just means the last line
+                Action::GotoLine(line) => match count {
+                    Some(count) => self.goto_line_number(count),
+                    None => self.goto_line(line.saturating_sub(1)),
+                },
+                Action::GotoPercent => match count {
+                    Some(percent) => self.goto_percent(percent),
+                    None => self.goto_match(),
+                },
+                Action::GotoPosition(row, col) => {
+                    let old_row = self.render_row;
+                    self.set_cursor(row, col);
+
+                    if self.selection_anchor.is_some() {
+                        self.redraw_rows(old_row, self.render_row);
+                    }
+                }
+                Action::GotoFile => {
+                    if let Err(message) = self.goto_file() {
+                        self.message = Some(message);
+                    }
+                }
+                Action::OpenEntry if self.directory => {
+                    if let Err(message) = self.open_entry() {
+                        self.message = Some(message);
+                    }
+                }
+                Action::OpenEntry => self.move_cursor(Movement::Down),
+                Action::ShowChar => {
+                    let c = self
+                        .content
+                        .get_line(self.row)
+                        .and_then(|line| line.chars().nth(self.render_col as usize));
+                    self.message = Some(describe_char(c));
+                }
+                // the cursor keeps its line while it is visible, otherwise it
+                // stays on the edge of the view it would leave through
+                Action::ScrollBy(steps) => {
+                    self.scroll_to(self.view.left as i32, self.view.top as i32 + steps);
+                    self.set_cursor(self.render_row, self.col);
+                }
+                Action::ScrollHalfPage(down) => self.scroll_half_page(down, count),
+                // Action::ScrollTo(line_num) => {
+                // self.scroll_to(self.view.left as i32, line_num as i32);
+                // }
+                Action::Resize(width, height) => {
+                    // a terminal too small for any text still keeps one cell
+                    let (width, height) = (cmp::max(width, 1), cmp::max(height, 1));
+                    self.view.bottom = self.view.top + (height as u32).saturating_sub(1);
+                    self.view.right = self.view.left + (width as u32).saturating_sub(1);
+
+                    self.should_redraw = Some(Redraw::All);
+                }
+                Action::Undo | Action::Redo | Action::Travel(_) => {
+                    for _ in 0..count.unwrap_or(1) {
+                        let restore = match *action {
+                            Action::Undo => self.undo.undo(&mut self.content),
+                            Action::Travel(later) => self.undo.travel(&mut self.content, later),
+                            _ => self.undo.redo(&mut self.content),
+                        };
+
+                        match restore {
+                            Some(restore) => self.restore(restore),
+                            None => {
+                                self.message = Some(String::from(match *action {
+                                    Action::Undo | Action::Travel(false) => {
+                                        "already at oldest change"
+                                    }
+                                    _ => "already at newest change",
+                                }));
+                                break;
+                            }
+                        }
+                    }
+                    // undoing back to the written text leaves nothing to save
+                    self.modified = !self.undo.is_saved();
+                    self.should_redraw = Some(Redraw::All);
+                }
+                Action::SaveFile => {
+                    if let Err(message) = self.save() {
+                        self.message = Some(message);
+                    }
+                }
+                Action::AskRedraw(redraw) => {
+                    self.should_redraw = Some(redraw);
+                }
+                Action::Quit => {
+                    if let Err(message) = self.confirm_unsaved(Confirm::Quit, false) {
+                        self.message = Some(message);
+                    }
+                }
+                Action::Tab(forward) => self.switch_tab(forward),
+                Action::Window(c) => {
+                    if let Err(message) = self.window_command(c) {
+                        self.message = Some(message);
+                    }
+                }
+                Action::None => {}
+            };
+        });
+
+        if self.options.wrap {
+            self.scroll_wrapped();
+        }
+
+        if self.mode != Mode::Insert {
+            self.undo.commit(self.options.undolevels as usize);
+        }
+
+        if let Some(message) = self.message.clone() {
+            self.remember_message(message);
+        }
+        self.show_log();
+    }
+}
+
+/// Index of the wrapped row, given by `row_starts`, that screen column
+/// `display` is on; the end of a line that fills its last row stays on it.
+pub fn row_at(starts: &[u32], display: u32) -> usize {
+    starts.partition_point(|start| *start <= display) - 1
+}
+
+/// Formats `c` the way `ga` reports it, e.g. `<a> 97, Hex 61, Oct 141`.
+fn describe_char(c: Option<char>) -> String {
+    let Some(c) = c else {
+        return String::from("NUL");
+    };
+
+    let code = c as u32;
+    let shown = match c {
+        c if c.is_control() && code < 0x20 => format!("^{}", char::from_u32(code + 0x40).unwrap()),
+        '\x7f' => String::from("^?"),
+        c => c.to_string(),
+    };
+
+    let mut description = format!("<{}> {}, Hex {:02x}, Oct {:03o}", shown, code, code, code);
+    if !c.is_ascii() {
+        description.push_str(&format!(", U+{:04X}", code));
+    }
+
+    description
+}
+
+/// A parsed `:s/pattern/replacement/[g]` or `:%s/...` command.
+struct Substitute {
+    pattern: String,
+    replacement: String,
+    global: bool,
+    whole_file: bool,
+}
+
+impl Substitute {
+    /// `line` with the matches replaced, and the char ranges the replacements
+    /// take in it; `None` when nothing matches.
+    fn replace(&self, line: &str) -> Option<(String, Vec<Range<usize>>)> {
+        let limit = if self.global { usize::MAX } else { 1 };
+        let (mut new_line, mut replaced, mut end) = (String::new(), Vec::new(), 0);
+
+        for (index, _) in line.match_indices(&self.pattern).take(limit) {
+            new_line.push_str(&line[end..index]);
+            let start = new_line.chars().count();
+            new_line.push_str(&self.replacement);
+            replaced.push(start..start + self.replacement.chars().count());
+            end = index + self.pattern.len();
+        }
+
+        if replaced.is_empty() {
+            return None;
+        }
+        new_line.push_str(&line[end..]);
+        Some((new_line, replaced))
+    }
+
+    /// `None` when `command` isn't a substitution at all.
+    fn parse(command: &str) -> Option<Result<Self, String>> {
+        let (whole_file, rest) = match command.strip_prefix('%') {
+            Some(rest) => (true, rest),
+            None => (false, command),
+        };
+        let rest = rest.strip_prefix("s/")?;
+
+        // split on `/` not preceded by a backslash, `\/` being a literal slash
+        let mut parts = vec![String::new()];
+        let mut chars = rest.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if chars.peek() == Some(&'/') => {
+                    chars.next();
+                    parts.last_mut()?.push('/');
+                }
+                '/' => parts.push(String::new()),
+                c => parts.last_mut()?.push(c),
+            }
+        }
+
+        let result = match parts.as_slice() {
+            [_] => Err(String::from("missing replacement")),
+            [pattern, replacement, flags @ ..] if flags.len() <= 1 => {
+                let flags = flags.first().map(String::as_str).unwrap_or("");
+                match flags {
+                    "" | "g" => Ok(Self {
+                        pattern: pattern.clone(),
+                        replacement: replacement.clone(),
+                        global: flags == "g",
+                        whole_file,
+                    }),
+                    _ => Err(format!("invalid flags: {}", flags)),
+                }
+            }
+            _ => Err(String::from("too many delimiters")),
+        };
+
+        Some(result)
+    }
+}
+
+/// Whether both paths name the same file, spelled the same or not.
+fn same_file(a: &str, b: &str) -> bool {
+    a == b
+        || matches!(
+            (fs::canonicalize(a), fs::canonicalize(b)),
+            (Ok(a), Ok(b)) if a == b
+        )
+}
+
+fn parse_line_count(arg: Option<&str>) -> Result<u32, String> {
+    match arg.map(str::parse::<u32>) {
+        Some(Ok(n)) if n > 0 => Ok(n),
+        _ => Err(String::from("expected a positive line count")),
+    }
+}
+
+/// The entries of `path` one per line, directories first and marked with a
+/// trailing `/`, below a `../` entry.
+fn list_directory(path: &str) -> Result<String, std::io::Error> {
+    let mut entries: Vec<(bool, String)> = fs::read_dir(path)?
+        .filter_map(Result::ok)
+        .map(|entry| {
+            let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+            (!is_dir, entry.file_name().to_string_lossy().into_owned())
+        })
+        .collect();
+    entries.sort();
+
+    let mut listing = String::from("../");
+    for (is_file, name) in entries {
+        listing.push('\n');
+        listing.push_str(&name);
+        if !is_file {
+            listing.push('/');
+        }
+    }
+
+    Ok(listing)
+}
+
+/// Guesses from the first 8 KB whether `data` is binary: any NUL byte, or
+/// more than 10% control characters other than whitespace.
+fn looks_binary(data: &[u8]) -> bool {
+    let sample = &data[..cmp::min(data.len(), BINARY_SAMPLE)];
+    if sample.contains(&0) {
+        return true;
+    }
+
+    let control = sample
+        .iter()
+        .filter(|b| (b.is_ascii_control() && !b.is_ascii_whitespace()) || **b == 0x7f)
+        .count();
+    control * 10 > sample.len()
+}
+
+/// Decodes raw file bytes as UTF-8 into chars with every line ending as a single `\n`.
+/// Invalid sequences become U+FFFD.
+fn decode_line_endings(raw_data: &[u8]) -> Vec<char> {
+    let text = String::from_utf8_lossy(raw_data);
+    let mut decoded = Vec::with_capacity(raw_data.len());
+    decoded.extend(line_ending_chars(&text));
+
+    decoded
+}
+
+/// The chars of `text` with `\r\n` and lone `\r` turned into `\n`.
+fn line_ending_chars(text: &str) -> impl Iterator<Item = char> + '_ {
+    let mut chars = text.chars().peekable();
+
+    std::iter::from_fn(move || loop {
+        match chars.next()? {
+            '\r' if chars.peek() == Some(&'\n') => (),
+            '\r' => return Some('\n'),
+            c => return Some(c),
+        }
+    })
+}
+
+pub trait EditorContentTrait {
+    fn new() -> Self;
+
+    fn load_data(&mut self, raw_data: Vec<u8>);
+    fn read_data(&self, buffer: &mut Vec<u8>);
+    fn get_line(&self, i: u32) -> Option<String>;
+    /// Every line in order, borrowed from the buffer where it is stored contiguously.
+    fn lines(&self) -> Box<dyn Iterator<Item = Cow<'_, [char]>> + '_>;
+    fn get_line_len(&self, i: u32) -> Option<u32>;
+    fn line_count(&self) -> u32;
+    fn insert_line(&mut self, row: u32, line: &str);
+    fn replace_line(&mut self, row: u32, line: &str);
+    fn delete_line(&mut self, row: u32) -> Option<String>;
+    /// Deletes the lines in `rows` that exist, returning their text in order.
+    fn delete_lines(&mut self, rows: Range<u32>) -> Vec<String> {
+        let mut removed: Vec<String> = rows.rev().filter_map(|row| self.delete_line(row)).collect();
+        removed.reverse();
+        removed
+    }
+    fn write_char(&mut self, c: char, col: u32, row: u32);
+    fn delete_char(&mut self, col: u32, row: u32) -> Option<char>;
+    fn file_format(&self) -> FileFormat;
+    fn set_file_format(&mut self, format: FileFormat);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{vector::CharVectorEditor, *};
+
+    // an editor showing `text` in a 40x10 view
+    fn editor(text: &str) -> CharVectorEditor {
+        editor_with(text)
+    }
+
+    fn editor_with<T: EditorContentTrait>(text: &str) -> Editor<T> {
+        let mut editor = Editor::new();
+        editor.on_action(vec![Action::Resize(40, 10)]);
+        editor.load_data(text.as_bytes().to_vec());
+        editor
+    }
+
+    // types `:{command}` and Enter
+    fn run<T: EditorContentTrait>(editor: &mut Editor<T>, command: &str) {
+        let mut actions = vec![Action::ChangeMode(Mode::Command)];
+        actions.extend(command.chars().map(Action::CommandChar));
+        actions.push(Action::RunCommand);
+        editor.on_action(actions);
+    }
+
+    // an empty directory for the files of one test
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("rust-text-editor-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn numbered_lines(count: u32) -> String {
+        (1..=count).map(|i| format!("  line {}\n", i)).collect()
+    }
+
+    #[test]
+    fn inverted_container_has_no_size() {
+        let view = Container {
+            top: 5,
+            left: 8,
+            bottom: 2,
+            right: 3,
+        };
+
+        assert_eq!(view.get_width(), 0);
+        assert_eq!(view.get_height(), 0);
+    }
+
+    #[test]
+    fn count_percent_goes_to_that_part_of_the_file() {
+        let mut editor = editor(&numbered_lines(20));
+
+        editor.on_action(vec![Action::PushCount(5)]);
+        editor.on_action(vec![Action::PushCount(0)]);
+        editor.on_action(vec![Action::GotoPercent]);
+
+        // 50% of 21 lines, the last one empty, rounds up to line 11
+        assert_eq!(editor.render_cursor(), (10, 2));
+    }
+
+    #[test]
+    fn leading_zero_moves_to_line_start() {
+        let mut editor = editor("  indented\n");
+        editor.set_cursor(0, 5);
+
+        editor.on_action(vec![Action::PushCount(0)]);
+
+        assert_eq!(editor.render_cursor(), (0, 0));
+        assert_eq!(editor.pending_count, None);
+    }
+
+    #[test]
+    fn set_cursor_clamps_and_scrolls_into_view() {
+        let mut editor = editor(&numbered_lines(30));
+
+        editor.set_cursor(100, 100);
+
+        // the empty line after the last newline
+        assert_eq!(editor.render_cursor(), (30, 0));
+        assert!(editor.view.top <= 30 && 30 <= editor.view.bottom);
+
+        editor.set_cursor(3, 100);
+        assert_eq!(editor.render_cursor(), (3, 8));
+        assert!(editor.view.top <= 3 && 3 <= editor.view.bottom);
+    }
+
+    #[test]
+    fn path_under_cursor_stays_inside_one_string() {
+        let mut editor = editor("open(\"a.txt\") + x + open(\"b.txt\")");
+
+        editor.set_cursor(0, 16);
+        assert_eq!(editor.path_under_cursor().as_deref(), Some("x"));
+
+        editor.set_cursor(0, 28);
+        assert_eq!(editor.path_under_cursor().as_deref(), Some("b.txt"));
+    }
+
+    #[test]
+    fn goto_file_opens_a_relative_path() {
+        let dir = temp_dir("goto-file");
+        fs::write(dir.join("notes.txt"), "hello\n").unwrap();
+        fs::write(dir.join("index.txt"), "see notes.txt\n").unwrap();
+
+        let mut editor = editor("");
+        editor.on_load_file(dir.join("index.txt").to_string_lossy().to_string());
+        editor.set_cursor(0, 6);
+        editor.on_action(vec![Action::GotoFile]);
+
+        assert_eq!(editor.file_path(), Some(dir.join("notes.txt").as_path()));
+        assert_eq!(editor.content.get_line(0).as_deref(), Some("hello"));
+    }
+
+    fn truncate_and_pad<T: EditorContentTrait>() {
+        let mut editor: Editor<T> = editor_with(&numbered_lines(10));
+        editor.set_cursor(8, 0);
+
+        run(&mut editor, "truncate 4");
+        assert_eq!(editor.line_count(), 4);
+        assert_eq!(editor.content.get_line(3).as_deref(), Some("  line 4"));
+        assert_eq!(editor.message.as_deref(), Some("7 lines removed"));
+        assert_eq!(editor.render_cursor().0, 3);
+
+        run(&mut editor, "pad 6");
+        assert_eq!(editor.line_count(), 6);
+        assert_eq!(editor.content.get_line(5).as_deref(), Some(""));
+
+        editor.on_action(vec![Action::Undo, Action::Undo]);
+        assert_eq!(editor.line_count(), 11);
+        assert_eq!(editor.content.get_line(9).as_deref(), Some("  line 10"));
+    }
+
+    #[test]
+    fn truncate_and_pad_set_the_line_count() {
+        truncate_and_pad::<EditorContent<vector::CharVector>>();
+        truncate_and_pad::<EditorContent<gap::GapBuffer>>();
+        truncate_and_pad::<EditorContent<rope::Rope>>();
+    }
+
+    #[test]
+    fn o_swaps_the_selection_ends() {
+        let mut editor = editor("one two three\n");
+        editor.set_cursor(0, 4);
+
+        editor.on_action(vec![Action::ChangeMode(Mode::Visual)]);
+        editor.on_action(vec![Action::Move(Movement::WordEnd)]);
+        assert_eq!(editor.selection(), Some(((0, 4), (0, 6))));
+
+        editor.on_action(vec![Action::SwapSelectionEnds]);
+        assert_eq!(editor.render_cursor(), (0, 4));
+
+        // the selection now grows from its start
+        editor.on_action(vec![Action::Move(Movement::CharLeft('h'))]);
+        assert_eq!(editor.selection(), Some(((0, 3), (0, 6))));
+    }
+
+    #[test]
+    fn modelines_are_read_from_the_last_lines() {
+        let mut text = numbered_lines(20);
+        text += "// vim: ts=2 sw=2 et\n";
+        let editor = editor(&text);
+
+        assert_eq!(editor.options.tabstop, 2);
+        assert!(editor.options.expandtab);
+
+        let mut editor = editor_with::<EditorContent<vector::CharVector>>("");
+        run(&mut editor, "set nomodeline");
+        editor.load_data(text.into_bytes());
+        assert_eq!(editor.options.tabstop, 4);
+    }
+
+    #[test]
+    fn backspace_and_space_cross_line_ends() {
+        let mut editor = editor("ab\ncd\n");
+        editor.set_cursor(1, 0);
+
+        editor.on_action(vec![Action::Move(Movement::CharLeft('b'))]);
+        assert_eq!(editor.render_cursor(), (0, 2));
+
+        editor.on_action(vec![Action::Move(Movement::CharRight('s'))]);
+        assert_eq!(editor.render_cursor(), (1, 0));
+
+        // h and l stop at the line ends unless `whichwrap` lists them
+        editor.on_action(vec![Action::Move(Movement::CharLeft('h'))]);
+        assert_eq!(editor.render_cursor(), (1, 0));
+        run(&mut editor, "set whichwrap=h");
+        editor.on_action(vec![Action::Move(Movement::CharLeft('h'))]);
+        assert_eq!(editor.render_cursor(), (0, 2));
+    }
+
+    #[test]
+    fn undo_restores_the_selection_of_a_visual_delete() {
+        let mut editor = editor("one two\nthree four\n");
+        editor.set_cursor(0, 4);
+        editor.on_action(vec![Action::ChangeMode(Mode::Visual)]);
+        editor.on_action(vec![Action::Move(Movement::Down)]);
+        editor.on_action(vec![Action::DeleteSelection]);
+
+        assert_eq!(editor.content.get_line(0).as_deref(), Some("one  four"));
+        assert_eq!(editor.mode(), Mode::Normal);
+
+        editor.on_action(vec![Action::Undo]);
+
+        assert_eq!(editor.content.get_line(0).as_deref(), Some("one two"));
+        assert_eq!(editor.content.get_line(1).as_deref(), Some("three four"));
+        assert_eq!(editor.mode(), Mode::Visual);
+        assert_eq!(editor.selection(), Some(((0, 4), (1, 4))));
+    }
+
+    fn written_with_format<T: EditorContentTrait>(text: &str, format: &str) -> Vec<u8> {
+        let mut editor: Editor<T> = editor_with(text);
+        run(&mut editor, &format!("set ff={}", format));
+        assert!(editor.modified);
+
+        let mut data = Vec::new();
+        editor.content.read_data(&mut data);
+        data
+    }
+
+    #[test]
+    fn file_format_changes_the_written_line_endings() {
+        fn check<T: EditorContentTrait>() {
+            assert_eq!(written_with_format::<T>("a\nb\n", "dos"), b"a\r\nb\r\n");
+            assert_eq!(written_with_format::<T>("a\nb\n", "mac"), b"a\rb\r");
+            assert_eq!(written_with_format::<T>("a\r\nb\r\n", "unix"), b"a\nb\n");
+        }
+
+        check::<EditorContent<vector::CharVector>>();
+        check::<EditorContent<gap::GapBuffer>>();
+        check::<EditorContent<rope::Rope>>();
+    }
+
+    #[test]
+    fn undone_branch_is_reachable_with_g_plus() {
+        let mut editor = editor("one\ntwo\n");
+        editor.on_action(vec![Action::DeleteLine]);
+        editor.on_action(vec![Action::Undo]);
+        editor.on_action(vec![Action::Move(Movement::Down), Action::DeleteLine]);
+        assert_eq!(editor.content.get_line(0).as_deref(), Some("one"));
+
+        editor.on_action(vec![Action::Travel(false)]);
+        editor.on_action(vec![Action::Travel(false)]);
+        assert_eq!(editor.content.get_line(1).as_deref(), Some("two"));
+
+        // the first delete, undone before the second one
+        editor.on_action(vec![Action::Travel(true)]);
+        assert_eq!(editor.content.get_line(0).as_deref(), Some("two"));
+        editor.on_action(vec![Action::Travel(true)]);
+        assert_eq!(editor.content.get_line(0).as_deref(), Some("one"));
+        assert_eq!(editor.content.get_line(1).as_deref(), Some(""));
+    }
+
+    #[test]
+    fn declined_large_file_at_startup_quits_without_a_path() {
+        let dir = temp_dir("large-file");
+        let path = dir.join("big.txt").to_string_lossy().into_owned();
+        fs::write(&path, "big\n").unwrap();
+
+        let mut declined = editor("");
+        declined.options.maxfilesize = 0;
+        declined.on_load_file(path.clone());
+
+        assert!(declined.confirm.is_some());
+        assert_eq!(declined.file_path, None);
+        assert_eq!(declined.content.get_line(0).as_deref(), Some(""));
+
+        declined.on_action(vec![Action::ConfirmAnswer('n')]);
+        assert!(declined.should_quit);
+        assert_eq!(declined.file_path, None);
+
+        let mut accepted = editor("");
+        accepted.options.maxfilesize = 0;
+        accepted.on_load_file(path.clone());
+        accepted.on_action(vec![Action::ConfirmAnswer('y')]);
+        assert_eq!(accepted.content.get_line(0).as_deref(), Some("big"));
+        assert_eq!(accepted.file_path, Some(path));
+    }
+
+    #[test]
+    fn resize_keeps_at_least_one_cell() {
+        let mut editor = editor("one\ntwo\n");
+        editor.on_action(vec![Action::Resize(0, 0)]);
+
+        assert_eq!(editor.view.bottom, editor.view.top);
+        assert_eq!(editor.view.right, editor.view.left);
+
+        editor.on_action(vec![Action::Move(Movement::Down)]);
+        assert_eq!(editor.render_cursor(), (1, 0));
+        assert_eq!(editor.view.top, 1);
+    }
+
+    #[test]
+    fn inccommand_previews_substitution_until_cancelled() {
+        let mut editor = editor("a cat\nthe cat sat\n");
+        editor.options.inccommand = true;
+
+        let mut typing = vec![Action::ChangeMode(Mode::Command)];
+        typing.extend("%s/cat/dog/".chars().map(Action::CommandChar));
+        editor.on_action(typing);
+
+        assert_eq!(editor.content.get_line(0).as_deref(), Some("a dog"));
+        assert_eq!(editor.content.get_line(1).as_deref(), Some("the dog sat"));
+        assert!(editor
+            .line_highlights(1)
+            .contains(&(4..7, Highlight::Selection)));
+        assert!(!editor.modified);
+
+        editor.on_action(vec![Action::CancelCommand]);
+        assert_eq!(editor.content.get_line(0).as_deref(), Some("a cat"));
+        assert_eq!(editor.content.get_line(1).as_deref(), Some("the cat sat"));
+        assert!(editor.line_highlights(1).is_empty());
+
+        run(&mut editor, "%s/cat/dog/");
+        assert_eq!(editor.content.get_line(1).as_deref(), Some("the dog sat"));
+        editor.on_action(vec![Action::Undo]);
+        assert_eq!(editor.content.get_line(1).as_deref(), Some("the cat sat"));
+    }
+
+    #[test]
+    fn undo_to_the_saved_text_clears_modified() {
+        let dir = temp_dir("undo-saved");
+        let path = dir.join("notes.txt");
+        fs::write(&path, "one\ntwo\n").unwrap();
+
+        let mut editor = editor("");
+        editor.on_load_file(path.to_string_lossy().into_owned());
+        editor.on_action(vec![Action::DeleteLine]);
+        assert!(editor.modified);
+
+        editor.on_action(vec![Action::Undo]);
+        assert!(!editor.modified);
+        editor.on_action(vec![Action::Redo]);
+        assert!(editor.modified);
+
+        editor.on_action(vec![Action::SaveFile]);
+        editor.on_action(vec![Action::Undo]);
+        assert!(editor.modified);
+        editor.on_action(vec![Action::Redo]);
+        assert!(!editor.modified);
+    }
+
+    #[test]
+    fn utf8_text_round_trips_and_moves_by_char() {
+        fn check<T: EditorContentTrait>() {
+            let text = "héllo → 世界\n";
+            let mut editor: Editor<T> = editor_with(text);
+
+            let mut data = Vec::new();
+            editor.content.read_data(&mut data);
+            assert_eq!(data, text.as_bytes());
+            assert_eq!(editor.content.get_line_len(0), Some(10));
+
+            editor.on_action(vec![Action::Move(Movement::LineEnd)]);
+            assert_eq!(editor.render_cursor(), (0, 9));
+            editor.on_action(vec![Action::Move(Movement::WordBackward)]);
+            assert_eq!(editor.render_cursor(), (0, 8));
+        }
+
+        check::<EditorContent<vector::CharVector>>();
+        check::<EditorContent<gap::GapBuffer>>();
+        check::<EditorContent<rope::Rope>>();
+    }
+
+    #[test]
+    fn line_endings_are_kept_on_save() {
+        let mut lf = Vec::new();
+        editor("one\ntwo\n").content.read_data(&mut lf);
+        assert_eq!(lf, b"one\ntwo\n");
+
+        assert_eq!(FileFormat::detect(b"a\r\nb\r\nc\n"), Some(FileFormat::Dos));
+        assert_eq!(FileFormat::detect(b"a\nb\nc\r\n"), Some(FileFormat::Unix));
+        // ties go to unix, then dos
+        assert_eq!(FileFormat::detect(b"a\nb\r\n"), Some(FileFormat::Unix));
+        assert_eq!(FileFormat::detect(b"no line break"), None);
+    }
+
+    #[test]
+    fn files_without_line_breaks_use_the_platform_endings() {
+        fn check<T: EditorContentTrait>() {
+            let mut data = Vec::new();
+            editor_with::<T>("one line").content.read_data(&mut data);
+            assert_eq!(data, b"one line");
+
+            let mut e = editor_with::<T>("");
+            e.on_action(vec![Action::InsertChar('a'), Action::InsertChar('\n')]);
+            let mut data = Vec::new();
+            e.content.read_data(&mut data);
+            let expected: &[u8] = if cfg!(windows) { b"a\r\n" } else { b"a\n" };
+            assert_eq!(data, expected);
+        }
+
+        check::<EditorContent<vector::CharVector>>();
+        check::<EditorContent<gap::GapBuffer>>();
+        check::<EditorContent<rope::Rope>>();
+    }
+
+    #[test]
+    fn dd_deletes_the_cursor_line() {
+        let mut editor = editor("one\ntwo\nthree");
+        editor.set_cursor(1, 2);
+        editor.on_action(vec![Action::DeleteLine]);
+
+        assert_eq!(editor.content.get_line(0).as_deref(), Some("one"));
+        assert_eq!(editor.content.get_line(1).as_deref(), Some("three"));
+        assert_eq!(editor.line_count(), 2);
+        assert_eq!(editor.render_cursor().0, 1);
+
+        editor.on_action(vec![Action::DeleteLine]);
+        editor.on_action(vec![Action::DeleteLine]);
+        assert_eq!(editor.line_count(), 1);
+        assert_eq!(editor.content.get_line(0).as_deref(), Some(""));
+        assert_eq!(editor.render_cursor(), (0, 0));
+    }
+
+    #[test]
+    fn selection_ending_with_a_line_break_pastes_inside_the_line() {
+        let mut editor = editor("ab\ncd\n");
+        editor.set_cursor(0, 1);
+        editor.on_action(vec![Action::ChangeMode(Mode::Visual)]);
+        editor.on_action(vec![Action::Move(Movement::Right)]);
+        editor.on_action(vec![Action::Yank, Action::ChangeMode(Mode::Normal)]);
+        assert_eq!(editor.clipboard.text, "b\n");
+        assert!(!editor.clipboard.linewise);
+
+        editor.set_cursor(1, 0);
+        editor.on_action(vec![Action::Paste(true)]);
+        assert_eq!(editor.content.get_line(1).as_deref(), Some("b"));
+        assert_eq!(editor.content.get_line(2).as_deref(), Some("cd"));
+
+        editor.set_cursor(0, 0);
+        editor.on_action(vec![Action::Yank]);
+        assert!(editor.clipboard.linewise);
+        editor.on_action(vec![Action::Paste(false)]);
+        assert_eq!(editor.content.get_line(1).as_deref(), Some("ab"));
+        assert_eq!(editor.content.get_line(2).as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn missing_file_is_created_on_save() {
+        let dir = temp_dir("new-file");
+        let path = dir.join("new.txt");
+
+        let mut editor = editor("");
+        editor.on_load_file(path.to_string_lossy().into_owned());
+        assert!(!path.exists());
+        assert_eq!(editor.message, None);
+        // nothing to detect the line endings from
+        assert_eq!(editor.content.file_format(), FileFormat::Unix);
+
+        editor.on_action(vec![Action::ChangeMode(Mode::Insert)]);
+        editor.on_action("hi".chars().map(Action::InsertChar).collect());
+        editor.on_action(vec![Action::ChangeMode(Mode::Normal)]);
+        run(&mut editor, "w");
+
+        assert_eq!(fs::read(&path).unwrap(), b"hi\n");
+        assert!(!editor.modified);
+    }
+
+    #[test]
+    fn extending_a_selection_redraws_only_its_rows() {
+        let mut editor = editor(&numbered_lines(5));
+        editor.set_cursor(1, 0);
+        editor.on_action(vec![Action::ChangeMode(Mode::Visual)]);
+
+        editor.on_action(vec![Action::Move(Movement::Down)]);
+        assert_eq!(editor.should_redraw, Some(Redraw::Range(1, 2)));
+
+        editor.on_action(vec![Action::PushCount(2)]);
+        editor.on_action(vec![Action::Move(Movement::Down)]);
+        assert_eq!(editor.should_redraw, Some(Redraw::Range(2, 4)));
+    }
+
+    #[test]
+    fn write_commands_write_and_quit() {
+        let dir = temp_dir("write-commands");
+        let copy = dir.join("copy.txt");
+        let path = dir.join("notes.txt");
+        fs::write(&path, "one\n").unwrap();
+
+        let mut named = editor("");
+        named.on_load_file(path.to_string_lossy().into_owned());
+        named.on_action(vec![Action::DeleteChar(false)]);
+
+        run(&mut named, &format!("w {}", copy.display()));
+        assert_eq!(fs::read(&copy).unwrap(), b"ne\n");
+        assert_eq!(fs::read(&path).unwrap(), b"one\n");
+        assert!(named.modified && !named.should_quit);
+
+        fs::remove_file(&copy).unwrap();
+        run(&mut named, "x");
+        assert_eq!(fs::read(&path).unwrap(), b"ne\n");
+        assert!(named.should_quit);
+
+        // nothing to write, `:x` leaves the file alone and `:wq` writes it
+        let mut unchanged = editor("");
+        unchanged.on_load_file(path.to_string_lossy().into_owned());
+        fs::write(&path, "changed\n").unwrap();
+        run(&mut unchanged, "x");
+        assert_eq!(fs::read(&path).unwrap(), b"changed\n");
+        run(&mut unchanged, "wq");
+        assert_eq!(fs::read(&path).unwrap(), b"ne\n");
+    }
+
+    #[test]
+    fn horizontal_scroll_counts_tab_width() {
+        let mut editor = editor(&format!("\t{}end", "x".repeat(40)));
+
+        // the cells 2..4 are all the leading tab
+        assert_eq!(editor.char_col(0, 2), 0);
+        assert_eq!(editor.char_col(0, 4), 1);
+        assert_eq!(editor.display_col(0, 1), 4);
+
+        // the last char is drawn at column 46, the view is 39 wide
+        editor.on_action(vec![Action::Move(Movement::LineEnd)]);
+        assert_eq!(editor.render_cursor(), (0, 43));
+        assert_eq!(editor.view.left, 46 - 39);
+
+        editor.on_action(vec![Action::Move(Movement::LineStart)]);
+        assert_eq!(editor.view.left, 0);
+    }
+
+    #[test]
+    fn goto_line_clamps_with_a_message() {
+        let mut editor = editor(&numbered_lines(50));
+        let last_row = editor.line_count() - 1;
+
+        run(&mut editor, "99999");
+        assert_eq!(editor.render_cursor().0, last_row);
+        assert!(editor.message.is_some());
+        assert!(editor.view.top <= last_row && last_row <= editor.view.bottom);
+
+        editor.message = None;
+        run(&mut editor, "1");
+        assert_eq!(editor.render_cursor(), (0, 2));
+        assert!(editor.message.is_none());
+        assert_eq!(editor.view.top, 0);
+
+        editor.on_action(vec![Action::GotoLine(u32::MAX)]);
+        assert_eq!(editor.render_cursor().0, last_row);
+        assert!(editor.message.is_none());
+    }
+
+    #[test]
+    fn wrapped_rows_are_stepped_and_clicked_through() {
+        let mut editor = editor(&format!("{}\nshort\n", "a".repeat(80)));
+        editor.on_action(vec![Action::Resize(41, 10)]);
+        run(&mut editor, "set wrap");
+        assert_eq!(editor.display_rows(0), 2);
+
+        editor.set_cursor(0, 5);
+        editor.on_action(vec![Action::Move(Movement::Down)]);
+        assert_eq!(editor.render_cursor(), (0, 45));
+        editor.on_action(vec![Action::Move(Movement::Down)]);
+        assert_eq!(editor.render_cursor(), (1, 5));
+        editor.on_action(vec![Action::Move(Movement::Up)]);
+        assert_eq!(editor.render_cursor(), (0, 45));
+        editor.on_action(vec![Action::Move(Movement::Up)]);
+        assert_eq!(editor.render_cursor(), (0, 5));
+
+        // the second screen row is still the first line
+        assert_eq!(editor.position_at(1, 3), (0, 43));
+        assert_eq!(editor.position_at(2, 1), (1, 1));
+    }
+
+    #[test]
+    fn linebreak_wraps_at_a_blank() {
+        let text = "one two three four five six seven eighteen nineteen";
+        let mut e = editor(text);
+        run(&mut e, "set wrap");
+        assert_eq!(e.row_starts(0), [0, 39]);
+
+        // `eighteen` moves down whole, the blank before it ends the first row
+        run(&mut e, "set linebreak");
+        assert_eq!(e.row_starts(0), [0, 34]);
+        assert_eq!(e.position_at(1, 0), (0, 34));
+        assert_eq!(e.position_at(0, 38), (0, 33));
+
+        e.set_cursor(0, 5);
+        e.on_action(vec![Action::Move(Movement::Down)]);
+        assert_eq!(e.render_cursor(), (0, 39));
+        e.on_action(vec![Action::Move(Movement::Up)]);
+        assert_eq!(e.render_cursor(), (0, 5));
+        assert_eq!(e.content.get_line(0).as_deref(), Some(text));
+
+        // a word longer than the row is still cut at the edge
+        e.load_data("a".repeat(50).into_bytes());
+        assert_eq!(e.row_starts(0), [0, 39]);
+    }
+
+    #[test]
+    fn vertical_moves_restore_the_wanted_column() {
+        let mut editor = editor("abcdefghijklmnopqrst\nabc\nabcdefghijklmnopqrst");
+
+        editor.set_cursor(0, 15);
+        editor.on_action(vec![Action::Move(Movement::Down)]);
+        assert_eq!(editor.render_cursor(), (1, 3));
+        editor.on_action(vec![Action::Move(Movement::Down)]);
+        assert_eq!(editor.render_cursor(), (2, 15));
+        editor.on_action(vec![Action::Move(Movement::Up)]);
+        editor.on_action(vec![Action::Move(Movement::Up)]);
+        assert_eq!(editor.render_cursor(), (0, 15));
+
+        // nothing above the first line or below the last one
+        editor.on_action(vec![Action::Move(Movement::Up)]);
+        assert_eq!(editor.render_cursor(), (0, 15));
+        editor.set_cursor(2, 4);
+        editor.on_action(vec![Action::Move(Movement::Down)]);
+        assert_eq!(editor.render_cursor(), (2, 4));
+    }
+
+    #[test]
+    fn down_in_an_empty_buffer_stays_on_row_zero() {
+        let mut editor = editor("");
+
+        editor.on_action(vec![Action::Move(Movement::Down)]);
+        editor.on_action(vec![Action::Move(Movement::Up)]);
+        assert_eq!(editor.render_cursor(), (0, 0));
+    }
+
+    #[test]
+    fn readonly_only_lets_writes_to_another_path_through() {
+        let dir = temp_dir("readonly-write");
+        let path = dir.join("notes.txt");
+        fs::write(&path, "one\n").unwrap();
+        let mut editor = editor("");
+        editor.open_file(path.to_str().unwrap()).unwrap();
+        run(&mut editor, "set readonly");
+
+        // a trailing space is not a path
+        run(&mut editor, "w ");
+        assert!(editor.message.take().unwrap().contains("readonly"));
+
+        let copy = dir.join("copy.txt");
+        run(&mut editor, &format!("w {}", copy.display()));
+        assert_eq!(fs::read(&copy).unwrap(), b"one\n");
+    }
+
+    #[test]
+    fn binary_file_is_readonly_in_its_own_buffer() {
+        let dir = temp_dir("binary-buffer");
+        let binary = dir.join("image.png");
+        let text = dir.join("notes.txt");
+        fs::write(&binary, b"\x89PNG\0\0data").unwrap();
+        fs::write(&text, "one\n").unwrap();
+        let mut editor = editor("");
+
+        run(&mut editor, &format!("e {}", binary.display()));
+        editor.on_action(vec![Action::InsertChar('x')]);
+        assert_eq!(editor.message.as_deref(), Some("buffer is read-only"));
+        assert!(!editor.modified);
+        assert!(!editor.options.readonly);
+
+        run(&mut editor, &format!("e {}", text.display()));
+        editor.on_action(vec![Action::InsertChar('x')]);
+        assert_eq!(editor.content.get_line(0).as_deref(), Some("xone"));
+
+        run(&mut editor, "bp");
+        editor.on_action(vec![Action::InsertChar('x')]);
+        assert!(!editor.modified);
+
+        run(&mut editor, "set noreadonly");
+        editor.on_action(vec![Action::InsertChar('x')]);
+        assert!(editor.modified);
+    }
+
+    #[test]
+    fn scrolloff_scrolls_before_the_bottom_edge() {
+        let mut early = editor(&numbered_lines(50));
+        run(&mut early, "set scrolloff=3");
+        let down = |editor: &mut CharVectorEditor, times| {
+            for _ in 0..times {
+                editor.on_action(vec![Action::Move(Movement::Down)]);
+            }
+        };
+
+        // rows 0..=9 are on screen
+        down(&mut early, 6);
+        assert_eq!((early.render_cursor().0, early.view.top), (6, 0));
+        down(&mut early, 1);
+        assert_eq!((early.render_cursor().0, early.view.top), (7, 1));
+
+        let mut glued = editor(&numbered_lines(50));
+        run(&mut glued, "set scrolloff=0");
+        down(&mut glued, 9);
+        assert_eq!((glued.render_cursor().0, glued.view.top), (9, 0));
+        down(&mut glued, 1);
+        assert_eq!((glued.render_cursor().0, glued.view.top), (10, 1));
+    }
+
+    #[test]
+    fn deleting_back_out_of_view_redraws_everything() {
+        let mut editor = editor(&"a".repeat(60));
+        editor.on_action(vec![Action::Move(Movement::LineEnd)]);
+        assert!(editor.view.left > 0);
+
+        editor.should_redraw = None;
+        editor.on_action(vec![Action::DeleteChar(true)]);
+        assert_eq!(editor.should_redraw, Some(Redraw::Line(0)));
+
+        editor.should_redraw = None;
+        editor.on_action(vec![Action::PushCount(5), Action::PushCount(0)]);
+        editor.on_action(vec![Action::DeleteChar(true)]);
+        assert_eq!(editor.render_cursor(), (0, 8));
+        assert_eq!(editor.view.left, 8);
+        assert_eq!(editor.should_redraw, Some(Redraw::All));
+    }
+
+    #[test]
+    fn quitting_with_changes_needs_a_bang() {
+        let mut editor = editor("one\n");
+        editor.on_action(vec![Action::DeleteChar(false)]);
+
+        run(&mut editor, "q");
+        assert!(!editor.should_quit);
+        assert!(editor.message.take().unwrap().contains("add ! to override"));
+
+        editor.on_action(vec![Action::Quit]);
+        assert!(!editor.should_quit);
+
+        run(&mut editor, "q!");
+        assert!(editor.should_quit);
+    }
+
+    #[test]
+    fn confirm_asks_before_quitting_with_changes() {
+        let mut e = editor("one\n");
+        run(&mut e, "set confirm");
+        e.on_action(vec![Action::DeleteChar(false)]);
+
+        run(&mut e, "q");
+        assert!(e.confirm.is_some());
+        assert_eq!(
+            e.message.as_deref(),
+            Some("Save changes to \"Untitled\"? [Y]es, (N)o, (C)ancel")
+        );
+
+        // cancelled, the editor stays open with the change
+        e.on_action(vec![Action::ConfirmAnswer('c')]);
+        assert!(e.confirm.is_none());
+        assert!(!e.should_quit);
+        assert!(e.modified);
+        assert_eq!(e.content.get_line(0).as_deref(), Some("ne"));
+
+        run(&mut e, "q");
+        e.on_action(vec![Action::ConfirmAnswer('N')]);
+        assert!(e.should_quit);
+    }
+
+    #[test]
+    fn substitute_on_the_line_and_in_the_whole_file() {
+        let mut editor = editor("a-a-a\na-a\n");
+
+        run(&mut editor, "s/a/b/");
+        assert_eq!(editor.content.get_line(0).as_deref(), Some("b-a-a"));
+        run(&mut editor, "s/a/b/g");
+        assert_eq!(editor.content.get_line(0).as_deref(), Some("b-b-b"));
+        assert_eq!(editor.content.get_line(1).as_deref(), Some("a-a"));
+
+        run(&mut editor, "%s/-/\\//g");
+        assert_eq!(editor.content.get_line(0).as_deref(), Some("b/b/b"));
+        assert_eq!(editor.content.get_line(1).as_deref(), Some("a/a"));
+        assert_eq!(editor.message.as_deref(), Some("3 substitutions"));
+
+        // an empty pattern changes nothing
+        run(&mut editor, "%s//x/g");
+        assert_eq!(editor.content.get_line(1).as_deref(), Some("a/a"));
+    }
+
+    #[test]
+    fn join_collapses_the_indentation_to_one_space() {
+        let mut editor = editor("foo\n   bar");
+
+        editor.on_action(vec![Action::JoinLines]);
+        assert_eq!(editor.content.get_line(0).as_deref(), Some("foo bar"));
+        assert_eq!(editor.line_count(), 1);
+        assert_eq!(editor.render_cursor(), (0, 3));
+
+        // nothing below the last line
+        editor.on_action(vec![Action::JoinLines]);
+        assert_eq!(editor.content.get_line(0).as_deref(), Some("foo bar"));
+    }
+
+    #[test]
+    fn enter_copies_the_indentation() {
+        let mut editor = editor("    foo");
+        editor.on_action(vec![Action::ChangeMode(Mode::Insert)]);
+        editor.on_action(vec![Action::Move(Movement::LineEnd)]);
+
+        editor.on_action(vec![Action::InsertChar('\n')]);
+        assert_eq!(editor.content.get_line(1).as_deref(), Some("    "));
+        assert_eq!(editor.render_cursor(), (1, 4));
+
+        // a blank line still passes its indentation on
+        editor.on_action(vec![Action::InsertChar('\n')]);
+        assert_eq!(editor.content.get_line(2).as_deref(), Some("    "));
+
+        // nothing to copy from the left of column 0
+        editor.set_cursor(0, 0);
+        editor.on_action(vec![Action::InsertChar('\n')]);
+        assert_eq!(editor.content.get_line(0).as_deref(), Some(""));
+        assert_eq!(editor.content.get_line(1).as_deref(), Some("    foo"));
+    }
+
+    #[test]
+    fn autopairs_close_type_over_and_delete_together() {
+        let mut editor = editor("");
+        run(&mut editor, "set autopairs");
+        editor.on_action(vec![Action::ChangeMode(Mode::Insert)]);
+
+        editor.on_action(vec![Action::InsertChar('(')]);
+        assert_eq!(editor.content.get_line(0).as_deref(), Some("()"));
+        assert_eq!(editor.render_cursor(), (0, 1));
+
+        editor.on_action(vec![Action::InsertChar(')')]);
+        assert_eq!(editor.content.get_line(0).as_deref(), Some("()"));
+        assert_eq!(editor.render_cursor(), (0, 2));
+
+        editor.set_cursor(0, 1);
+        editor.on_action(vec![Action::Backspace]);
+        assert_eq!(editor.content.get_line(0).as_deref(), Some(""));
+
+        run(&mut editor, "set noautopairs");
+        editor.on_action(vec![Action::ChangeMode(Mode::Insert)]);
+        editor.on_action(vec![Action::InsertChar('(')]);
+        assert_eq!(editor.content.get_line(0).as_deref(), Some("("));
+    }
+
+    #[test]
+    fn cursor_follows_a_sequence_of_movements() {
+        let mut editor = editor("one two\nthree\nfour five six");
+        let mut moved = |movement| {
+            editor.on_action(vec![Action::Move(movement)]);
+            editor.cursor()
+        };
+
+        assert_eq!(moved(Movement::WordForward), (0, 4));
+        assert_eq!(moved(Movement::Down), (1, 4));
+        assert_eq!(moved(Movement::Down), (2, 4));
+        assert_eq!(moved(Movement::WordForward), (2, 5));
+        assert_eq!(moved(Movement::LineEnd), (2, 12));
+        // drawn at the end of `three`, the column is kept for the next line
+        assert_eq!(moved(Movement::Up), (1, 12));
+        assert_eq!(moved(Movement::LineStart), (1, 0));
+        assert_eq!(moved(Movement::Left), (0, 7));
+    }
+
+    #[test]
+    fn directory_listing_refuses_substitute_and_writes() {
+        let dir = temp_dir("listing-commands");
+        fs::write(dir.join("notes.txt"), "hello\n").unwrap();
+        let mut listing = editor("");
+        listing.on_load_file(dir.to_string_lossy().into_owned());
+        let before = listing.content.get_line(1);
+
+        run(&mut listing, "%s/notes/x/g");
+        assert_eq!(
+            listing.message.as_deref(),
+            Some("can't change a directory listing")
+        );
+        assert_eq!(listing.content.get_line(1), before);
+
+        for command in ["w!", "retab", "wq"] {
+            listing.message = None;
+            run(&mut listing, command);
+            assert_eq!(
+                listing.message.as_deref(),
+                Some("can't change a directory listing")
+            );
+        }
+        assert!(!listing.should_quit);
+
+        // nothing to write, so `:x` just closes it
+        run(&mut listing, "x");
+        assert!(listing.should_quit);
+    }
+
+    #[test]
+    fn half_page_scroll_moves_view_and_cursor_together() {
+        let mut editor = editor(&numbered_lines(200));
+        editor.on_action(vec![Action::Resize(80, 40)]);
+        editor.set_cursor(3, 2);
+
+        editor.on_action(vec![Action::ScrollHalfPage(true)]);
+        assert_eq!(editor.view.top, 20);
+        assert_eq!(editor.render_cursor(), (23, 2));
+
+        editor.on_action(vec![Action::ScrollHalfPage(false)]);
+        assert_eq!(editor.view.top, 0);
+        assert_eq!(editor.render_cursor(), (3, 2));
+    }
+
+    #[test]
+    fn scrolling_keeps_the_cursor_in_the_view() {
+        let mut editor = editor(&numbered_lines(50));
+        editor.set_cursor(2, 0);
+
+        // pushed down to the top edge
+        editor.on_action(vec![Action::ScrollBy(5)]);
+        assert_eq!(editor.view.top, 5);
+        assert_eq!(editor.render_cursor().0, 5);
+
+        // still visible, so it stays on its line
+        editor.set_cursor(10, 0);
+        editor.on_action(vec![Action::ScrollBy(-2)]);
+        assert_eq!(editor.view.top, 3);
+        assert_eq!(editor.render_cursor().0, 10);
+
+        editor.on_action(vec![Action::ScrollBy(-5)]);
+        let (top, bottom) = (editor.view.top, editor.view.bottom);
+        assert!((top..=bottom).contains(&editor.render_cursor().0));
+    }
+
+    #[test]
+    fn edit_bang_reloads_the_changed_file() {
+        let dir = temp_dir("edit-bang");
+        let path = dir.join("notes.txt");
+        fs::write(&path, "one\n").unwrap();
+        let mut notes = editor("");
+        run(&mut notes, &format!("e {}", path.display()));
+        notes.on_action(vec![Action::DeleteChar(false)]);
+
+        // without ! the changes are kept
+        run(&mut notes, &format!("e {}", path.display()));
+        assert_eq!(notes.content.get_line(0).as_deref(), Some("ne"));
+        assert!(notes.modified);
+
+        run(&mut notes, "e!");
+        assert_eq!(notes.content.get_line(0).as_deref(), Some("one"));
+        assert!(!notes.modified);
+
+        notes.on_action(vec![Action::DeleteChar(false)]);
+        run(&mut notes, &format!("e! {}", path.display()));
+        assert_eq!(notes.content.get_line(0).as_deref(), Some("one"));
+        assert!(!notes.modified);
+    }
+
+    #[test]
+    fn piped_text_is_unsaved_and_binary_is_readonly() {
+        let mut piped = editor("");
+        piped.load_stdin(b"one\r\ntwo\r\n".to_vec());
+        assert_eq!(piped.content.get_line(1).as_deref(), Some("two"));
+        assert!(piped.modified);
+
+        // undoing back to the piped text leaves it unsaved
+        piped.on_action(vec![Action::DeleteChar(false)]);
+        piped.on_action(vec![Action::Undo]);
+        assert_eq!(piped.content.get_line(0).as_deref(), Some("one"));
+        assert!(piped.modified);
+
+        let mut binary = editor("");
+        binary.load_stdin(b"\x7fELF\0\0".to_vec());
+        binary.on_action(vec![Action::InsertChar('x')]);
+        assert_eq!(binary.message.as_deref(), Some("buffer is read-only"));
+    }
+
+    #[test]
+    fn writing_elsewhere_trims_a_copy() {
+        let dir = temp_dir("trim-copy");
+        let copy = dir.join("copy.txt");
+        let mut spaced = editor("one  \ntwo\t\n");
+        run(&mut spaced, "set trimws");
+
+        run(&mut spaced, &format!("w {}", copy.display()));
+        assert_eq!(fs::read(&copy).unwrap(), b"one\ntwo\n");
+        assert_eq!(spaced.content.get_line(0).as_deref(), Some("one  "));
+        assert!(!spaced.modified);
+
+        fs::remove_file(&copy).unwrap();
+        run(&mut spaced, &format!("wq {}", copy.display()));
+        assert_eq!(fs::read(&copy).unwrap(), b"one\ntwo\n");
+        assert_eq!(spaced.content.get_line(1).as_deref(), Some("two\t"));
+        assert!(spaced.should_quit);
+    }
+
+    #[test]
+    fn saved_files_end_with_a_line_ending() {
+        let dir = temp_dir("final-newline");
+        let path = dir.join("out.txt");
+        let written = |text: &str| {
+            let mut source = editor(text);
+            run(&mut source, &format!("w {}", path.display()));
+            fs::read(&path).unwrap()
+        };
+
+        assert_eq!(written("foo\nbar"), b"foo\nbar\n");
+        assert_eq!(written("foo\nbar\n"), b"foo\nbar\n");
+        assert_eq!(written(""), b"");
+
+        let mut kept = editor("foo\nbar");
+        run(&mut kept, "set nofixendofline");
+        run(&mut kept, &format!("w {}", path.display()));
+        assert_eq!(fs::read(&path).unwrap(), b"foo\nbar");
+        assert_eq!(kept.line_count(), 2);
+    }
+
+    #[test]
+    fn shifting_a_two_space_line_keeps_the_cursor_on_its_char() {
+        let mut editor = editor("  foo");
+        run(&mut editor, "set shiftwidth=4 expandtab");
+        editor.set_cursor(0, 3);
+
+        editor.on_action(vec![Action::ShiftLines(true)]);
+        assert_eq!(editor.content.get_line(0).as_deref(), Some("      foo"));
+        assert_eq!(editor.render_cursor(), (0, 7));
+
+        // two steps back, the second one only has two spaces left to take
+        editor.on_action(vec![Action::ShiftLines(false)]);
+        assert_eq!(editor.content.get_line(0).as_deref(), Some("  foo"));
+        assert_eq!(editor.render_cursor(), (0, 3));
+        editor.on_action(vec![Action::ShiftLines(false)]);
+        assert_eq!(editor.content.get_line(0).as_deref(), Some("foo"));
+        assert_eq!(editor.render_cursor(), (0, 1));
+    }
+
+    #[test]
+    fn search_while_typing_a_command_keeps_the_command_line() {
+        let mut e = editor("one\n");
+        e.on_action(vec![
+            Action::ChangeMode(Mode::Command),
+            Action::Search(true),
+        ]);
+        assert_eq!(e.mode(), Mode::Command);
+        assert_eq!(e.prompt, ':');
+
+        e.on_action(vec![Action::CommandChar('q'), Action::Search(false)]);
+        assert_eq!(e.prompt, ':');
+        assert_eq!(e.command, "q");
+    }
+
+    #[test]
+    fn cursor_keeps_its_column_on_a_short_line() {
+        let mut e = editor("a long line\nab\n");
+        e.set_cursor(0, 8);
+        e.on_action(vec![Action::Move(Movement::Down)]);
+        assert_eq!(e.cursor(), (1, 8));
+        assert_eq!(e.render_cursor(), (1, 2));
+
+        e.on_action(vec![Action::Move(Movement::Up)]);
+        assert_eq!(e.cursor(), (0, 8));
+        assert_eq!(e.render_cursor(), (0, 8));
+    }
+
+    #[test]
+    fn virtualedit_all_pads_text_typed_past_the_line_end() {
+        let mut e = editor("ab\nlonger line\n");
+        e.on_action(vec![Action::Move(Movement::CharRight('l')); 4]);
+        assert_eq!(e.render_cursor(), (0, 2));
+
+        run(&mut e, "set virtualedit=all");
+        e.on_action(vec![Action::Move(Movement::CharRight('l')); 4]);
+        assert_eq!(e.render_cursor(), (0, 6));
+        // the column is kept on the way down and back up
+        e.on_action(vec![Action::Move(Movement::Down)]);
+        e.on_action(vec![Action::Move(Movement::Up)]);
+        assert_eq!(e.render_cursor(), (0, 6));
+
+        e.on_action(vec![Action::ChangeMode(Mode::Insert)]);
+        e.on_action(vec![Action::InsertChar('x')]);
+        assert_eq!(e.content.get_line(0).as_deref(), Some("ab    x"));
+        assert_eq!(e.render_cursor(), (0, 7));
+    }
+
+    #[test]
+    fn retab_round_trips_the_indentation() {
+        for expandtab in ["noexpandtab", "expandtab"] {
+            let mut e = editor("\tone\n\t\ttwo\tx\n  three\n");
+            run(&mut e, &format!("set {}", expandtab));
+
+            run(&mut e, "retab");
+            assert_eq!(e.message.as_deref(), Some("2 lines changed"));
+            assert_eq!(e.content.get_line(0).as_deref(), Some("    one"));
+            assert_eq!(e.content.get_line(1).as_deref(), Some("        two\tx"));
+            assert_eq!(e.content.get_line(2).as_deref(), Some("  three"));
+            assert_eq!(e.should_redraw, Some(Redraw::All));
+
+            // less than a tabstop of spaces stays as it is
+            run(&mut e, "retab!");
+            assert_eq!(e.message.as_deref(), Some("2 lines changed"));
+            assert_eq!(e.content.get_line(0).as_deref(), Some("\tone"));
+            assert_eq!(e.content.get_line(1).as_deref(), Some("\t\ttwo\tx"));
+            assert_eq!(e.content.get_line(2).as_deref(), Some("  three"));
+
+            // `all` expands the tabs after the indentation too
+            run(&mut e, "retab all");
+            assert_eq!(e.content.get_line(1).as_deref(), Some("        two x"));
+        }
+    }
+
+    #[test]
+    fn count_before_insert_repeats_the_typed_text() {
+        let mut e = editor("!\n");
+        e.on_action(vec![Action::PushCount(3)]);
+        e.on_action(vec![Action::ChangeMode(Mode::Insert)]);
+        e.on_action("hello".chars().map(Action::InsertChar).collect());
+        assert_eq!(e.content.get_line(0).as_deref(), Some("hello!"));
+
+        e.on_action(vec![Action::ChangeMode(Mode::Normal)]);
+        assert_eq!(e.content.get_line(0).as_deref(), Some("hellohellohello!"));
+
+        // one undo takes back all three
+        e.on_action(vec![Action::Undo]);
+        assert_eq!(e.content.get_line(0).as_deref(), Some("!"));
+    }
+
+    #[test]
+    fn percent_jumps_between_added_match_pairs() {
+        let mut e = editor("<a <b> c>\n");
+        e.on_action(vec![Action::GotoPercent]);
+        assert_eq!(e.render_cursor(), (0, 0));
+
+        run(&mut e, "set mps+=<:>");
+        e.on_action(vec![Action::GotoPercent]);
+        assert_eq!(e.render_cursor(), (0, 8));
+        e.on_action(vec![Action::GotoPercent]);
+        assert_eq!(e.render_cursor(), (0, 0));
+
+        e.set_cursor(0, 1);
+        e.on_action(vec![Action::GotoPercent]);
+        assert_eq!(e.render_cursor(), (0, 5));
+    }
+
+    #[test]
+    fn screen_line_motions_stop_at_the_wrapped_row_edges() {
+        let mut e = editor(&format!("{}\n", "abcdefghij".repeat(10)));
+        run(&mut e, "set wrap");
+        assert_eq!(e.row_starts(0), [0, 39, 78]);
+
+        e.set_cursor(0, 50);
+        e.on_action(vec![Action::Move(Movement::ScreenLineStart)]);
+        assert_eq!(e.render_cursor(), (0, 39));
+        e.on_action(vec![Action::Move(Movement::ScreenLineEnd)]);
+        assert_eq!(e.render_cursor(), (0, 77));
+
+        // `0` and `$` still go to the ends of the whole line
+        e.on_action(vec![Action::Move(Movement::LineStart)]);
+        assert_eq!(e.render_cursor(), (0, 0));
+        e.on_action(vec![Action::Move(Movement::ScreenLineEnd)]);
+        assert_eq!(e.render_cursor(), (0, 38));
+        e.on_action(vec![Action::Move(Movement::LineEnd)]);
+        assert_eq!(e.render_cursor(), (0, 99));
+        e.on_action(vec![Action::Move(Movement::ScreenLineStart)]);
+        assert_eq!(e.render_cursor(), (0, 78));
+    }
+
+    #[test]
+    fn messages_lists_messages_and_log_records_oldest_first() {
+        // the logger only exists once set, another test may have set it
+        let _ = logger::init(None);
+        logger::start_capture();
+
+        let mut e = editor("one\n");
+        run(&mut e, "frob");
+        let unknown = e.message.clone().unwrap();
+
+        log::warn!("logged in between");
+        e.on_action(vec![Action::None]);
+        assert_eq!(e.message.as_deref(), Some("WARN: logged in between"));
+
+        run(&mut e, "set ts=x");
+        let invalid = e.message.clone().unwrap();
+
+        run(&mut e, "messages");
+        assert_eq!(
+            e.overlay.as_deref(),
+            Some(&[unknown, String::from("WARN: logged in between"), invalid][..])
+        );
+        assert!(logger::stop_capture().is_empty());
+    }
+
+    #[test]
+    fn taller_command_area_takes_a_row_from_the_view() {
+        let down = |e: &mut CharVectorEditor, times| {
+            for _ in 0..times {
+                e.on_action(vec![Action::Move(Movement::Down)]);
+            }
+        };
+
+        let mut e = editor(&numbered_lines(30));
+        assert_eq!((e.view.top, e.view.bottom), (0, 9));
+        down(&mut e, 6);
+        assert_eq!(e.view.top, 0);
+
+        let mut e = editor(&numbered_lines(30));
+        run(&mut e, "set cmdheight=2");
+        assert_eq!((e.view.top, e.view.bottom), (0, 8));
+        // `scrolloff` counts from the new bottom row
+        down(&mut e, 6);
+        assert_eq!((e.view.top, e.view.bottom), (1, 9));
+        assert_eq!(e.render_cursor(), (6, 0));
+
+        run(&mut e, "set cmdheight=1");
+        assert_eq!((e.view.top, e.view.bottom), (1, 10));
+        run(&mut e, "set cmdheight=11");
+        assert_eq!(
+            e.message.as_deref(),
+            Some("cmdheight is larger than the screen")
+        );
+        assert_eq!(e.options.cmdheight, 1);
+    }
+
+    #[test]
+    fn lines_match_get_line_in_every_backend() {
+        fn check<T: EditorContentTrait>(text: &str) {
+            let e = editor_with::<T>(text);
+            let lines: Vec<String> = e.content.lines().map(|l| l.iter().collect()).collect();
+            let expected: Vec<String> = (0..e.content.line_count())
+                .map(|i| e.content.get_line(i).unwrap())
+                .collect();
+            assert_eq!(lines, expected, "{:?}", text);
+        }
+
+        for text in ["one\n\nthré\r\n", "", "no line break", "\n\n"] {
+            check::<EditorContent<vector::CharVector>>(text);
+            check::<EditorContent<gap::GapBuffer>>(text);
+            check::<EditorContent<rope::Rope>>(text);
+        }
+
+        // the line after the last line break is there, empty
+        let e = editor("one\ntwo\n");
+        assert_eq!(e.content.lines().last().as_deref(), Some(&[][..]));
+        assert_eq!(e.content.lines().count(), 3);
+    }
+
+    #[test]
+    fn buffers_keep_their_own_cursor() {
+        let dir = temp_dir("buffer-cursors");
+        let first = dir.join("first.txt");
+        let second = dir.join("second.txt");
+        fs::write(&first, numbered_lines(30)).unwrap();
+        fs::write(&second, "alpha beta\ngamma\n").unwrap();
+        let mut e = editor("");
+
+        run(&mut e, &format!("e {}", first.display()));
+        e.set_cursor(20, 3);
+        let view_top = e.view.top;
+        run(&mut e, &format!("e {}", second.display()));
+        assert_eq!(e.cursor(), (0, 0));
+        e.set_cursor(1, 2);
+
+        run(&mut e, "b 1");
+        assert_eq!(e.file_path(), Some(first.as_path()));
+        assert_eq!(e.cursor(), (20, 3));
+        assert_eq!(e.view.top, view_top);
+
+        run(&mut e, "bn");
+        assert_eq!(e.file_path(), Some(second.as_path()));
+        assert_eq!(e.cursor(), (1, 2));
+
+        run(&mut e, "b 0");
+        assert_eq!(e.message.as_deref(), Some("buffer 0 does not exist"));
+        run(&mut e, "b 3");
+        assert_eq!(e.message.as_deref(), Some("buffer 3 does not exist"));
+        assert_eq!(e.file_path(), Some(second.as_path()));
+    }
+
+    #[test]
+    fn backupdir_holds_the_backup_instead_of_the_file_directory() {
+        let dir = temp_dir("backupdir");
+        let backups = dir.join("backups");
+        let path = dir.join("notes.txt");
+        fs::write(&path, "before\n").unwrap();
+
+        let mut e = editor("");
+        e.on_load_file(path.to_string_lossy().into_owned());
+        run(&mut e, "set backup");
+        run(&mut e, &format!("set backupdir={}", backups.display()));
+        e.on_action(vec![Action::InsertChar('x')]);
+        run(&mut e, "w");
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "xbefore\n");
+        // the directory is made on the first write
+        assert_eq!(
+            fs::read_to_string(backups.join("notes.txt~")).unwrap(),
+            "before\n"
+        );
+        assert!(!dir.join("notes.txt~").exists());
+    }
+
+    #[test]
+    fn line_len_counts_chars_in_every_backend() {
+        fn check<T: EditorContentTrait>() {
+            let e = editor_with::<T>("héllo\r\n\n日本語\nlast");
+            let lens: Vec<Option<u32>> = (0..5).map(|i| e.content.get_line_len(i)).collect();
+            assert_eq!(lens, [Some(5), Some(0), Some(3), Some(4), None]);
+
+            for i in 0..e.content.line_count() {
+                let line = e.content.get_line(i).unwrap();
+                assert_eq!(e.content.get_line_len(i), Some(line.chars().count() as u32));
+            }
+        }
+
+        check::<EditorContent<vector::CharVector>>();
+        check::<EditorContent<gap::GapBuffer>>();
+        check::<EditorContent<rope::Rope>>();
+    }
+
+    #[test]
+    fn spell_marks_misspelled_words_by_char_column() {
+        let dir = temp_dir("spell");
+        let words = dir.join("words.txt");
+        fs::write(&words, "the\nhéllo\nfox\ndon't\n").unwrap();
+
+        let mut e = editor("héllo the wrold fox, don't Fxo\nthe fox\n");
+        run(&mut e, &format!("set spellfile={}", words.display()));
+        assert!(e.line_highlights(0).is_empty());
+
+        run(&mut e, "set spell");
+        let spans: Vec<_> = e
+            .line_highlights(0)
+            .into_iter()
+            .filter(|(_, h)| *h == Highlight::SpellError)
+            .map(|(range, _)| range)
+            .collect();
+        assert_eq!(spans, [10..15, 27..30]);
+        assert!(e.line_highlights(1).is_empty());
+
+        run(&mut e, "set nospell");
+        assert!(e.line_highlights(0).is_empty());
+    }
+
+    #[test]
+    fn split_windows_keep_their_own_buffer_and_cursor() {
+        let dir = temp_dir("split-windows");
+        let notes = dir.join("notes.txt");
+        fs::write(&notes, "alpha beta\ngamma\n").unwrap();
+        let mut e = editor(&numbered_lines(30));
+        e.set_cursor(12, 4);
+
+        // the new window shows the same, and gets the focus
+        run(&mut e, "vsplit");
+        assert_eq!(e.windows.count(), 2);
+        let [left, right] = e.windows.ids()[..] else {
+            panic!("two windows");
+        };
+        assert_eq!(e.windows.focused(), left);
+        assert_eq!(e.cursor(), (12, 4));
+
+        e.set_cursor(3, 0);
+        e.on_action(vec![Action::Window('w')]);
+        assert_eq!(e.windows.focused(), right);
+        assert_eq!(e.cursor(), (12, 4));
+
+        // a file split open in the right window, above it
+        run(&mut e, &format!("sp {}", notes.display()));
+        assert_eq!(e.windows.count(), 3);
+        assert_eq!(e.file_path(), Some(notes.as_path()));
+        e.set_cursor(1, 2);
+
+        e.on_action(vec![Action::Window('W')]);
+        assert_eq!(e.windows.focused(), left);
+        assert_eq!(e.file_path(), None);
+        assert_eq!(e.cursor(), (3, 0));
+
+        let (content, window) = e.window_content(e.windows.ids()[1]).unwrap();
+        assert_eq!(content.get_line(0).as_deref(), Some("alpha beta"));
+        assert_eq!(window.cursor, (1, 2));
+        assert_eq!(e.buffer_name(window.buffer), notes.to_str().unwrap());
+
+        e.on_action(vec![Action::Window('w')]);
+        assert_eq!(e.file_path(), Some(notes.as_path()));
+        assert_eq!(e.cursor(), (1, 2));
+    }
+
+    #[test]
+    fn only_window_keeps_unsaved_changes_on_screen() {
+        let dir = temp_dir("only-window");
+        let notes = dir.join("notes.txt");
+        fs::write(&notes, "alpha\n").unwrap();
+        let mut e = editor("first\n");
+        run(&mut e, &format!("split {}", notes.display()));
+        e.on_action(vec![Action::InsertChar('x')]);
+        e.on_action(vec![Action::Window('w')]);
+        run(&mut e, "vsplit");
+        assert_eq!(e.windows.count(), 3);
+        assert_eq!(e.content.get_line(0).as_deref(), Some("first"));
+
+        // another window shows changes nobody would see anymore
+        e.on_action(vec![Action::Window('o')]);
+        assert_eq!(e.windows.count(), 3);
+        assert!(e
+            .message
+            .as_deref()
+            .unwrap()
+            .starts_with("no write since last change"));
+
+        run(&mut e, "only!");
+        assert_eq!(e.windows.ids(), [e.windows.focused()]);
+        assert_eq!(e.content.get_line(0).as_deref(), Some("first"));
+    }
+
+    #[test]
+    fn ctrl_w_equal_gives_split_windows_the_same_height() {
+        let area = Container {
+            top: 0,
+            left: 0,
+            bottom: 29,
+            right: 79,
+        };
+        let heights = |e: &CharVectorEditor| -> Vec<u32> {
+            let containers = e.windows.containers(&area);
+            containers.iter().map(|(_, c)| c.get_height() + 1).collect()
+        };
+
+        let mut e = editor("first\n");
+        run(&mut e, "set noequalalways");
+        run(&mut e, "split");
+        run(&mut e, "split");
+        assert_eq!(heights(&e), [7, 8, 15]);
+
+        e.on_action(vec![Action::Window('=')]);
+        assert_eq!(heights(&e), [10, 10, 10]);
+
+        // with `equalalways` the split does it
+        run(&mut e, "set ea");
+        run(&mut e, "split");
+        assert_eq!(heights(&e), [7, 7, 8, 8]);
+    }
+
+    #[test]
+    fn split_file_opens_each_file_in_its_own_window() {
+        let dir = temp_dir("split-file");
+        let (old, new) = (dir.join("old.txt"), dir.join("new.txt"));
+        fs::write(&old, "one\n").unwrap();
+        fs::write(&new, "two\n").unwrap();
+
+        let mut e = editor("");
+        e.on_load_file(new.to_string_lossy().to_string());
+        e.split_file(&old.to_string_lossy());
+
+        assert_eq!(e.windows.count(), 2);
+        assert_eq!(e.file_path(), Some(old.as_path()));
+        let (content, window) = e.window_content(e.windows.ids()[1]).unwrap();
+        assert_eq!(content.get_line(0).as_deref(), Some("two"));
+        assert_eq!(e.buffer_name(window.buffer), new.to_str().unwrap());
+    }
+
+    #[test]
+    fn closing_a_window_gives_the_focus_to_a_neighbour() {
+        let dir = temp_dir("close-window");
+        let notes = dir.join("notes.txt");
+        fs::write(&notes, "alpha\n").unwrap();
+        let mut e = editor("first\nsecond\n");
+        e.set_cursor(1, 0);
+        run(&mut e, "vsplit");
+        run(&mut e, &format!("split {}", notes.display()));
+        let [top, bottom, right] = e.windows.ids()[..] else {
+            panic!("three windows");
+        };
+        assert_eq!(e.windows.focused(), top);
+
+        // changes only this window shows keep it open
+        e.on_action(vec![Action::InsertChar('x')]);
+        e.on_action(vec![Action::Window('c')]);
+        assert_eq!(e.windows.count(), 3);
+        assert!(e
+            .message
+            .as_deref()
+            .unwrap()
+            .starts_with("no write since last change"));
+
+        run(&mut e, "close!");
+        assert_eq!(e.windows.ids(), [bottom, right]);
+        assert_eq!(e.windows.focused(), bottom);
+        assert_eq!(e.file_path(), None);
+
+        e.set_cursor(0, 3);
+        e.on_action(vec![Action::Window('q')]);
+        assert_eq!(e.windows.ids(), [right]);
+        assert_eq!(e.cursor(), (1, 0));
+        assert!(!e.should_quit);
+
+        // the last one can only be quit
+        e.on_action(vec![Action::Window('c')]);
+        assert_eq!(e.message.as_deref(), Some("can't close the last window"));
+
+        // and the closed window's changes are still there to lose
+        run(&mut e, "q");
+        assert!(!e.should_quit);
+        run(&mut e, "q!");
+        assert!(e.should_quit);
+    }
+
+    #[test]
+    fn ctrl_w_t_moves_the_window_to_a_new_tab() {
+        let dir = temp_dir("window-tab");
+        let notes = dir.join("notes.txt");
+        fs::write(&notes, "alpha\n").unwrap();
+        let mut e = editor("first\nsecond\n");
+
+        // alone in its tab, the window stays
+        e.on_action(vec![Action::Window('T')]);
+        assert_eq!(e.tab_count(), 1);
+
+        run(&mut e, "split");
+        run(&mut e, &format!("vsplit {}", notes.display()));
+        e.set_cursor(0, 2);
+        assert_eq!(e.windows.count(), 3);
+
+        e.on_action(vec![Action::Window('T')]);
+        assert_eq!(e.tab_count(), 2);
+        assert_eq!(e.windows.count(), 1);
+        assert_eq!(e.file_path(), Some(notes.as_path()));
+        assert_eq!(e.cursor(), (0, 2));
+        let (labels, current) = e.tab_labels();
+        assert_eq!(labels, ["[No Name]", notes.to_str().unwrap()]);
+        assert_eq!(current, 1);
+
+        // the first tab kept the other two
+        e.on_action(vec![Action::Tab(false)]);
+        assert_eq!(e.windows.count(), 2);
+        assert_eq!(e.file_path(), None);
+        e.on_action(vec![Action::Tab(true)]);
+        assert_eq!(e.file_path(), Some(notes.as_path()));
+
+        // closing its only window closes the tab
+        run(&mut e, "q");
+        assert!(!e.should_quit);
+        assert_eq!(e.tab_count(), 1);
+        assert_eq!(e.windows.count(), 2);
+    }
 }
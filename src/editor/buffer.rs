@@ -0,0 +1,69 @@
+use super::{syntax::Highlighter, undo::UndoTree};
+
+/// An open file kept aside while another one is shown.
+pub struct Buffer<T> {
+    pub content: T,
+    pub file_path: Option<String>,
+    // logical `(row, col)`
+    pub cursor: (u32, u32),
+    // `(left, top)` of the view
+    pub scroll: (u32, u32),
+    pub undo: UndoTree,
+    pub modified: bool,
+    pub directory: bool,
+    pub binary: bool,
+    pub highlighter: Option<Box<dyn Highlighter>>,
+}
+
+/// Every open file but the shown one, which lives in the editor itself.
+/// Buffers are numbered in the order they were opened, the shown one keeping
+/// its place among them as `current`.
+pub struct BufferList<T> {
+    hidden: Vec<Buffer<T>>,
+    current: usize,
+}
+
+impl<T> BufferList<T> {
+    pub fn new() -> Self {
+        Self {
+            hidden: Vec::new(),
+            current: 0,
+        }
+    }
+
+    /// Open buffers, the shown one included.
+    pub fn count(&self) -> usize {
+        self.hidden.len() + 1
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Puts `shown` back in its place and takes out buffer `index` instead.
+    pub fn swap(&mut self, shown: Buffer<T>, index: usize) -> Buffer<T> {
+        self.hidden.insert(self.current, shown);
+        self.current = index;
+        self.hidden.remove(index)
+    }
+
+    /// Keeps `shown` aside, the next buffer shown being a new last one.
+    pub fn push(&mut self, shown: Buffer<T>) {
+        self.hidden.insert(self.current, shown);
+        self.current = self.hidden.len();
+    }
+
+    /// Forgets the shown buffer and takes out buffer `index` instead.
+    pub fn replace_current(&mut self, index: usize) -> Buffer<T> {
+        self.current = index;
+        self.hidden.remove(index)
+    }
+
+    /// The buffers set aside, with their index.
+    pub fn hidden(&self) -> impl Iterator<Item = (usize, &Buffer<T>)> {
+        self.hidden
+            .iter()
+            .enumerate()
+            .map(|(i, buffer)| (if i < self.current { i } else { i + 1 }, buffer))
+    }
+}
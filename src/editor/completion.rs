@@ -0,0 +1,97 @@
+use std::fs;
+
+use crate::utils::expand_home;
+
+const COMMANDS: [&str; 18] = [
+    "bnext",
+    "bprevious",
+    "buffer",
+    "buffers",
+    "close",
+    "edit",
+    "messages",
+    "only",
+    "pad",
+    "quit",
+    "retab",
+    "set",
+    "split",
+    "truncate",
+    "vsplit",
+    "wq",
+    "write",
+    "xit",
+];
+
+/// Completion candidates for the last word of the command line, cycled with Tab.
+pub struct Wildmenu {
+    pub start: usize,
+    pub candidates: Vec<String>,
+    pub selected: usize,
+}
+
+impl Wildmenu {
+    /// Builds the candidates for `command`, or `None` if nothing matches.
+    pub fn new(command: &str, option_names: &[String]) -> Option<Self> {
+        let start = command.rfind(' ').map_or(0, |i| i + 1);
+        let word = &command[start..];
+
+        let mut candidates = match command[..start].split_whitespace().next() {
+            None => COMMANDS.iter().map(|c| c.to_string()).collect(),
+            Some("set" | "se") => option_names.to_vec(),
+            Some("e" | "edit" | "w" | "write" | "sp" | "split" | "vs" | "vsplit") => {
+                list_paths(word)
+            }
+            Some(_) => Vec::new(),
+        };
+
+        candidates.retain(|c| c.starts_with(word));
+        candidates.sort();
+
+        if candidates.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            start,
+            candidates,
+            selected: 0,
+        })
+    }
+
+    pub fn select_next(&mut self, forward: bool) {
+        let len = self.candidates.len();
+        self.selected = match forward {
+            true => (self.selected + 1) % len,
+            false => (self.selected + len - 1) % len,
+        };
+    }
+
+    pub fn current(&self) -> &str {
+        &self.candidates[self.selected]
+    }
+}
+
+fn list_paths(word: &str) -> Vec<String> {
+    let (dir, prefix) = match word.rfind('/') {
+        Some(i) => (&word[..=i], &word[..=i]),
+        None => (".", ""),
+    };
+
+    let show_hidden = word[prefix.len()..].starts_with('.');
+    let Ok(entries) = fs::read_dir(expand_home(dir)) else {
+        return Vec::new();
+    };
+
+    entries
+        .flatten()
+        .filter(|entry| show_hidden || !entry.file_name().to_string_lossy().starts_with('.'))
+        .map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            match entry.file_type().map(|t| t.is_dir()) {
+                Ok(true) => format!("{}{}/", prefix, name),
+                _ => format!("{}{}", prefix, name),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,413 @@
+use std::{borrow::Cow, cmp, io::Write, ops::Range};
+
+use super::{decode_line_endings, Editor, EditorContent, EditorContentTrait, FileFormat};
+
+const MIN_GAP: usize = 64;
+
+pub type GapEditor = Editor<EditorContent<GapBuffer>>;
+
+pub struct GapBuffer {
+    buffer: Vec<char>,
+    gap_start: usize,
+    gap_end: usize,
+    line_count: u32,
+    gap_line: u32,
+    gap_line_start: usize,
+}
+
+impl GapBuffer {
+    fn new() -> Self {
+        Self {
+            buffer: Vec::new(),
+            gap_start: 0,
+            gap_end: 0,
+            line_count: 1,
+            gap_line: 0,
+            gap_line_start: 0,
+        }
+    }
+
+    fn from_chars(chars: Vec<char>) -> Self {
+        let line_count = chars.iter().filter(|c| **c == '\n').count() as u32 + 1;
+
+        Self {
+            buffer: chars,
+            line_count,
+            ..Self::new()
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.buffer.len() - (self.gap_end - self.gap_start)
+    }
+
+    fn get(&self, i: usize) -> Option<char> {
+        if i < self.gap_start {
+            Some(self.buffer[i])
+        } else {
+            self.buffer.get(i + self.gap_end - self.gap_start).copied()
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &char> {
+        self.buffer[..self.gap_start]
+            .iter()
+            .chain(self.buffer[self.gap_end..].iter())
+    }
+
+    fn move_gap(&mut self, pos: usize) {
+        if pos < self.gap_start {
+            let moved = self.gap_start - pos;
+            let crossed_lines = self.buffer[pos..self.gap_start]
+                .iter()
+                .filter(|c| **c == '\n')
+                .count() as u32;
+
+            self.buffer
+                .copy_within(pos..self.gap_start, self.gap_end - moved);
+            self.gap_start -= moved;
+            self.gap_end -= moved;
+            self.gap_line -= crossed_lines;
+        } else if pos > self.gap_start {
+            let moved = pos - self.gap_start;
+            let crossed_lines = self.buffer[self.gap_end..self.gap_end + moved]
+                .iter()
+                .filter(|c| **c == '\n')
+                .count() as u32;
+
+            self.buffer
+                .copy_within(self.gap_end..self.gap_end + moved, self.gap_start);
+            self.gap_start += moved;
+            self.gap_end += moved;
+            self.gap_line += crossed_lines;
+        } else {
+            return;
+        }
+
+        self.gap_line_start = self.buffer[..self.gap_start]
+            .iter()
+            .rposition(|c| *c == '\n')
+            .map_or(0, |i| i + 1);
+    }
+
+    fn grow(&mut self) {
+        let gap_size = cmp::max(MIN_GAP, self.len());
+        let after_gap = self.buffer.len() - self.gap_end;
+
+        self.buffer.resize(self.buffer.len() + gap_size, '\0');
+        self.buffer.copy_within(
+            self.gap_end..self.gap_end + after_gap,
+            self.gap_end + gap_size,
+        );
+        self.gap_end += gap_size;
+    }
+
+    fn insert(&mut self, pos: usize, c: char) {
+        self.move_gap(pos);
+
+        if self.gap_start == self.gap_end {
+            self.grow();
+        }
+
+        self.buffer[self.gap_start] = c;
+        self.gap_start += 1;
+
+        if c == '\n' {
+            self.line_count += 1;
+            self.gap_line += 1;
+            self.gap_line_start = self.gap_start;
+        }
+    }
+
+    fn remove(&mut self, pos: usize) -> Option<char> {
+        self.move_gap(pos);
+
+        let c = *self.buffer.get(self.gap_end)?;
+        self.gap_end += 1;
+
+        if c == '\n' {
+            self.line_count -= 1;
+        }
+
+        Some(c)
+    }
+
+    fn line_start(&self, row: u32) -> Option<usize> {
+        if row >= self.line_count {
+            return None;
+        }
+
+        let mut line = self.gap_line;
+        let mut i = self.gap_line_start;
+
+        while line < row {
+            if self.get(i)? == '\n' {
+                line += 1;
+            }
+            i += 1;
+        }
+
+        while line > row {
+            i -= 1;
+            if i == 0 {
+                line = 0;
+            } else if self.get(i - 1) == Some('\n') {
+                line -= 1;
+            }
+        }
+
+        Some(i)
+    }
+
+    /// The chars in `range`, copied only when the range spans the gap.
+    fn slice(&self, range: Range<usize>) -> Cow<'_, [char]> {
+        let gap_len = self.gap_end - self.gap_start;
+
+        if range.end <= self.gap_start {
+            Cow::Borrowed(&self.buffer[range])
+        } else if range.start >= self.gap_start {
+            Cow::Borrowed(&self.buffer[range.start + gap_len..range.end + gap_len])
+        } else {
+            Cow::Owned(range.filter_map(|i| self.get(i)).collect())
+        }
+    }
+
+    fn line_len_from(&self, start: usize) -> usize {
+        (start..self.len())
+            .take_while(|i| self.get(*i) != Some('\n'))
+            .count()
+    }
+}
+
+impl EditorContent<GapBuffer> {
+    fn get_pos(&self, col: u32, row: u32) -> Option<usize> {
+        let start = self.data.line_start(row)?;
+        let col = cmp::min(col as usize, self.data.line_len_from(start));
+
+        Some(start + col)
+    }
+}
+
+impl EditorContentTrait for EditorContent<GapBuffer> {
+    fn new() -> EditorContent<GapBuffer> {
+        Self {
+            data: GapBuffer::new(),
+            format: FileFormat::default(),
+        }
+    }
+
+    fn load_data(&mut self, raw_data: Vec<u8>) {
+        if let Some(format) = FileFormat::detect(&raw_data) {
+            self.format = format;
+        }
+        self.data = GapBuffer::from_chars(decode_line_endings(&raw_data));
+    }
+
+    fn get_line(&self, i: u32) -> Option<String> {
+        let start = self.data.line_start(i)?;
+        let len = self.data.line_len_from(start);
+
+        (start..start + len).map(|i| self.data.get(i)).collect()
+    }
+
+    fn lines(&self) -> Box<dyn Iterator<Item = Cow<'_, [char]>> + '_> {
+        let mut start = Some(0);
+
+        Box::new(std::iter::from_fn(move || {
+            let line_start = start?;
+            let len = self.data.line_len_from(line_start);
+            let end = line_start + len;
+
+            start = (end < self.data.len()).then_some(end + 1);
+            Some(self.data.slice(line_start..end))
+        }))
+    }
+
+    fn get_line_len(&self, i: u32) -> Option<u32> {
+        let start = self.data.line_start(i)?;
+        Some(self.data.line_len_from(start) as u32)
+    }
+
+    fn line_count(&self) -> u32 {
+        self.data.line_count
+    }
+
+    fn insert_line(&mut self, row: u32, line: &str) {
+        let (pos, text): (usize, Vec<char>) = match self.data.line_start(row) {
+            Some(start) => (start, line.chars().chain(['\n']).collect()),
+            None => (
+                self.data.len(),
+                ['\n'].into_iter().chain(line.chars()).collect(),
+            ),
+        };
+
+        for (offset, c) in text.into_iter().enumerate() {
+            self.data.insert(pos + offset, c);
+        }
+    }
+
+    fn replace_line(&mut self, row: u32, line: &str) {
+        if let Some(start) = self.data.line_start(row) {
+            for _ in 0..self.data.line_len_from(start) {
+                self.data.remove(start);
+            }
+
+            for (offset, c) in line.chars().enumerate() {
+                self.data.insert(start + offset, c);
+            }
+        }
+    }
+
+    fn delete_line(&mut self, row: u32) -> Option<String> {
+        let start = self.data.line_start(row)?;
+        let len = self.data.line_len_from(start);
+        let has_newline = start + len < self.data.len();
+
+        let (from, count) = match (has_newline, start) {
+            (true, _) => (start, len + 1),
+            (false, 0) => (start, len),
+            (false, _) => (start - 1, len + 1),
+        };
+
+        let removed: String = (0..count).filter_map(|_| self.data.remove(from)).collect();
+        Some(removed.chars().filter(|c| *c != '\n').collect())
+    }
+
+    fn write_char(&mut self, c: char, col: u32, row: u32) {
+        if let Some(i) = self.get_pos(col, row) {
+            self.data.insert(i, c);
+        }
+    }
+
+    fn delete_char(&mut self, col: u32, row: u32) -> Option<char> {
+        let i = self.get_pos(col, row)?;
+        if i < self.data.len() {
+            return self.data.remove(i);
+        }
+
+        None
+    }
+
+    fn file_format(&self) -> FileFormat {
+        self.format
+    }
+
+    fn set_file_format(&mut self, format: FileFormat) {
+        self.format = format;
+    }
+
+    fn read_data(&self, buffer: &mut Vec<u8>) {
+        let data_bytes: Vec<u8> = self
+            .data
+            .iter()
+            .map(|c| c.to_string().into_bytes())
+            .flat_map(|c| {
+                if c[0] == 0x0A {
+                    self.format.line_ending().to_vec()
+                } else {
+                    c
+                }
+            })
+            .collect();
+        buffer.write_all(&data_bytes).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::vector::CharVector;
+
+    fn loaded<T: EditorContentTrait>(text: &str) -> T {
+        let mut content = T::new();
+        content.load_data(text.as_bytes().to_vec());
+        content
+    }
+
+    fn all_lines<T: EditorContentTrait>(content: &T) -> Vec<String> {
+        (0..content.line_count())
+            .map(|row| content.get_line(row).unwrap())
+            .collect()
+    }
+
+    fn edit<T: EditorContentTrait>(content: &mut T) {
+        content.write_char('!', 5, 0);
+        content.write_char('\n', 3, 1);
+        content.delete_char(0, 3);
+        content.insert_line(2, "inserted");
+        content.replace_line(4, "replaced");
+        content.delete_line(0);
+        content.write_char('x', 0, 4);
+    }
+
+    #[test]
+    fn edits_match_the_vector_backend() {
+        let text = "first\nsecond line\n\nlast";
+        let mut gap: EditorContent<GapBuffer> = loaded(text);
+        let mut vector: EditorContent<CharVector> = loaded(text);
+
+        edit(&mut gap);
+        edit(&mut vector);
+
+        assert_eq!(all_lines(&gap), all_lines(&vector));
+        assert_eq!(gap.line_count(), vector.line_count());
+        assert_eq!(gap.get_line_len(1), vector.get_line_len(1));
+    }
+
+    #[test]
+    fn typing_in_the_middle_of_a_large_file_leaves_the_gap_in_place() {
+        let text = "some text on a line\n".repeat(20_000);
+        let mut content: EditorContent<GapBuffer> = loaded(&text);
+        let (mut row, mut col) = (10_000, 5);
+
+        // the first char moves the gap to the cursor, the rest are typed into it
+        content.write_char('x', col, row);
+        col += 1;
+        let at = content.data.gap_start;
+        let gap_end = content.data.gap_end;
+
+        for i in 1..2_000 {
+            let c = if i % 10 == 9 { '\n' } else { 'x' };
+            content.write_char(c, col, row);
+            (row, col) = if c == '\n' {
+                (row + 1, 0)
+            } else {
+                (row, col + 1)
+            };
+
+            assert_eq!(content.data.gap_start, at + i);
+            assert_eq!(content.data.gap_end, gap_end);
+            assert_eq!(content.data.gap_line, row);
+        }
+        assert_eq!(content.line_count(), 20_000 + 1 + 200);
+    }
+
+    fn written<T: EditorContentTrait>(content: &T) -> Vec<u8> {
+        let mut data = Vec::new();
+        content.read_data(&mut data);
+        data
+    }
+
+    #[test]
+    fn scattered_edits_write_the_same_file_as_the_vector_backend() {
+        fn scatter<T: EditorContentTrait>(content: &mut T) {
+            // far apart, so the gap moves back and forth
+            content.write_char('y', 9, 1);
+            content.delete_char(0, 4);
+            content.write_char('a', 0, 0);
+            content.delete_char(2, 3);
+            content.write_char('\n', 4, 1);
+            content.delete_char(0, 2);
+            content.write_char(';', 0, 5);
+        }
+
+        let text = "fn main() {\r\n    let x = 1;\r\n\r\n    println!(\"{}\", x);\r\n}\r\n";
+        let mut gap: EditorContent<GapBuffer> = loaded(text);
+        let mut vector: EditorContent<CharVector> = loaded(text);
+
+        scatter(&mut gap);
+        scatter(&mut vector);
+
+        assert_eq!(written(&gap), written(&vector));
+    }
+}
@@ -0,0 +1,534 @@
+use std::cmp;
+
+use super::Container;
+
+/// Names a window for as long as it is open.
+pub type WindowId = usize;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Axis {
+    // one above the other, as `:split` makes them
+    Horizontal,
+    // side by side, with a separator column between them, as `:vsplit` makes them
+    Vertical,
+}
+
+/// What a window shows, kept while another window has the focus; the
+/// focused one lives in the editor itself.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Window {
+    pub buffer: usize,
+    // logical `(row, col)`
+    pub cursor: (u32, u32),
+    // `(left, top)` of the view
+    pub scroll: (u32, u32),
+}
+
+enum Node {
+    Window(WindowId, Window),
+    // the first half is above or left of the second, their sizes in the
+    // ratio of `weights`
+    Split {
+        axis: Axis,
+        weights: (u32, u32),
+        halves: Box<(Node, Node)>,
+    },
+}
+
+impl Node {
+    fn ids(&self, ids: &mut Vec<WindowId>) {
+        match self {
+            Node::Window(id, _) => ids.push(*id),
+            Node::Split { halves, .. } => {
+                halves.0.ids(ids);
+                halves.1.ids(ids);
+            }
+        }
+    }
+
+    fn find_mut(&mut self, id: WindowId) -> Option<&mut Node> {
+        match self {
+            Node::Window(window, _) if *window == id => Some(self),
+            Node::Window(..) => None,
+            Node::Split { halves, .. } => halves.0.find_mut(id).or_else(|| halves.1.find_mut(id)),
+        }
+    }
+
+    fn first_id(&self) -> WindowId {
+        match self {
+            Node::Window(id, _) => *id,
+            Node::Split { halves, .. } => halves.0.first_id(),
+        }
+    }
+
+    fn last_id(&self) -> WindowId {
+        match self {
+            Node::Window(id, _) => *id,
+            Node::Split { halves, .. } => halves.1.last_id(),
+        }
+    }
+
+    /// Takes window `id` out of the tree below `self`, its sibling taking the
+    /// place of their split. Returns the window and the one next to it that
+    /// gets its space.
+    fn remove(&mut self, id: WindowId) -> Option<(Window, WindowId)> {
+        let Node::Split { halves, .. } = self else {
+            return None;
+        };
+
+        let (removed, kept, neighbour) = match &**halves {
+            (Node::Window(first, window), second) if *first == id => {
+                (*window, 1, second.first_id())
+            }
+            (first, Node::Window(second, window)) if *second == id => (*window, 0, first.last_id()),
+            _ => return halves.0.remove(id).or_else(|| halves.1.remove(id)),
+        };
+
+        let halves = std::mem::replace(halves, Box::new((Node::empty(), Node::empty())));
+        *self = if kept == 0 { halves.0 } else { halves.1 };
+        Some((removed, neighbour))
+    }
+
+    // a placeholder while the tree is rearranged
+    fn empty() -> Node {
+        Node::Window(WindowId::MAX, Window::default())
+    }
+
+    /// How many windows are in a row along `axis`, for splits along it to
+    /// give each the same room.
+    fn span(&self, axis: Axis) -> u32 {
+        match self {
+            Node::Window(..) => 1,
+            Node::Split {
+                axis: along,
+                halves,
+                ..
+            } if *along == axis => halves.0.span(axis) + halves.1.span(axis),
+            Node::Split { halves, .. } => cmp::max(halves.0.span(axis), halves.1.span(axis)),
+        }
+    }
+
+    fn equalize(&mut self) {
+        if let Node::Split {
+            axis,
+            weights,
+            halves,
+        } = self
+        {
+            *weights = (halves.0.span(*axis), halves.1.span(*axis));
+            halves.0.equalize();
+            halves.1.equalize();
+        }
+    }
+
+    fn containers(&self, area: Container, containers: &mut Vec<(WindowId, Container)>) {
+        let Node::Split {
+            axis,
+            weights,
+            halves,
+        } = self
+        else {
+            if let Node::Window(id, _) = self {
+                containers.push((*id, area));
+            }
+            return;
+        };
+
+        let (first, second) = match axis {
+            Axis::Horizontal => {
+                let rows = area.get_height() + 1;
+                let above = share(rows, *weights);
+                (
+                    Container {
+                        bottom: area.top + above - 1,
+                        ..area
+                    },
+                    Container {
+                        top: area.top + above,
+                        ..area
+                    },
+                )
+            }
+            Axis::Vertical => {
+                // the column between the two is the separator
+                let columns = area.get_width();
+                let left = share(columns, *weights);
+                (
+                    Container {
+                        right: area.left + left - 1,
+                        ..area
+                    },
+                    Container {
+                        left: area.left + left + 1,
+                        ..area
+                    },
+                )
+            }
+        };
+
+        halves.0.containers(first, containers);
+        halves.1.containers(second, containers);
+    }
+}
+
+/// The part of `size` cells going to the first half, at least one cell each.
+fn share(size: u32, (first, second): (u32, u32)) -> u32 {
+    let part = (size as u64 * first as u64 / cmp::max(first + second, 1) as u64) as u32;
+    part.clamp(1, cmp::max(size.saturating_sub(1), 1))
+}
+
+/// The windows on screen as a tree of splits, each giving two windows, or
+/// further splits, the room of one.
+pub struct Layout {
+    root: Node,
+    focused: WindowId,
+    next_id: WindowId,
+}
+
+impl Layout {
+    pub fn new() -> Self {
+        Self {
+            root: Node::Window(0, Window::default()),
+            focused: 0,
+            next_id: 1,
+        }
+    }
+
+    /// A layout of one window showing what `window` does.
+    pub fn single(window: Window) -> Self {
+        Self {
+            root: Node::Window(0, window),
+            ..Self::new()
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.ids().len()
+    }
+
+    /// Every window, from the top left.
+    pub fn ids(&self) -> Vec<WindowId> {
+        let mut ids = Vec::new();
+        self.root.ids(&mut ids);
+        ids
+    }
+
+    pub fn focused(&self) -> WindowId {
+        self.focused
+    }
+
+    /// Gives the focus to window `id`, false if there is no such window.
+    pub fn focus(&mut self, id: WindowId) -> bool {
+        let found = self.ids().contains(&id);
+        if found {
+            self.focused = id;
+        }
+        found
+    }
+
+    pub fn window(&self, id: WindowId) -> Option<&Window> {
+        let mut nodes = vec![&self.root];
+        while let Some(node) = nodes.pop() {
+            match node {
+                Node::Window(window, state) if *window == id => return Some(state),
+                Node::Window(..) => (),
+                Node::Split { halves, .. } => nodes.extend([&halves.0, &halves.1]),
+            }
+        }
+        None
+    }
+
+    pub fn window_mut(&mut self, id: WindowId) -> Option<&mut Window> {
+        match self.root.find_mut(id)? {
+            Node::Window(_, state) => Some(state),
+            Node::Split { .. } => None,
+        }
+    }
+
+    /// Splits window `id` into two stacked ones, the new one above showing
+    /// the same. Returns the new window.
+    pub fn split_horizontal(&mut self, id: WindowId) -> Option<WindowId> {
+        self.split(id, Axis::Horizontal)
+    }
+
+    /// Splits window `id` into two side by side, the new one on the left
+    /// showing the same. Returns the new window.
+    pub fn split_vertical(&mut self, id: WindowId) -> Option<WindowId> {
+        self.split(id, Axis::Vertical)
+    }
+
+    fn split(&mut self, id: WindowId, axis: Axis) -> Option<WindowId> {
+        let new = self.next_id;
+        let node = self.root.find_mut(id)?;
+        let Node::Window(_, window) = node else {
+            return None;
+        };
+
+        let halves = (Node::Window(new, *window), Node::Window(id, *window));
+        *node = Node::Split {
+            axis,
+            weights: (1, 1),
+            halves: Box::new(halves),
+        };
+
+        self.next_id += 1;
+        Some(new)
+    }
+
+    /// Closes every window but `id`, which takes the whole room and the
+    /// focus, false if there is no such window.
+    pub fn only(&mut self, id: WindowId) -> bool {
+        let Some(window) = self.window(id).copied() else {
+            return false;
+        };
+
+        self.root = Node::Window(id, window);
+        self.focused = id;
+        true
+    }
+
+    /// Closes window `id`, the window next to it getting its room, and the
+    /// focus if it had it. The last window can't be closed.
+    pub fn close(&mut self, id: WindowId) -> Option<Window> {
+        let (window, neighbour) = self.root.remove(id)?;
+        if self.focused == id {
+            self.focused = neighbour;
+        }
+        Some(window)
+    }
+
+    /// Gives every window the same room, as far as the splits allow.
+    pub fn equalize(&mut self) {
+        self.root.equalize();
+    }
+
+    /// The part of `area` each window takes, in the order of `ids`; the
+    /// containers are inclusive, like the view.
+    pub fn containers(&self, area: &Container) -> Vec<(WindowId, Container)> {
+        let mut containers = Vec::new();
+        self.root.containers(*area, &mut containers);
+        containers
+    }
+}
+
+/// Every tab page but the shown one, whose windows live in the editor.
+/// Tabs are numbered from the left, the shown one keeping its place among
+/// them as `current`.
+pub struct TabList {
+    hidden: Vec<Layout>,
+    current: usize,
+}
+
+impl TabList {
+    pub fn new() -> Self {
+        Self {
+            hidden: Vec::new(),
+            current: 0,
+        }
+    }
+
+    /// Tab pages, the shown one included.
+    pub fn count(&self) -> usize {
+        self.hidden.len() + 1
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Puts `shown` back in its place and takes out tab `index` instead.
+    pub fn swap(&mut self, shown: Layout, index: usize) -> Layout {
+        self.hidden.insert(self.current, shown);
+        self.current = index;
+        self.hidden.remove(index)
+    }
+
+    /// Keeps `shown` aside, the next tab shown being a new one after it.
+    pub fn push(&mut self, shown: Layout) {
+        self.hidden.insert(self.current, shown);
+        self.current += 1;
+    }
+
+    /// Forgets the shown tab and takes out tab `index` instead, numbered as
+    /// it is before the shown one goes.
+    pub fn replace_current(&mut self, index: usize) -> Layout {
+        let index = if index > self.current {
+            index - 1
+        } else {
+            index
+        };
+        self.current = index;
+        self.hidden.remove(index)
+    }
+
+    /// The tabs set aside, with their index.
+    pub fn hidden(&self) -> impl Iterator<Item = (usize, &Layout)> {
+        self.hidden
+            .iter()
+            .enumerate()
+            .map(|(i, layout)| (if i < self.current { i } else { i + 1 }, layout))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen(width: u32, height: u32) -> Container {
+        Container {
+            top: 0,
+            left: 0,
+            bottom: height - 1,
+            right: width - 1,
+        }
+    }
+
+    fn edges(layout: &Layout, area: &Container) -> Vec<(u32, u32, u32, u32)> {
+        layout
+            .containers(area)
+            .into_iter()
+            .map(|(_, c)| (c.top, c.left, c.bottom, c.right))
+            .collect()
+    }
+
+    #[test]
+    fn splits_follow_their_axis() {
+        let area = screen(81, 24);
+        let mut layout = Layout::new();
+        assert_eq!(edges(&layout, &area), [(0, 0, 23, 80)]);
+
+        // side by side, the new window on the left of the separator
+        let left = layout.split_vertical(0).unwrap();
+        assert_eq!(layout.ids(), [left, 0]);
+        assert_eq!(edges(&layout, &area), [(0, 0, 23, 39), (0, 41, 23, 80)]);
+
+        // the right one stacked in two, the new one above
+        let top = layout.split_horizontal(0).unwrap();
+        assert_eq!(layout.ids(), [left, top, 0]);
+        assert_eq!(
+            edges(&layout, &area),
+            [(0, 0, 23, 39), (0, 41, 11, 80), (12, 41, 23, 80)]
+        );
+
+        assert_eq!(layout.focused(), 0);
+        assert_eq!(layout.split_horizontal(7), None);
+    }
+
+    #[test]
+    fn new_windows_show_what_the_split_one_did() {
+        let mut layout = Layout::new();
+        *layout.window_mut(0).unwrap() = Window {
+            buffer: 2,
+            cursor: (5, 1),
+            scroll: (0, 3),
+        };
+
+        let new = layout.split_vertical(0).unwrap();
+        assert_eq!(layout.window(new), layout.window(0));
+        assert!(layout.focus(new));
+        assert!(!layout.focus(9));
+        assert_eq!(layout.focused(), new);
+    }
+
+    #[test]
+    fn only_keeps_one_window_with_the_whole_room() {
+        let area = screen(80, 24);
+        let mut layout = Layout::new();
+        let top = layout.split_horizontal(0).unwrap();
+        let left = layout.split_vertical(0).unwrap();
+        layout.window_mut(left).unwrap().buffer = 1;
+
+        assert!(layout.only(left));
+        assert_eq!(layout.ids(), [left]);
+        assert_eq!(layout.focused(), left);
+        assert_eq!(layout.window(left).unwrap().buffer, 1);
+        assert_eq!(edges(&layout, &area), [(0, 0, 23, 79)]);
+
+        assert!(!layout.only(top));
+    }
+
+    #[test]
+    fn equalize_shares_the_room_between_all_windows() {
+        let area = screen(80, 31);
+        let mut layout = Layout::new();
+        layout.split_horizontal(0).unwrap();
+        layout.split_horizontal(0).unwrap();
+        assert_eq!(
+            edges(&layout, &area),
+            [(0, 0, 14, 79), (15, 0, 22, 79), (23, 0, 30, 79)]
+        );
+
+        layout.equalize();
+        assert_eq!(
+            edges(&layout, &area),
+            [(0, 0, 9, 79), (10, 0, 19, 79), (20, 0, 30, 79)]
+        );
+
+        // a window split the other way counts once in the column
+        layout.split_vertical(0).unwrap();
+        layout.equalize();
+        assert_eq!(
+            edges(&layout, &area),
+            [
+                (0, 0, 9, 79),
+                (10, 0, 19, 79),
+                (20, 0, 30, 38),
+                (20, 40, 30, 79)
+            ]
+        );
+    }
+
+    #[test]
+    fn closing_gives_the_room_and_the_focus_to_a_neighbour() {
+        let area = screen(80, 24);
+        let mut layout = Layout::new();
+        let top = layout.split_horizontal(0).unwrap();
+        let left = layout.split_vertical(0).unwrap();
+        layout.focus(left);
+
+        // the bottom row had two windows, the one left takes the whole row
+        assert!(layout.close(left).is_some());
+        assert_eq!(layout.focused(), 0);
+        assert_eq!(layout.ids(), [top, 0]);
+        assert_eq!(edges(&layout, &area), [(0, 0, 11, 79), (12, 0, 23, 79)]);
+
+        assert!(layout.close(0).is_some());
+        assert_eq!(layout.focused(), top);
+        assert_eq!(edges(&layout, &area), [(0, 0, 23, 79)]);
+
+        // the last window stays
+        assert!(layout.close(top).is_none());
+        assert_eq!(layout.count(), 1);
+    }
+
+    #[test]
+    fn tabs_keep_their_place_around_the_shown_one() {
+        let tab = |buffer| {
+            Layout::single(Window {
+                buffer,
+                ..Window::default()
+            })
+        };
+        let buffers = |tabs: &TabList| -> Vec<(usize, usize)> {
+            tabs.hidden()
+                .map(|(i, layout)| (i, layout.window(0).unwrap().buffer))
+                .collect()
+        };
+
+        // tabs 0, 1 and 2, the new ones after the shown one
+        let mut tabs = TabList::new();
+        tabs.push(tab(0));
+        tabs.push(tab(1));
+        assert_eq!((tabs.count(), tabs.current()), (3, 2));
+
+        let shown = tabs.swap(tab(2), 0);
+        assert_eq!(shown.window(0).unwrap().buffer, 0);
+        assert_eq!(buffers(&tabs), [(1, 1), (2, 2)]);
+
+        // tab 0 goes, tab 2 becomes tab 1
+        let shown = tabs.replace_current(2);
+        assert_eq!(shown.window(0).unwrap().buffer, 2);
+        assert_eq!((tabs.count(), tabs.current()), (2, 1));
+        assert_eq!(buffers(&tabs), [(0, 1)]);
+    }
+}
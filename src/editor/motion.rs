@@ -0,0 +1,174 @@
+use super::EditorContentTrait;
+
+/// Walks the content one char at a time, treating each line end as a blank.
+struct Walker<'a, T: EditorContentTrait> {
+    content: &'a T,
+    row: u32,
+    col: u32,
+    line: Vec<char>,
+}
+
+impl<'a, T: EditorContentTrait> Walker<'a, T> {
+    fn new(content: &'a T, row: u32, col: u32) -> Self {
+        let line: Vec<char> = content.get_line(row).unwrap_or_default().chars().collect();
+        let col = col.min(line.len() as u32);
+
+        Self {
+            content,
+            row,
+            col,
+            line,
+        }
+    }
+
+    // 0 for blanks and line ends, 1 for punctuation, 2 for keyword chars
+    fn class(&self) -> u8 {
+        match self.line.get(self.col as usize) {
+            None => 0,
+            Some(c) if c.is_whitespace() => 0,
+            Some(c) if c.is_alphanumeric() || *c == '_' => 2,
+            Some(_) => 1,
+        }
+    }
+
+    // empty lines count as a word of their own, like in vim
+    fn on_empty_line(&self) -> bool {
+        self.line.is_empty()
+    }
+
+    fn load_row(&mut self, row: u32) {
+        self.row = row;
+        self.line = self
+            .content
+            .get_line(row)
+            .unwrap_or_default()
+            .chars()
+            .collect();
+    }
+
+    fn next(&mut self) -> bool {
+        if (self.col as usize) < self.line.len() {
+            self.col += 1;
+        } else if self.row + 1 < self.content.line_count() {
+            self.load_row(self.row + 1);
+            self.col = 0;
+        } else {
+            return false;
+        }
+        true
+    }
+
+    fn prev(&mut self) -> bool {
+        if self.col > 0 {
+            self.col -= 1;
+        } else if self.row > 0 {
+            self.load_row(self.row - 1);
+            self.col = self.line.len() as u32;
+        } else {
+            return false;
+        }
+        true
+    }
+
+    fn position(&self) -> (u32, u32) {
+        (self.row, self.col)
+    }
+}
+
+/// Start of the next word (`w`).
+pub fn word_forward<T: EditorContentTrait>(content: &T, row: u32, col: u32) -> (u32, u32) {
+    let mut walker = Walker::new(content, row, col);
+    let class = walker.class();
+
+    while class != 0 && walker.class() == class {
+        if !walker.next() {
+            return walker.position();
+        }
+    }
+
+    while walker.class() == 0 && !(walker.on_empty_line() && walker.row != row) {
+        if !walker.next() {
+            break;
+        }
+    }
+
+    walker.position()
+}
+
+/// End of the current or next word (`e`).
+pub fn word_end<T: EditorContentTrait>(content: &T, row: u32, col: u32) -> (u32, u32) {
+    let mut walker = Walker::new(content, row, col);
+
+    if !walker.next() {
+        return walker.position();
+    }
+
+    while walker.class() == 0 {
+        if !walker.next() {
+            return walker.position();
+        }
+    }
+
+    let class = walker.class();
+    loop {
+        let end = walker.position();
+        if !walker.next() || walker.class() != class {
+            return end;
+        }
+    }
+}
+
+/// Start of the current or previous word (`b`).
+pub fn word_backward<T: EditorContentTrait>(content: &T, row: u32, col: u32) -> (u32, u32) {
+    let mut walker = Walker::new(content, row, col);
+
+    if !walker.prev() {
+        return walker.position();
+    }
+
+    while walker.class() == 0 && !walker.on_empty_line() {
+        if !walker.prev() {
+            return walker.position();
+        }
+    }
+
+    let class = walker.class();
+    loop {
+        let start = walker.position();
+        if class == 0 || !walker.prev() || walker.class() != class {
+            return start;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::{vector::CharVector, EditorContent};
+
+    fn content(text: &str) -> EditorContent<CharVector> {
+        let mut content = EditorContent::new();
+        content.load_data(text.as_bytes().to_vec());
+        content
+    }
+
+    #[test]
+    fn word_motions_stop_at_punctuation() {
+        let content = content("foo  bar.baz\n  next\n");
+
+        assert_eq!(word_forward(&content, 0, 0), (0, 5));
+        assert_eq!(word_forward(&content, 0, 5), (0, 8));
+        assert_eq!(word_forward(&content, 0, 8), (0, 9));
+        assert_eq!(word_forward(&content, 0, 9), (1, 2));
+
+        assert_eq!(word_end(&content, 0, 0), (0, 2));
+        assert_eq!(word_end(&content, 0, 2), (0, 7));
+        assert_eq!(word_end(&content, 0, 7), (0, 8));
+        assert_eq!(word_end(&content, 0, 8), (0, 11));
+
+        assert_eq!(word_backward(&content, 1, 2), (0, 9));
+        assert_eq!(word_backward(&content, 0, 9), (0, 8));
+        assert_eq!(word_backward(&content, 0, 8), (0, 5));
+        assert_eq!(word_backward(&content, 0, 5), (0, 0));
+    }
+}
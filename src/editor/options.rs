@@ -0,0 +1,343 @@
+const MODELINE_OPTIONS: [&str; 6] = ["tabstop", "ts", "shiftwidth", "sw", "expandtab", "et"];
+
+pub struct Options {
+    pub showcmd: bool,
+    pub backup: bool,
+    pub backupdir: String,
+    pub modeline: bool,
+    pub tabstop: u32,
+    pub shiftwidth: u32,
+    pub expandtab: bool,
+    pub confirm: bool,
+    pub whichwrap: String,
+    pub virtualedit: String,
+    pub spell: bool,
+    pub spellfile: String,
+    pub matchpairs: String,
+    pub updatetime: u32,
+    pub readline: bool,
+    pub maxfilesize: u32,
+    pub wildmenu: bool,
+    pub cmdheight: u32,
+    pub undolevels: u32,
+    pub relativenumber: bool,
+    pub laststatus: u32,
+    pub wrap: bool,
+    pub linebreak: bool,
+    pub readonly: bool,
+    pub scrolloff: u32,
+    pub autosave: bool,
+    pub autosavetime: u32,
+    pub autoindent: bool,
+    pub autopairs: bool,
+    pub cursorline: bool,
+    pub trimws: bool,
+    pub fixendofline: bool,
+    pub inccommand: bool,
+    pub equalalways: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            showcmd: true,
+            backup: false,
+            backupdir: String::from("."),
+            modeline: true,
+            tabstop: 4,
+            shiftwidth: 4,
+            expandtab: false,
+            confirm: false,
+            whichwrap: String::from("b,s"),
+            virtualedit: String::new(),
+            spell: false,
+            spellfile: String::from("/usr/share/dict/words"),
+            matchpairs: String::from("(:),{:},[:]"),
+            updatetime: 4000,
+            readline: false,
+            maxfilesize: 50,
+            wildmenu: true,
+            cmdheight: 1,
+            undolevels: 1000,
+            relativenumber: false,
+            laststatus: 2,
+            wrap: false,
+            linebreak: false,
+            readonly: false,
+            scrolloff: 3,
+            autosave: false,
+            autosavetime: 5,
+            autoindent: true,
+            autopairs: false,
+            cursorline: false,
+            trimws: false,
+            fixendofline: true,
+            inccommand: false,
+            equalalways: true,
+        }
+    }
+}
+
+impl Options {
+    pub fn set(&mut self, arg: &str) -> Result<(), String> {
+        if let Some((name, value)) = arg.split_once("+=") {
+            return self.append_value(name, value);
+        }
+
+        if let Some((name, value)) = arg.split_once('=') {
+            return self.set_value(name, value);
+        }
+
+        let (name, value) = match arg.strip_prefix("no") {
+            Some(name) => (name, false),
+            None => (arg, true),
+        };
+
+        match name {
+            "showcmd" | "sc" => self.showcmd = value,
+            "backup" | "bk" => self.backup = value,
+            "modeline" | "ml" => self.modeline = value,
+            "expandtab" | "et" => self.expandtab = value,
+            "confirm" | "cf" => self.confirm = value,
+            "spell" => self.spell = value,
+            "readline" | "rl" => self.readline = value,
+            "wildmenu" | "wmnu" => self.wildmenu = value,
+            "relativenumber" | "rnu" => self.relativenumber = value,
+            "wrap" => self.wrap = value,
+            "linebreak" | "lbr" => self.linebreak = value,
+            "readonly" | "ro" => self.readonly = value,
+            "autosave" | "as" => self.autosave = value,
+            "autoindent" | "ai" => self.autoindent = value,
+            "autopairs" | "ap" => self.autopairs = value,
+            "cursorline" | "cul" => self.cursorline = value,
+            "trimws" => self.trimws = value,
+            "fixendofline" | "fixeol" => self.fixendofline = value,
+            "inccommand" | "icm" => self.inccommand = value,
+            "equalalways" | "ea" => self.equalalways = value,
+            _ => return Err(format!("unknown option: {}", arg)),
+        }
+
+        Ok(())
+    }
+
+    fn set_value(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "backupdir" | "bdir" => self.backupdir = value.to_string(),
+            "tabstop" | "ts" => self.tabstop = parse_number(name, value)?,
+            "shiftwidth" | "sw" => self.shiftwidth = parse_number(name, value)?,
+            "whichwrap" | "ww" => self.whichwrap = value.to_string(),
+            "virtualedit" | "ve" => self.virtualedit = value.to_string(),
+            "spellfile" | "spf" => self.spellfile = value.to_string(),
+            "undolevels" | "ul" => self.undolevels = parse_number(name, value)?,
+            "cmdheight" | "ch" => self.cmdheight = parse_number(name, value)?,
+            "maxfilesize" | "mfs" => self.maxfilesize = parse_number(name, value)?,
+            "updatetime" | "ut" => self.updatetime = parse_number(name, value)?,
+            "autosavetime" | "ast" => self.autosavetime = parse_number(name, value)?,
+            "scrolloff" | "so" => match value.parse::<u32>() {
+                Ok(n) => self.scrolloff = n,
+                _ => return Err(format!("invalid value for {}: {}", name, value)),
+            },
+            "laststatus" | "ls" => match value.parse::<u32>() {
+                Ok(n) if n <= 2 => self.laststatus = n,
+                _ => return Err(format!("invalid value for {}: {}", name, value)),
+            },
+            "matchpairs" | "mps" => {
+                if parse_pairs(value).is_none() {
+                    return Err(format!("invalid value for {}: {}", name, value));
+                }
+                self.matchpairs = value.to_string();
+            }
+            _ => return Err(format!("unknown option: {}", name)),
+        }
+
+        Ok(())
+    }
+
+    // `+=` adds an item to one of the comma separated lists
+    fn append_value(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let list = match name {
+            "matchpairs" | "mps" => &self.matchpairs,
+            "whichwrap" | "ww" => &self.whichwrap,
+            "virtualedit" | "ve" => &self.virtualedit,
+            _ => return Err(format!("can't add to {}", name)),
+        };
+
+        let joined = match list.is_empty() {
+            true => value.to_string(),
+            false => format!("{},{}", list, value),
+        };
+        self.set_value(name, &joined)
+    }
+
+    pub fn list(&self) -> Vec<String> {
+        let flag = |name: &str, value: bool| {
+            if value {
+                name.to_string()
+            } else {
+                format!("no{}", name)
+            }
+        };
+
+        vec![
+            flag("showcmd", self.showcmd),
+            flag("backup", self.backup),
+            format!("backupdir={}", self.backupdir),
+            flag("modeline", self.modeline),
+            format!("tabstop={}", self.tabstop),
+            format!("shiftwidth={}", self.shiftwidth),
+            flag("expandtab", self.expandtab),
+            flag("confirm", self.confirm),
+            format!("whichwrap={}", self.whichwrap),
+            format!("virtualedit={}", self.virtualedit),
+            flag("spell", self.spell),
+            format!("spellfile={}", self.spellfile),
+            format!("matchpairs={}", self.matchpairs),
+            format!("updatetime={}", self.updatetime),
+            flag("readline", self.readline),
+            format!("maxfilesize={}", self.maxfilesize),
+            flag("wildmenu", self.wildmenu),
+            format!("cmdheight={}", self.cmdheight),
+            format!("undolevels={}", self.undolevels),
+            flag("relativenumber", self.relativenumber),
+            format!("laststatus={}", self.laststatus),
+            flag("wrap", self.wrap),
+            flag("linebreak", self.linebreak),
+            flag("readonly", self.readonly),
+            format!("scrolloff={}", self.scrolloff),
+            flag("autosave", self.autosave),
+            format!("autosavetime={}", self.autosavetime),
+            flag("autoindent", self.autoindent),
+            flag("autopairs", self.autopairs),
+            flag("cursorline", self.cursorline),
+            flag("trimws", self.trimws),
+            flag("fixendofline", self.fixendofline),
+            flag("inccommand", self.inccommand),
+            flag("equalalways", self.equalalways),
+        ]
+    }
+
+    /// Option names as accepted by `:set`, without values or `no` prefixes.
+    pub fn names(&self) -> Vec<String> {
+        self.list()
+            .iter()
+            .map(|entry| {
+                let name = entry.split('=').next().unwrap_or_default();
+                name.strip_prefix("no").unwrap_or(name).to_string()
+            })
+            .collect()
+    }
+
+    /// Whether the status line is shown with a single window; split windows
+    /// always have one each, so `laststatus=1` is off here like `0`.
+    pub fn show_status(&self) -> bool {
+        self.laststatus == 2
+    }
+
+    pub fn virtualedit_all(&self) -> bool {
+        self.virtualedit.split(',').any(|mode| mode == "all")
+    }
+
+    /// The `(open, close)` pairs that `%` jumps between.
+    pub fn match_pairs(&self) -> Vec<(char, char)> {
+        parse_pairs(&self.matchpairs).unwrap_or_default()
+    }
+
+    /// Applies a `vim:`/`ed:` modeline found in `line`, returning whether one was found.
+    /// Only the indentation options are accepted from files.
+    pub fn apply_modeline(&mut self, line: &str) -> bool {
+        let Some(settings) = find_modeline(line) else {
+            return false;
+        };
+
+        let settings = match settings
+            .strip_prefix("set ")
+            .or_else(|| settings.strip_prefix("se "))
+        {
+            Some(rest) => rest.split(':').next().unwrap_or_default(),
+            None => settings,
+        };
+
+        for arg in settings.split(|c: char| c.is_whitespace() || c == ':') {
+            let name = arg.split('=').next().unwrap_or_default();
+            let name = name.strip_prefix("no").unwrap_or(name);
+
+            if arg.is_empty() || !MODELINE_OPTIONS.contains(&name) {
+                continue;
+            }
+
+            let _ = self.set(arg);
+        }
+
+        true
+    }
+}
+
+fn find_modeline(line: &str) -> Option<&str> {
+    ["vim:", "vi:", "ed:"].iter().find_map(|marker| {
+        let start = line.find(marker)?;
+        let before = &line[..start];
+        let preceded_by_blank = before.is_empty() || before.ends_with(char::is_whitespace);
+
+        preceded_by_blank.then(|| line[start + marker.len()..].trim())
+    })
+}
+
+fn parse_pairs(value: &str) -> Option<Vec<(char, char)>> {
+    value
+        .split(',')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let chars: Vec<char> = pair.chars().collect();
+            match chars[..] {
+                [open, ':', close] if open != close => Some((open, close)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn parse_number(name: &str, value: &str) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("invalid value for {}: {}", name, value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modeline_sets_the_indentation_options() {
+        let mut options = Options::default();
+
+        assert!(options.apply_modeline("// vim: ts=2 sw=2 et"));
+        assert_eq!((options.tabstop, options.shiftwidth), (2, 2));
+        assert!(options.expandtab);
+    }
+
+    #[test]
+    fn modeline_ignores_other_options() {
+        let mut options = Options::default();
+
+        assert!(options.apply_modeline("# vim: set backup ts=8:"));
+        assert!(!options.backup);
+        assert_eq!(options.tabstop, 8);
+        assert!(!options.apply_modeline("novim: ts=2"));
+    }
+
+    #[test]
+    fn plus_equals_adds_to_a_list() {
+        let mut options = Options::default();
+
+        options.set("mps+=<:>").unwrap();
+        assert_eq!(options.matchpairs, "(:),{:},[:],<:>");
+        assert_eq!(options.match_pairs().last(), Some(&('<', '>')));
+
+        options.set("ve+=all").unwrap();
+        assert_eq!(options.virtualedit, "all");
+        assert!(options.set("mps+=<>").is_err());
+        assert_eq!(options.matchpairs, "(:),{:},[:],<:>");
+        assert!(options.set("ts+=2").is_err());
+    }
+}
@@ -0,0 +1,486 @@
+use std::{borrow::Cow, cmp, iter, mem, ops::Range};
+
+use super::{line_ending_chars, Editor, EditorContent, EditorContentTrait, FileFormat};
+
+// leaves are split in two past this many chars
+const MAX_LEAF: usize = 1024;
+
+pub type RopeEditor = Editor<EditorContent<Rope>>;
+
+/// A binary tree of char chunks. Every branch keeps the length and line count
+/// of its subtree, so finding an offset or a line only walks down one path.
+pub struct Rope {
+    root: Node,
+}
+
+enum Node {
+    Leaf {
+        chars: Vec<char>,
+        newlines: usize,
+    },
+    Branch {
+        left: Box<Node>,
+        right: Box<Node>,
+        len: usize,
+        newlines: usize,
+        depth: usize,
+    },
+}
+
+impl Node {
+    fn leaf(chars: Vec<char>) -> Self {
+        let newlines = chars.iter().filter(|c| **c == '\n').count();
+        Node::Leaf { chars, newlines }
+    }
+
+    fn branch(left: Node, right: Node) -> Self {
+        Node::Branch {
+            len: left.len() + right.len(),
+            newlines: left.newlines() + right.newlines(),
+            depth: cmp::max(left.depth(), right.depth()) + 1,
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    /// A balanced tree over `leaves`, which must not be empty.
+    fn build(mut leaves: Vec<Node>) -> Self {
+        if leaves.len() == 1 {
+            return leaves.remove(0);
+        }
+
+        let right = leaves.split_off(leaves.len() / 2);
+        Node::branch(Node::build(leaves), Node::build(right))
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Node::Leaf { chars, .. } => chars.len(),
+            Node::Branch { len, .. } => *len,
+        }
+    }
+
+    fn newlines(&self) -> usize {
+        match self {
+            Node::Leaf { newlines, .. } | Node::Branch { newlines, .. } => *newlines,
+        }
+    }
+
+    fn depth(&self) -> usize {
+        match self {
+            Node::Leaf { .. } => 0,
+            Node::Branch { depth, .. } => *depth,
+        }
+    }
+
+    // recomputes the cached sizes of a branch after one of its children changed
+    fn update(&mut self) {
+        if let Node::Branch {
+            left,
+            right,
+            len,
+            newlines,
+            depth,
+        } = self
+        {
+            *len = left.len() + right.len();
+            *newlines = left.newlines() + right.newlines();
+            *depth = cmp::max(left.depth(), right.depth()) + 1;
+        }
+    }
+
+    fn insert(&mut self, pos: usize, c: char) {
+        match self {
+            Node::Leaf { chars, newlines } => {
+                chars.insert(pos, c);
+                if c == '\n' {
+                    *newlines += 1;
+                }
+
+                if chars.len() > MAX_LEAF {
+                    let right = chars.split_off(chars.len() / 2);
+                    let left = mem::take(chars);
+                    *self = Node::branch(Node::leaf(left), Node::leaf(right));
+                }
+            }
+            Node::Branch { left, right, .. } => {
+                if pos <= left.len() {
+                    left.insert(pos, c);
+                } else {
+                    right.insert(pos - left.len(), c);
+                }
+                self.update();
+            }
+        }
+    }
+
+    fn remove(&mut self, pos: usize) -> Option<char> {
+        match self {
+            Node::Leaf { chars, newlines } => {
+                if pos >= chars.len() {
+                    return None;
+                }
+
+                let c = chars.remove(pos);
+                if c == '\n' {
+                    *newlines -= 1;
+                }
+                Some(c)
+            }
+            Node::Branch { left, right, .. } => {
+                let c = if pos < left.len() {
+                    left.remove(pos)
+                } else {
+                    right.remove(pos - left.len())
+                };
+
+                // an emptied child is dropped, its sibling takes the branch's place
+                let remaining = match (left.len(), right.len()) {
+                    (0, _) => Some(mem::replace(&mut **right, Node::leaf(Vec::new()))),
+                    (_, 0) => Some(mem::replace(&mut **left, Node::leaf(Vec::new()))),
+                    _ => None,
+                };
+                match remaining {
+                    Some(node) => *self = node,
+                    None => self.update(),
+                }
+
+                c
+            }
+        }
+    }
+
+    /// Offset of the first char of line `row`, which must exist.
+    fn line_start(&self, row: usize) -> usize {
+        match self {
+            Node::Leaf { chars, .. } => match row {
+                0 => 0,
+                _ => chars
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, c)| **c == '\n')
+                    .nth(row - 1)
+                    .map_or(chars.len(), |(i, _)| i + 1),
+            },
+            Node::Branch { left, right, .. } => {
+                if row <= left.newlines() {
+                    left.line_start(row)
+                } else {
+                    left.len() + right.line_start(row - left.newlines())
+                }
+            }
+        }
+    }
+
+    fn extend_range(&self, range: Range<usize>, out: &mut Vec<char>) {
+        match self {
+            Node::Leaf { chars, .. } => out.extend_from_slice(&chars[range]),
+            Node::Branch { left, right, .. } => {
+                let split = left.len();
+                if range.start < split {
+                    left.extend_range(range.start..cmp::min(range.end, split), out);
+                }
+                if range.end > split {
+                    let start = range.start.saturating_sub(split);
+                    right.extend_range(start..range.end - split, out);
+                }
+            }
+        }
+    }
+
+    fn into_leaves(self, leaves: &mut Vec<Node>) {
+        match self {
+            Node::Leaf { ref chars, .. } if chars.is_empty() => (),
+            Node::Leaf { .. } => leaves.push(self),
+            Node::Branch { left, right, .. } => {
+                left.into_leaves(leaves);
+                right.into_leaves(leaves);
+            }
+        }
+    }
+}
+
+impl Rope {
+    fn new() -> Self {
+        Self {
+            root: Node::leaf(Vec::new()),
+        }
+    }
+
+    fn from_chars(chars: impl Iterator<Item = char>) -> Self {
+        let mut leaves = Vec::new();
+        let mut chunk = Vec::with_capacity(MAX_LEAF / 2);
+
+        for c in chars {
+            chunk.push(c);
+            if chunk.len() == MAX_LEAF / 2 {
+                leaves.push(Node::leaf(mem::replace(
+                    &mut chunk,
+                    Vec::with_capacity(MAX_LEAF / 2),
+                )));
+            }
+        }
+
+        if !chunk.is_empty() || leaves.is_empty() {
+            leaves.push(Node::leaf(chunk));
+        }
+
+        Self {
+            root: Node::build(leaves),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.root.len()
+    }
+
+    fn line_count(&self) -> u32 {
+        self.root.newlines() as u32 + 1
+    }
+
+    fn insert(&mut self, pos: usize, c: char) {
+        self.root.insert(pos, c);
+
+        // typing at one spot keeps splitting the same path, rebuild when it
+        // gets much deeper than a balanced tree would be
+        let leaf_count = self.len() / (MAX_LEAF / 2) + 1;
+        let balanced_depth = (usize::BITS - leaf_count.leading_zeros()) as usize;
+        if self.root.depth() > 2 * balanced_depth + 4 {
+            let mut leaves = Vec::new();
+            mem::replace(&mut self.root, Node::leaf(Vec::new())).into_leaves(&mut leaves);
+            if !leaves.is_empty() {
+                self.root = Node::build(leaves);
+            }
+        }
+    }
+
+    fn remove(&mut self, pos: usize) -> Option<char> {
+        self.root.remove(pos)
+    }
+
+    fn line_start(&self, row: u32) -> Option<usize> {
+        (row < self.line_count()).then(|| self.root.line_start(row as usize))
+    }
+
+    fn line_len(&self, row: u32) -> Option<usize> {
+        let start = self.line_start(row)?;
+        let end = match self.line_start(row + 1) {
+            Some(next) => next - 1,
+            None => self.len(),
+        };
+
+        Some(end - start)
+    }
+
+    fn slice(&self, range: Range<usize>) -> Vec<char> {
+        let mut chars = Vec::with_capacity(range.len());
+        self.root.extend_range(range, &mut chars);
+        chars
+    }
+
+    /// The chunks of text in order.
+    fn leaves(&self) -> impl Iterator<Item = &[char]> {
+        let mut stack = vec![&self.root];
+
+        iter::from_fn(move || loop {
+            match stack.pop()? {
+                Node::Leaf { chars, .. } => return Some(chars.as_slice()),
+                Node::Branch { left, right, .. } => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+            }
+        })
+    }
+}
+
+impl EditorContent<Rope> {
+    fn get_pos(&self, col: u32, row: u32) -> Option<usize> {
+        let start = self.data.line_start(row)?;
+        let len = self.data.line_len(row)?;
+
+        Some(start + cmp::min(col as usize, len))
+    }
+}
+
+impl EditorContentTrait for EditorContent<Rope> {
+    fn new() -> EditorContent<Rope> {
+        Self {
+            data: Rope::new(),
+            format: FileFormat::default(),
+        }
+    }
+
+    fn load_data(&mut self, raw_data: Vec<u8>) {
+        if let Some(format) = FileFormat::detect(&raw_data) {
+            self.format = format;
+        }
+
+        // decoded straight into the leaves, without a copy of the whole file
+        let text = String::from_utf8_lossy(&raw_data);
+        self.data = Rope::from_chars(line_ending_chars(&text));
+    }
+
+    fn get_line(&self, i: u32) -> Option<String> {
+        let start = self.data.line_start(i)?;
+        let len = self.data.line_len(i)?;
+
+        Some(self.data.slice(start..start + len).into_iter().collect())
+    }
+
+    fn lines(&self) -> Box<dyn Iterator<Item = Cow<'_, [char]>> + '_> {
+        let mut row = 0;
+
+        Box::new(iter::from_fn(move || {
+            let start = self.data.line_start(row)?;
+            let len = self.data.line_len(row)?;
+            row += 1;
+
+            Some(Cow::Owned(self.data.slice(start..start + len)))
+        }))
+    }
+
+    fn get_line_len(&self, i: u32) -> Option<u32> {
+        self.data.line_len(i).map(|len| len as u32)
+    }
+
+    fn line_count(&self) -> u32 {
+        self.data.line_count()
+    }
+
+    fn insert_line(&mut self, row: u32, line: &str) {
+        let (pos, text): (usize, Vec<char>) = match self.data.line_start(row) {
+            Some(start) => (start, line.chars().chain(['\n']).collect()),
+            None => (
+                self.data.len(),
+                ['\n'].into_iter().chain(line.chars()).collect(),
+            ),
+        };
+
+        for (offset, c) in text.into_iter().enumerate() {
+            self.data.insert(pos + offset, c);
+        }
+    }
+
+    fn replace_line(&mut self, row: u32, line: &str) {
+        if let (Some(start), Some(len)) = (self.data.line_start(row), self.data.line_len(row)) {
+            for _ in 0..len {
+                self.data.remove(start);
+            }
+
+            for (offset, c) in line.chars().enumerate() {
+                self.data.insert(start + offset, c);
+            }
+        }
+    }
+
+    fn delete_line(&mut self, row: u32) -> Option<String> {
+        let start = self.data.line_start(row)?;
+        let len = self.data.line_len(row)?;
+        let has_newline = start + len < self.data.len();
+
+        let (from, count) = match (has_newline, start) {
+            (true, _) => (start, len + 1),
+            (false, 0) => (start, len),
+            (false, _) => (start - 1, len + 1),
+        };
+
+        let removed: String = (0..count).filter_map(|_| self.data.remove(from)).collect();
+        Some(removed.chars().filter(|c| *c != '\n').collect())
+    }
+
+    fn write_char(&mut self, c: char, col: u32, row: u32) {
+        if let Some(i) = self.get_pos(col, row) {
+            self.data.insert(i, c);
+        }
+    }
+
+    fn delete_char(&mut self, col: u32, row: u32) -> Option<char> {
+        let i = self.get_pos(col, row)?;
+        self.data.remove(i)
+    }
+
+    fn file_format(&self) -> FileFormat {
+        self.format
+    }
+
+    fn set_file_format(&mut self, format: FileFormat) {
+        self.format = format;
+    }
+
+    fn read_data(&self, buffer: &mut Vec<u8>) {
+        let mut utf8 = [0; 4];
+
+        for c in self.data.leaves().flatten() {
+            match c {
+                '\n' => buffer.extend_from_slice(self.format.line_ending()),
+                c => buffer.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::vector::CharVector;
+
+    fn loaded<T: EditorContentTrait>(text: &str) -> T {
+        let mut content = T::new();
+        content.load_data(text.as_bytes().to_vec());
+        content
+    }
+
+    fn written<T: EditorContentTrait>(content: &T) -> Vec<u8> {
+        let mut data = Vec::new();
+        content.read_data(&mut data);
+        data
+    }
+
+    // the same pseudo-random edits on every backend, from a fixed seed
+    fn random_edits<T: EditorContentTrait>(content: &mut T, count: usize) {
+        let mut seed: u64 = 0x2545_f491;
+        let mut next = |bound: u32| {
+            seed = seed
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            ((seed >> 33) % cmp::max(bound, 1) as u64) as u32
+        };
+
+        for _ in 0..count {
+            let row = next(content.line_count());
+            let col = next(content.get_line_len(row).unwrap_or(0) + 1);
+            match next(6) {
+                0 => content.write_char('\n', col, row),
+                1 | 2 => {
+                    content.delete_char(col, row);
+                }
+                3 => content.insert_line(row, "inserted"),
+                4 => content.replace_line(row, "replaced"),
+                5 if content.line_count() > 1 => {
+                    content.delete_line(row);
+                }
+                _ => content.write_char('é', col, row),
+            }
+        }
+    }
+
+    #[test]
+    fn random_edits_match_the_vector_backend() {
+        let text: String = (0..10_000)
+            .map(|i| format!("line {} of the generated text\n", i))
+            .collect();
+        let mut rope: EditorContent<Rope> = loaded(&text);
+        let mut vector: EditorContent<CharVector> = loaded(&text);
+
+        random_edits(&mut rope, 2_000);
+        random_edits(&mut vector, 2_000);
+
+        assert_eq!(rope.line_count(), vector.line_count());
+        for row in (0..vector.line_count()).step_by(97) {
+            assert_eq!(rope.get_line(row), vector.get_line(row));
+            assert_eq!(rope.get_line_len(row), vector.get_line_len(row));
+        }
+        assert_eq!(written(&rope), written(&vector));
+    }
+}
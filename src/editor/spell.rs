@@ -0,0 +1,63 @@
+use std::{collections::HashSet, fs, ops::Range};
+
+pub struct Dictionary {
+    words: HashSet<String>,
+}
+
+impl Dictionary {
+    pub fn load(path: &str) -> Result<Self, std::io::Error> {
+        let words = fs::read_to_string(path)?
+            .lines()
+            .map(|word| word.trim().to_lowercase())
+            .filter(|word| !word.is_empty())
+            .collect();
+
+        Ok(Self { words })
+    }
+
+    // char ranges of the words in `line` missing from the dictionary
+    pub fn misspelled(&self, line: &str) -> Vec<Range<usize>> {
+        let chars: Vec<char> = line.chars().collect();
+        let mut ranges = Vec::new();
+        let mut start = 0;
+
+        while start < chars.len() {
+            if !is_word_char(chars[start]) {
+                start += 1;
+                continue;
+            }
+
+            let end = chars[start..]
+                .iter()
+                .position(|c| !is_word_char(*c))
+                .map_or(chars.len(), |i| start + i);
+            let word = &chars[start..end];
+
+            if !looks_like_code(word) && !self.words.contains(&to_lower(word)) {
+                ranges.push(start..end);
+            }
+
+            start = end;
+        }
+
+        ranges
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '\''
+}
+
+// identifiers, numbers, camelCase and CONSTANTS are not prose
+fn looks_like_code(word: &[char]) -> bool {
+    word.len() < 2
+        || word.iter().any(|c| c.is_numeric() || *c == '_')
+        || word[1..].iter().any(|c| c.is_uppercase())
+}
+
+fn to_lower(word: &[char]) -> String {
+    word.iter()
+        .collect::<String>()
+        .trim_matches('\'')
+        .to_lowercase()
+}
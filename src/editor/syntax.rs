@@ -0,0 +1,97 @@
+use std::{ops::Range, path::Path};
+
+use super::Highlight;
+
+const RUST_KEYWORDS: [&str; 38] = [
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while",
+];
+
+/// Splits a line of one language into highlighted char ranges.
+pub trait Highlighter {
+    fn highlight(&self, line: &str) -> Vec<(Range<usize>, Highlight)>;
+}
+
+/// The highlighter for the language of `path`, going by its extension.
+pub fn for_path(path: &str) -> Option<Box<dyn Highlighter>> {
+    match Path::new(path).extension()?.to_str()? {
+        "rs" => Some(Box::new(Rust)),
+        _ => None,
+    }
+}
+
+/// Keywords, strings, numbers and `//` comments. Each line is read on its
+/// own, so block comments and strings spanning lines aren't recognized.
+pub struct Rust;
+
+impl Highlighter for Rust {
+    fn highlight(&self, line: &str) -> Vec<(Range<usize>, Highlight)> {
+        let chars: Vec<char> = line.chars().collect();
+        let mut highlights = Vec::new();
+        let mut i = 0;
+
+        while i < chars.len() {
+            let start = i;
+
+            match chars[i] {
+                '/' if chars.get(i + 1) == Some(&'/') => {
+                    highlights.push((start..chars.len(), Highlight::Comment));
+                    break;
+                }
+                '"' => {
+                    i += 1;
+                    while i < chars.len() && chars[i] != '"' {
+                        // skips the escaped char, which may be a quote
+                        i += if chars[i] == '\\' { 2 } else { 1 };
+                    }
+                    i = (i + 1).min(chars.len());
+                    highlights.push((start..i, Highlight::String));
+                }
+                c if c.is_ascii_digit() => {
+                    i = word_end(&chars, i);
+                    highlights.push((start..i, Highlight::Number));
+                }
+                c if c.is_alphabetic() || c == '_' => {
+                    i = word_end(&chars, i);
+                    let word: String = chars[start..i].iter().collect();
+
+                    if RUST_KEYWORDS.contains(&word.as_str()) {
+                        highlights.push((start..i, Highlight::Keyword));
+                    }
+                }
+                _ => i += 1,
+            }
+        }
+
+        highlights
+    }
+}
+
+fn word_end(chars: &[char], start: usize) -> usize {
+    chars[start..]
+        .iter()
+        .position(|c| !(c.is_alphanumeric() || *c == '_'))
+        .map_or(chars.len(), |i| start + i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rust_keywords_strings_and_comments() {
+        assert_eq!(Rust.highlight("fn main"), vec![(0..2, Highlight::Keyword)]);
+        assert_eq!(
+            Rust.highlight(r#"let s = "a\"b"; // 1"#),
+            vec![
+                (0..3, Highlight::Keyword),
+                (8..14, Highlight::String),
+                (16..20, Highlight::Comment),
+            ]
+        );
+        assert!(for_path("src/main.rs").is_some());
+        assert!(for_path("notes.txt").is_none());
+    }
+}
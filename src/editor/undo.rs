@@ -0,0 +1,393 @@
+use std::collections::{BTreeMap, HashSet};
+
+use super::EditorContentTrait;
+
+/// A single reversible edit of the content.
+pub enum Change {
+    InsertChar { row: u32, col: u32, c: char },
+    DeleteChar { row: u32, col: u32, c: char },
+    InsertLine { row: u32, line: String },
+    DeleteLine { row: u32, line: String },
+    ReplaceLine { row: u32, old: String, new: String },
+}
+
+impl Change {
+    fn apply<T: EditorContentTrait>(&self, content: &mut T) {
+        match self {
+            Change::InsertChar { row, col, c } => content.write_char(*c, *col, *row),
+            Change::DeleteChar { row, col, .. } => {
+                content.delete_char(*col, *row);
+            }
+            Change::InsertLine { row, line } => content.insert_line(*row, line),
+            Change::DeleteLine { row, .. } => {
+                content.delete_line(*row);
+            }
+            Change::ReplaceLine { row, new, .. } => content.replace_line(*row, new),
+        }
+    }
+
+    fn revert<T: EditorContentTrait>(&self, content: &mut T) {
+        match self {
+            Change::InsertChar { row, col, .. } => {
+                content.delete_char(*col, *row);
+            }
+            Change::DeleteChar { row, col, c } => content.write_char(*c, *col, *row),
+            Change::InsertLine { row, .. } => {
+                content.delete_line(*row);
+            }
+            Change::DeleteLine { row, line } => content.insert_line(*row, line),
+            Change::ReplaceLine { row, old, .. } => content.replace_line(*row, old),
+        }
+    }
+
+    fn position(&self) -> (u32, u32) {
+        match self {
+            Change::InsertChar { row, col, .. } | Change::DeleteChar { row, col, .. } => {
+                (*row, *col)
+            }
+            Change::InsertLine { row, .. }
+            | Change::DeleteLine { row, .. }
+            | Change::ReplaceLine { row, .. } => (*row, 0),
+        }
+    }
+}
+
+/// A visual selection as `(anchor, cursor)`.
+pub type Selection = ((u32, u32), (u32, u32));
+
+/// Where the cursor goes after an undo or redo.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Restore {
+    Cursor(u32, u32),
+    // the group was made from visual mode, its selection is shown again
+    Selection(Selection),
+}
+
+struct Group {
+    changes: Vec<Change>,
+    selection: Option<Selection>,
+}
+
+impl Group {
+    fn position(&self) -> (u32, u32) {
+        self.changes.first().map_or((0, 0), Change::position)
+    }
+
+    fn revert<T: EditorContentTrait>(&self, content: &mut T) {
+        self.changes
+            .iter()
+            .rev()
+            .for_each(|change| change.revert(content));
+    }
+
+    fn apply<T: EditorContentTrait>(&self, content: &mut T) {
+        self.changes.iter().for_each(|change| change.apply(content));
+    }
+}
+
+/// A state of the text, reached from its parent by applying `group`.
+struct Node {
+    parent: Option<usize>,
+    group: Group,
+    // the child `redo` goes to, the last one visited
+    redo: Option<usize>,
+}
+
+/// Undo history as a tree: an edit made after an undo starts a new branch
+/// instead of dropping the undone changes. Every entry is a group of
+/// changes undone together, e.g. everything typed in one insert session,
+/// numbered in the order they were made.
+pub struct UndoTree {
+    // by number, parents always before their children; the first one is the
+    // root, the oldest state still reachable
+    nodes: BTreeMap<usize, Node>,
+    current: usize,
+    next: usize,
+    pending: Vec<Change>,
+    pending_selection: Option<Selection>,
+    // the state written to the file, `None` once it's not in the tree
+    saved: Option<usize>,
+}
+
+impl Default for UndoTree {
+    fn default() -> Self {
+        let root = Node {
+            parent: None,
+            group: Group {
+                changes: Vec::new(),
+                selection: None,
+            },
+            redo: None,
+        };
+
+        Self {
+            nodes: BTreeMap::from([(0, root)]),
+            current: 0,
+            next: 1,
+            pending: Vec::new(),
+            pending_selection: None,
+            saved: Some(0),
+        }
+    }
+}
+
+impl UndoTree {
+    pub fn record(&mut self, change: Change) {
+        self.pending.push(change);
+    }
+
+    /// Records that the current state is the one in the file. Changes not
+    /// committed yet make a state no undo or redo can come back to.
+    pub fn mark_saved(&mut self) {
+        self.saved = self.pending.is_empty().then_some(self.current);
+    }
+
+    /// Records that no state in the tree matches the file any more, e.g.
+    /// after its line endings changed.
+    pub fn forget_saved(&mut self) {
+        self.saved = None;
+    }
+
+    /// Whether the text is back to the state last written to the file.
+    pub fn is_saved(&self) -> bool {
+        self.pending.is_empty() && self.saved == Some(self.current)
+    }
+
+    /// Remembers the selection the next group starts from, until its first change.
+    pub fn set_selection(&mut self, selection: Option<Selection>) {
+        if self.pending.is_empty() {
+            self.pending_selection = selection;
+        }
+    }
+
+    /// Closes the current group as a child of the current state, dropping the
+    /// oldest ones past `limit`.
+    pub fn commit(&mut self, limit: usize) {
+        if self.pending.is_empty() {
+            return;
+        }
+
+        let number = self.next;
+        self.next += 1;
+        self.nodes.insert(
+            number,
+            Node {
+                parent: Some(self.current),
+                group: Group {
+                    changes: std::mem::take(&mut self.pending),
+                    selection: self.pending_selection.take(),
+                },
+                redo: None,
+            },
+        );
+        self.node_mut(self.current).redo = Some(number);
+        self.current = number;
+
+        while self.nodes.len() > limit + 1 {
+            self.drop_oldest();
+        }
+    }
+
+    /// Reverts the current group, returning the selection it was made from
+    /// or where its first change happened.
+    pub fn undo<T: EditorContentTrait>(&mut self, content: &mut T) -> Option<Restore> {
+        let node = &self.nodes[&self.current];
+        let parent = node.parent?;
+        node.group.revert(content);
+
+        let restore = match node.group.selection {
+            Some(selection) => Restore::Selection(selection),
+            None => {
+                let (row, col) = node.group.position();
+                Restore::Cursor(row, col)
+            }
+        };
+        self.current = parent;
+        Some(restore)
+    }
+
+    /// Applies the last undone group again, returning where it starts.
+    pub fn redo<T: EditorContentTrait>(&mut self, content: &mut T) -> Option<Restore> {
+        let child = self.nodes[&self.current].redo?;
+        let group = &self.nodes[&child].group;
+        group.apply(content);
+
+        let (row, col) = group.position();
+        self.current = child;
+        Some(Restore::Cursor(row, col))
+    }
+
+    /// Goes to the state made just before (`g-`) or after (`g+`) the current
+    /// one, whatever branch it's on.
+    pub fn travel<T: EditorContentTrait>(
+        &mut self,
+        content: &mut T,
+        later: bool,
+    ) -> Option<Restore> {
+        let target = match later {
+            true => self.nodes.range(self.current + 1..).next(),
+            false => self.nodes.range(..self.current).next_back(),
+        }
+        .map(|(number, _)| *number)?;
+
+        // up to the common ancestor, then down to the target
+        let ancestors = self.ancestors(target);
+        let mut position = (0, 0);
+        while !ancestors.contains(&self.current) {
+            let node = &self.nodes[&self.current];
+            node.group.revert(content);
+            position = node.group.position();
+            self.current = node.parent?;
+        }
+
+        let common = ancestors.iter().position(|&n| n == self.current);
+        for &number in ancestors[..common.unwrap_or(0)].iter().rev() {
+            let node = &self.nodes[&number];
+            node.group.apply(content);
+            position = node.group.position();
+            if let Some(parent) = node.parent {
+                self.node_mut(parent).redo = Some(number);
+            }
+        }
+        self.current = target;
+
+        Some(Restore::Cursor(position.0, position.1))
+    }
+
+    // `number` and its parents up to the root
+    fn ancestors(&self, number: usize) -> Vec<usize> {
+        std::iter::successors(Some(number), |n| self.nodes[n].parent).collect()
+    }
+
+    fn node_mut(&mut self, number: usize) -> &mut Node {
+        self.nodes.get_mut(&number).expect("undo node exists")
+    }
+
+    // Forgets the oldest group. Its parent can only be the root, so when the
+    // current state depends on it it becomes the new root and the other
+    // branches of the old one go, otherwise its own branch goes.
+    fn drop_oldest(&mut self) {
+        let mut numbers = self.nodes.keys().copied();
+        let (Some(root), Some(oldest)) = (numbers.next(), numbers.next()) else {
+            return;
+        };
+
+        let dropped = match self.ancestors(self.current).contains(&oldest) {
+            true => {
+                self.nodes.remove(&root);
+                if self.saved == Some(root) {
+                    self.saved = None;
+                }
+                let node = self.node_mut(oldest);
+                node.parent = None;
+                node.group.changes.clear();
+                node.group.selection = None;
+                self.subtrees(|number| number != oldest, root)
+            }
+            false => self.subtrees(|number| number == oldest, root),
+        };
+
+        for number in &dropped {
+            self.nodes.remove(number);
+        }
+        if self.saved.is_some_and(|saved| dropped.contains(&saved)) {
+            self.saved = None;
+        }
+        for node in self.nodes.values_mut() {
+            if node.redo.is_some_and(|child| dropped.contains(&child)) {
+                node.redo = None;
+            }
+        }
+    }
+
+    // the children of `parent` picked by `pick`, with all their descendants
+    fn subtrees(&self, pick: impl Fn(usize) -> bool, parent: usize) -> HashSet<usize> {
+        let mut found = HashSet::new();
+        for (&number, node) in &self.nodes {
+            let included = match node.parent {
+                Some(p) if p == parent => pick(number),
+                Some(p) => found.contains(&p),
+                None => false,
+            };
+            if included {
+                found.insert(number);
+            }
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::{vector::CharVector, EditorContent};
+
+    fn content(text: &str) -> EditorContent<CharVector> {
+        let mut content = EditorContent::new();
+        content.load_data(text.as_bytes().to_vec());
+        content
+    }
+
+    fn text(content: &EditorContent<CharVector>) -> String {
+        content.get_line(0).unwrap_or_default()
+    }
+
+    // replaces the first line, as one group
+    fn edit(tree: &mut UndoTree, content: &mut EditorContent<CharVector>, new: &str, limit: usize) {
+        let old = text(content);
+        content.replace_line(0, new);
+        tree.record(Change::ReplaceLine {
+            row: 0,
+            old,
+            new: new.to_string(),
+        });
+        tree.commit(limit);
+    }
+
+    #[test]
+    fn undone_branch_stays_reachable_in_time_order() {
+        let mut tree = UndoTree::default();
+        let mut content = content("zero\n");
+
+        edit(&mut tree, &mut content, "one", 100);
+        edit(&mut tree, &mut content, "two", 100);
+        tree.undo(&mut content);
+        edit(&mut tree, &mut content, "three", 100);
+
+        // plain redo has nothing left on the new branch
+        assert!(tree.redo(&mut content).is_none());
+
+        tree.travel(&mut content, false);
+        assert_eq!(text(&content), "two");
+        tree.travel(&mut content, false);
+        assert_eq!(text(&content), "one");
+        tree.travel(&mut content, false);
+        assert_eq!(text(&content), "zero");
+        assert!(tree.travel(&mut content, false).is_none());
+
+        tree.travel(&mut content, true);
+        tree.travel(&mut content, true);
+        assert_eq!(text(&content), "two");
+        tree.travel(&mut content, true);
+        assert_eq!(text(&content), "three");
+        assert!(tree.travel(&mut content, true).is_none());
+    }
+
+    #[test]
+    fn dropping_old_groups_keeps_the_current_state() {
+        let mut tree = UndoTree::default();
+        let mut content = content("0\n");
+
+        for i in 1..=5 {
+            edit(&mut tree, &mut content, &i.to_string(), 3);
+        }
+        tree.undo(&mut content);
+        edit(&mut tree, &mut content, "6", 3);
+
+        // the groups making 4, 5 and 6 are left, 5 on its own branch
+        while tree.undo(&mut content).is_some() {}
+        assert_eq!(text(&content), "3");
+        while tree.redo(&mut content).is_some() {}
+        assert_eq!(text(&content), "6");
+    }
+}
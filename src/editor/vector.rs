@@ -1,97 +1,314 @@
-use std::io::Write;
+use std::{borrow::Cow, cmp, io::Write, ops::Range};
 
 use crate::utils::is_crlf;
 
-use super::{Editor, EditorContent, EditorContentTrait};
+use super::{decode_line_endings, Editor, EditorContent, EditorContentTrait, FileFormat};
 
-pub type VectorEditor<T> = Editor<EditorContent<Vec<T>>>;
-pub type CharVectorEditor = VectorEditor<char>;
+pub type CharVectorEditor = Editor<EditorContent<CharVector>>;
 
-impl EditorContent<Vec<char>> {
-    fn get_pos(&self, col: u32, row: u32) -> Option<usize> {
-        let mut line_count = 0;
-        let mut col_count = 0;
-        let mut i = 0;
+/// The text as one `Vec<char>`, with the index where each line begins kept
+/// up to date by every edit.
+pub struct CharVector {
+    chars: Vec<char>,
+    line_starts: Vec<usize>,
+}
 
-        while let Some(c_ref) = self.data.get(i) {
-            if line_count == row && col_count == col {
-                break;
-            }
+impl CharVector {
+    fn new() -> Self {
+        Self {
+            chars: Vec::new(),
+            line_starts: vec![0],
+        }
+    }
 
-            i += 1;
-            if *c_ref == '\n' {
-                line_count += 1;
-                col_count = 0;
-            } else {
-                col_count += 1;
-            }
+    fn from_chars(chars: Vec<char>) -> Self {
+        let line_starts = [0]
+            .into_iter()
+            .chain(
+                chars
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, c)| is_crlf(**c))
+                    .map(|(i, _)| i + 1),
+            )
+            .collect();
+
+        Self { chars, line_starts }
+    }
+
+    fn line_start(&self, row: u32) -> Option<usize> {
+        self.line_starts.get(row as usize).copied()
+    }
+
+    fn line_len_from(&self, row: u32, start: usize) -> usize {
+        match self.line_starts.get(row as usize + 1) {
+            Some(next) => next - start - 1,
+            None => self.chars.len() - start,
         }
+    }
 
-        if i <= self.data.len() {
-            Some(i as usize)
-        } else {
-            None
+    // moves the starts of every line after `row` by `delta` chars
+    fn shift_line_starts(&mut self, row: u32, delta: isize) {
+        for start in self.line_starts.iter_mut().skip(row as usize + 1) {
+            *start = start.wrapping_add_signed(delta);
         }
     }
 }
 
-impl EditorContentTrait for EditorContent<Vec<char>> {
-    fn new() -> EditorContent<Vec<char>> {
+impl EditorContent<CharVector> {
+    fn get_pos(&self, col: u32, row: u32) -> Option<usize> {
+        let start = self.data.line_start(row)?;
+        let len = self.data.line_len_from(row, start);
+
+        Some(start + cmp::min(col as usize, len))
+    }
+}
+
+impl EditorContentTrait for EditorContent<CharVector> {
+    fn new() -> EditorContent<CharVector> {
         Self {
-            data: Vec::<char>::new(),
-            is_crlf: true,
+            data: CharVector::new(),
+            format: FileFormat::default(),
         }
     }
 
     fn load_data(&mut self, raw_data: Vec<u8>) {
-        self.data = raw_data
-            .iter()
-            .map(|c| *c as char)
-            .filter(|c| *c != '\r')
-            .collect();
+        if let Some(format) = FileFormat::detect(&raw_data) {
+            self.format = format;
+        }
+        self.data = CharVector::from_chars(decode_line_endings(&raw_data));
     }
 
     fn get_line(&self, i: u32) -> Option<String> {
-        self.data
-            .split(|c| is_crlf(*c))
-            .map(|l| l.iter().collect())
-            .nth(i as usize)
+        let start = self.data.line_start(i)?;
+        let len = self.data.line_len_from(i, start);
+
+        Some(self.data.chars[start..start + len].iter().collect())
+    }
+
+    fn lines(&self) -> Box<dyn Iterator<Item = Cow<'_, [char]>> + '_> {
+        Box::new(self.data.chars.split(|c| is_crlf(*c)).map(Cow::Borrowed))
     }
 
     fn get_line_len(&self, i: u32) -> Option<u32> {
-        Some(self.get_line(i)?.len() as u32)
+        let start = self.data.line_start(i)?;
+        Some(self.data.line_len_from(i, start) as u32)
+    }
+
+    fn line_count(&self) -> u32 {
+        self.data.line_starts.len() as u32
     }
 
     fn write_char(&mut self, c: char, col: u32, row: u32) {
         if let Some(i) = self.get_pos(col, row) {
-            self.data.insert(i, c);
+            self.data.chars.insert(i, c);
+            self.data.shift_line_starts(row, 1);
+
+            if is_crlf(c) {
+                self.data.line_starts.insert(row as usize + 1, i + 1);
+            }
         }
     }
 
+    fn insert_line(&mut self, row: u32, line: &str) {
+        let data = &mut self.data;
+        let len = line.chars().count();
+
+        match data.line_start(row) {
+            Some(start) => {
+                data.chars.splice(start..start, line.chars().chain(['\n']));
+                data.line_starts.insert(row as usize, start);
+                data.shift_line_starts(row, len as isize + 1);
+            }
+            None => {
+                data.chars.push('\n');
+                data.line_starts.push(data.chars.len());
+                data.chars.extend(line.chars());
+            }
+        }
+    }
+
+    fn replace_line(&mut self, row: u32, line: &str) {
+        let data = &mut self.data;
+        if let Some(start) = data.line_start(row) {
+            let old_len = data.line_len_from(row, start);
+            data.chars.splice(start..start + old_len, line.chars());
+            data.shift_line_starts(row, line.chars().count() as isize - old_len as isize);
+        }
+    }
+
+    fn delete_line(&mut self, row: u32) -> Option<String> {
+        let data = &mut self.data;
+        let start = data.line_start(row)?;
+        let len = data.line_len_from(row, start);
+        let line = data.chars[start..start + len].iter().collect();
+
+        if row as usize + 1 < data.line_starts.len() {
+            // the line and the break after it
+            data.chars.drain(start..=start + len);
+            data.line_starts.remove(row as usize);
+            for start in data.line_starts.iter_mut().skip(row as usize) {
+                *start -= len + 1;
+            }
+        } else if row > 0 {
+            // the last line and the break before it
+            data.chars.drain(start - 1..);
+            data.line_starts.pop();
+        } else {
+            data.chars.clear();
+        }
+
+        Some(line)
+    }
+
+    fn delete_lines(&mut self, rows: Range<u32>) -> Vec<String> {
+        let end = cmp::min(rows.end, self.line_count());
+        let Some(start) = self
+            .data
+            .line_start(rows.start)
+            .filter(|_| rows.start < end)
+        else {
+            return Vec::new();
+        };
+        let removed = (rows.start..end)
+            .filter_map(|row| self.get_line(row))
+            .collect();
+
+        // the line break after the last line, or before the first at the end of the file
+        let (from, to) = match self.data.line_start(end) {
+            Some(next) => (start, next),
+            None => (start.saturating_sub(1), self.data.chars.len()),
+        };
+        self.data.chars.drain(from..to);
+
+        self.data
+            .line_starts
+            .drain(rows.start as usize..end as usize);
+        for start in self.data.line_starts.iter_mut().skip(rows.start as usize) {
+            *start -= to - from;
+        }
+        if self.data.line_starts.is_empty() {
+            self.data.line_starts.push(0);
+        }
+
+        removed
+    }
+
     fn delete_char(&mut self, col: u32, row: u32) -> Option<char> {
         if let Some(i) = self.get_pos(col, row) {
-            if i < self.data.len() {
-                return Some(self.data.remove(i));
+            if i < self.data.chars.len() {
+                let c = self.data.chars.remove(i);
+                self.data.shift_line_starts(row, -1);
+
+                if is_crlf(c) {
+                    self.data.line_starts.remove(row as usize + 1);
+                }
+                return Some(c);
             }
         }
 
         None
     }
 
+    fn file_format(&self) -> FileFormat {
+        self.format
+    }
+
+    fn set_file_format(&mut self, format: FileFormat) {
+        self.format = format;
+    }
+
     fn read_data(&self, buffer: &mut Vec<u8>) {
         let data_bytes: Vec<u8> = self
             .data
+            .chars
             .iter()
             .map(|c| c.to_string().into_bytes())
-            .map(|c| {
-                if c[0] == 0x0A && self.is_crlf {
-                    vec![0x0D, 0x0A]
+            .flat_map(|c| {
+                if c[0] == 0x0A {
+                    self.format.line_ending().to_vec()
                 } else {
                     c
                 }
             })
-            .flatten()
             .collect();
-        buffer.write(&data_bytes).unwrap();
+        buffer.write_all(&data_bytes).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loaded(text: &str) -> EditorContent<CharVector> {
+        let mut content = EditorContent::new();
+        content.load_data(text.as_bytes().to_vec());
+        content
+    }
+
+    // the index kept by the edits, against one built from scratch
+    fn assert_index_matches(content: &EditorContent<CharVector>) {
+        let rebuilt = CharVector::from_chars(content.data.chars.clone());
+        assert_eq!(content.data.line_starts, rebuilt.line_starts);
+    }
+
+    #[test]
+    fn line_edits_keep_the_index() {
+        let mut content = loaded("one\ntwo\nthree\n");
+
+        content.insert_line(1, "inserted");
+        assert_index_matches(&content);
+        content.insert_line(content.line_count(), "appended");
+        assert_index_matches(&content);
+        content.replace_line(0, "a longer first line");
+        assert_index_matches(&content);
+        content.replace_line(2, "");
+        assert_index_matches(&content);
+
+        assert_eq!(
+            content.delete_line(0).as_deref(),
+            Some("a longer first line")
+        );
+        assert_index_matches(&content);
+        let last = content.line_count() - 1;
+        assert_eq!(content.delete_line(last).as_deref(), Some("appended"));
+        assert_index_matches(&content);
+
+        while content.line_count() > 1 {
+            content.delete_line(0);
+            assert_index_matches(&content);
+        }
+        assert_eq!(content.delete_line(0).as_deref(), Some(""));
+        assert_index_matches(&content);
+    }
+
+    #[test]
+    fn typing_shifts_only_the_starts_after_the_line() {
+        let mut content = loaded(&"a line of text\n".repeat(1_000));
+        let (row, mut col) = (500, 3);
+
+        for _ in 0..10 {
+            let before = content.data.line_starts.clone();
+            content.write_char('x', col, row);
+            col += 1;
+
+            let (kept, moved) = content.data.line_starts.split_at(row as usize + 1);
+            assert_eq!(kept, &before[..=row as usize]);
+            assert!(moved
+                .iter()
+                .zip(&before[row as usize + 1..])
+                .all(|(a, b)| *a == b + 1));
+        }
+
+        // a newline adds one start, right after it
+        let before = content.data.line_starts.clone();
+        content.write_char('\n', col, row);
+        assert_eq!(content.data.line_starts.len(), before.len() + 1);
+        assert_eq!(
+            content.data.line_starts[row as usize + 1],
+            before[row as usize] + col as usize + 1
+        );
+        assert_eq!(content.get_line_len(row), Some(col));
+        assert_index_matches(&content);
     }
 }
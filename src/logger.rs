@@ -1,14 +1,77 @@
+use std::{
+    cell::RefCell,
+    env,
+    fs::{File, OpenOptions},
+    io::Write,
+    sync::{Mutex, OnceLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
 use ansi_term::Color::*;
-use log::{Level, SetLoggerError};
+use log::{Level, LevelFilter, SetLoggerError};
+
+// name of the environment variable holding the log level
+const LOG_ENV: &str = "EDITOR_LOG";
+const DEFAULT_LEVEL: LevelFilter = LevelFilter::Warn;
 
-struct Logger;
+/// Prints records on the terminal, or appends them to the log file once one is set.
+/// While the editor is drawn the terminal ones are captured instead, so they
+/// don't land in the middle of the text.
+struct Logger {
+    file: OnceLock<Mutex<File>>,
+}
+
+thread_local! {
+    // the records kept while the editor is drawn from this thread
+    static CAPTURED: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
 
 impl log::Log for Logger {
     fn enabled(&self, metadata: &log::Metadata) -> bool {
-        metadata.level() <= Level::Trace
+        metadata.level() <= log::max_level()
     }
 
     fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let location = format!(
+            "{}:{}",
+            record.file().unwrap_or("unknown"),
+            record.line().unwrap_or(0)
+        );
+
+        if let Some(file) = self.file.get() {
+            let time = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+
+            // nowhere to report a failed write, the record is dropped
+            let _ = writeln!(
+                file,
+                "{}.{:03} {} {}: {}",
+                time.as_secs(),
+                time.subsec_millis(),
+                record.level(),
+                location,
+                record.args()
+            );
+            return;
+        }
+
+        let captured = CAPTURED.with_borrow_mut(|captured| match captured {
+            Some(captured) => {
+                captured.push(format!("{}: {}", record.level(), record.args()));
+                true
+            }
+            None => false,
+        });
+        if captured {
+            return;
+        }
+
         let level_colored = match record.level() {
             Level::Error => Red.paint(record.level().as_str()),
             Level::Warn => Yellow.paint(record.level().as_str()),
@@ -17,24 +80,93 @@ impl log::Log for Logger {
             Level::Trace => Black.on(Yellow).bold().paint(record.level().as_str()),
         };
 
-        if self.enabled(record.metadata()) {
-            println!(
-                "{}:{}: {} - {}",
-                record.file().unwrap_or("unknown"),
-                record.line().unwrap_or(0),
-                level_colored,
-                record.args()
-            );
+        println!("{}: {} - {}", location, level_colored, record.args());
+    }
+
+    fn flush(&self) {
+        if let Some(file) = self.file.get() {
+            let _ = file.lock().unwrap_or_else(|e| e.into_inner()).flush();
+        }
+    }
+}
+
+static LOGGER: Logger = Logger {
+    file: OnceLock::new(),
+};
+
+/// Sets the level from `EDITOR_LOG`, `warn` when unset or invalid, and
+/// appends the records to `log_file` when given, the terminal otherwise.
+pub fn init(log_file: Option<&str>) -> Result<(), SetLoggerError> {
+    let level = env_level();
+    let filter = level.clone().unwrap_or(DEFAULT_LEVEL);
+    log::set_logger(&LOGGER).map(|()| log::set_max_level(filter))?;
+
+    if let Some(path) = log_file {
+        match OpenOptions::new().create(true).append(true).open(path) {
+            Ok(file) => {
+                let _ = LOGGER.file.set(Mutex::new(file));
+            }
+            Err(e) => log::error!("can't open log file {}: {}", path, e),
         }
     }
 
-    fn flush(&self) {}
+    if let Err(value) = level {
+        log::warn!(
+            "invalid {} level {:?}, using {}",
+            LOG_ENV,
+            value,
+            DEFAULT_LEVEL
+        );
+    }
+    Ok(())
 }
 
-static LOGGER: Logger = Logger;
+/// Keeps the records meant for the terminal and logged from the calling
+/// thread, until `stop_capture`.
+pub fn start_capture() {
+    CAPTURED.with_borrow_mut(|captured| {
+        captured.get_or_insert_with(Vec::new);
+    });
+}
+
+/// The records captured since the last call, formatted as `LEVEL: message`.
+pub fn take_captured() -> Vec<String> {
+    CAPTURED.with_borrow_mut(|captured| captured.as_mut().map(std::mem::take).unwrap_or_default())
+}
 
-pub fn init() -> Result<(), SetLoggerError> {
-    log::set_logger(&LOGGER).map(|()| 
-        log::set_max_level(log::LevelFilter::Trace)
-    )
+/// Prints the records again, returning the ones captured but not taken yet.
+pub fn stop_capture() -> Vec<String> {
+    CAPTURED.with_borrow_mut(|captured| captured.take().unwrap_or_default())
+}
+
+/// The level named by `EDITOR_LOG`, or the invalid value it holds.
+fn env_level() -> Result<LevelFilter, String> {
+    let value = env::var(LOG_ENV).ok();
+    parse_level(value.as_deref()).map_err(str::to_string)
+}
+
+/// A level name such as `debug` or `off`, case insensitive.
+fn parse_level(value: Option<&str>) -> Result<LevelFilter, &str> {
+    match value {
+        None => Ok(DEFAULT_LEVEL),
+        Some(value) => value.trim().parse().map_err(|_| value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the only test touching the variable, so setting it can't race
+    #[test]
+    fn level_follows_the_environment() {
+        env::set_var(LOG_ENV, "debug");
+        assert_eq!(env_level(), Ok(LevelFilter::Debug));
+        env::set_var(LOG_ENV, " TRACE ");
+        assert_eq!(env_level(), Ok(LevelFilter::Trace));
+        env::set_var(LOG_ENV, "loud");
+        assert_eq!(env_level(), Err(String::from("loud")));
+        env::remove_var(LOG_ENV);
+        assert_eq!(env_level(), Ok(DEFAULT_LEVEL));
+    }
 }
@@ -1,33 +1,159 @@
-use std::env;
+use std::{
+    env,
+    io::{self, IsTerminal, Read},
+};
 
-use client::{console::ConsoleClient, ClientEvent};
-use editor::{vector::CharVectorEditor, Editor, EditorEvent};
+use client::{
+    console::{ConsoleClient, SystemClock, TerminalEvents},
+    keymap::{self, KeyMap},
+    theme::{self, Theme},
+    ClientEvent,
+};
+use editor::{
+    gap::GapEditor, rope::RopeEditor, vector::CharVectorEditor, Editor, EditorContentTrait,
+    EditorEvent,
+};
+use log::error;
+use utils::expand_home;
 
 mod client;
 mod editor;
 mod logger;
 mod utils;
 
-fn main() {
-    logger::init().unwrap();
+/// What the command line asks for.
+#[derive(Default, PartialEq, Debug)]
+struct Args {
+    backend: String,
+    paths: Vec<String>,
+    readonly: bool,
+    log_file: Option<String>,
+    dump_keys: bool,
+    // `--diff` or `-d`, the two files side by side
+    diff: bool,
+}
+
+impl Args {
+    fn parse(args: impl Iterator<Item = String>) -> Self {
+        let mut parsed = Args {
+            backend: String::from("vector"),
+            ..Args::default()
+        };
+
+        for arg in args {
+            match arg.as_str() {
+                "--dump-keys" => parsed.dump_keys = true,
+                "--readonly" | "-R" => parsed.readonly = true,
+                "--diff" | "-d" => parsed.diff = true,
+                _ => {
+                    if let Some(path) = arg.strip_prefix("--log=") {
+                        parsed.log_file = Some(expand_home(path).to_string_lossy().to_string());
+                    } else if let Some(name) = arg.strip_prefix("--backend=") {
+                        parsed.backend = name.to_string();
+                    } else {
+                        parsed.paths.push(arg);
+                    }
+                }
+            }
+        }
 
-    let mut editor: CharVectorEditor = Editor::new();
-    let mut client: ConsoleClient = ConsoleClient::new(true);
+        parsed
+    }
 
-    let mut args = env::args().skip(1);
-    let path_arg = args.next();
+    /// Whether the files open side by side in their own windows rather
+    /// than just the first one, as they do for `--diff` or with two or more.
+    fn vsplit(&self) -> bool {
+        self.diff || self.paths.len() > 1
+    }
+}
+
+fn run<T: EditorContentTrait>(mut editor: Editor<T>, args: Args) {
+    editor.options.readonly = args.readonly;
+
+    // piped text is read before raw mode, keys then come from the terminal itself
+    if args.paths.is_empty() && !io::stdin().is_terminal() {
+        let mut data = Vec::new();
+        match io::stdin().read_to_end(&mut data) {
+            Ok(_) => editor.load_stdin(data),
+            Err(e) => error!("can't read stdin: {}", e),
+        }
+    }
+
+    let keymap = KeyMap::load(&expand_home(keymap::KEYMAP_PATH));
+    let theme = Theme::load(&expand_home(theme::THEME_PATH));
+    let mut client = ConsoleClient::new(
+        true,
+        TerminalEvents,
+        SystemClock,
+        io::stdout(),
+        keymap,
+        theme,
+    );
 
     client.load(&mut editor);
 
-    if let Some(path) = path_arg {
+    // split windows open on the left, so the last file goes first
+    let vsplit = args.vsplit();
+    let mut paths = args.paths.into_iter().rev();
+    if let Some(path) = paths.next() {
         editor.on_load_file(path);
     }
+    if vsplit {
+        for path in paths {
+            editor.split_file(&path);
+        }
+    }
 
     loop {
         client.draw(&editor);
 
-        if let Some(_) = client.update(&mut editor) {
+        if client.update(&mut editor).is_some() {
             break;
         }
     }
 }
+
+fn main() {
+    let args = Args::parse(env::args().skip(1));
+    if args.dump_keys {
+        print!("{}", keymap::starter_config());
+        return;
+    }
+
+    logger::init(args.log_file.as_deref()).unwrap();
+
+    match args.backend.as_str() {
+        "gap" => run::<_>(GapEditor::new(), args),
+        "rope" => run::<_>(RopeEditor::new(), args),
+        _ => run::<_>(CharVectorEditor::new(), args),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Args {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn two_files_open_side_by_side() {
+        let args = parse(&["--diff", "old.txt", "new.txt"]);
+        assert_eq!(args.paths, ["old.txt", "new.txt"]);
+        assert!(args.diff);
+        assert!(args.vsplit());
+
+        // two files are enough, without `--diff`
+        let args = parse(&["-R", "old.txt", "--backend=rope", "new.txt"]);
+        assert_eq!(args.paths, ["old.txt", "new.txt"]);
+        assert_eq!(args.backend, "rope");
+        assert!(args.readonly && !args.diff && args.vsplit());
+
+        let args = parse(&["notes.txt"]);
+        assert_eq!(args.paths, ["notes.txt"]);
+        assert_eq!(args.backend, "vector");
+        assert!(!args.vsplit());
+        assert!(!parse(&[]).vsplit());
+    }
+}
@@ -1,17 +1,53 @@
+use std::{env, path::PathBuf};
+
 pub fn is_crlf(c: char) -> bool {
-    return c == '\n' || c == '\r';
+    c == '\n' || c == '\r'
+}
+
+pub fn is_path_char(c: char) -> bool {
+    c.is_alphanumeric() || "/\\._-~+@:".contains(c)
+}
+
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix('~'), env::var("HOME")) {
+        (Some(rest), Ok(home)) => PathBuf::from(home + rest),
+        _ => PathBuf::from(path),
+    }
 }
 
-pub trait TruncAt {
-    fn trucate_at(&self, size: usize) -> Option<String>;
+/// Width of `text` when it starts at display column `start`, with tabs jumping to the next stop.
+pub fn display_width(text: &str, start: usize, tabstop: usize) -> usize {
+    text.chars().fold(start, |col, c| match c {
+        '\t' => (col / tabstop + 1) * tabstop,
+        _ => col + 1,
+    }) - start
 }
 
-impl TruncAt for str {
-    fn trucate_at(&self, size: usize) -> Option<String> {
-        if size > self.len() {
-            None
-        } else {
-            Some(self.split_at(size).1.to_owned())
-        }
+/// Cuts or pads `text` with spaces to exactly `width` chars.
+pub fn fit_width(text: &str, width: usize) -> String {
+    let mut fitted: String = text.chars().take(width).collect();
+    let len = fitted.chars().count();
+
+    fitted.push_str(&" ".repeat(width - len));
+    fitted
+}
+
+/// The text between the double quotes of a config value, e.g. `"ctrl-s"`.
+pub fn unquote(text: &str) -> Result<&str, String> {
+    text.strip_prefix('"')
+        .and_then(|t| t.strip_suffix('"'))
+        .ok_or(format!("expected a quoted string, found {}", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unquote_needs_both_quotes() {
+        assert_eq!(unquote("\"ctrl-s\""), Ok("ctrl-s"));
+        assert_eq!(unquote("\"\""), Ok(""));
+        assert!(unquote("ctrl-s").is_err());
+        assert!(unquote("\"ctrl-s").is_err());
     }
 }